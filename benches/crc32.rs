@@ -0,0 +1,40 @@
+use bencher::{benchmark_group, benchmark_main};
+
+use std::io::{Cursor, Write};
+
+use bencher::Bencher;
+use getrandom::getrandom;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+const SIZE: usize = 16 * 1024 * 1024;
+
+fn random_bytes() -> Vec<u8> {
+    let mut bytes = vec![0u8; SIZE];
+    getrandom(&mut bytes).unwrap();
+    bytes
+}
+
+fn crc32(bench: &mut Bencher) {
+    let bytes = random_bytes();
+
+    bench.iter(|| crc32fast::hash(&bytes));
+    bench.bytes = SIZE as u64;
+}
+
+fn write_stored_entry(bench: &mut Bencher) {
+    let bytes = random_bytes();
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let mut buffer = Vec::with_capacity(SIZE + 1024);
+
+    bench.iter(|| {
+        buffer.clear();
+        let mut writer = ZipWriter::new(Cursor::new(&mut buffer));
+        writer.start_file("random.dat", options).unwrap();
+        writer.write_all(&bytes).unwrap();
+        writer.finish().unwrap();
+    });
+    bench.bytes = SIZE as u64;
+}
+
+benchmark_group!(benches, crc32, write_stored_entry);
+benchmark_main!(benches);
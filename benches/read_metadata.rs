@@ -39,6 +39,16 @@ fn read_metadata(bench: &mut Bencher) {
     bench.bytes = bytes.len() as u64;
 }
 
+fn list_names_only(bench: &mut Bencher) {
+    let bytes = generate_random_archive(FILE_COUNT, FILE_SIZE).unwrap();
+
+    bench.iter(|| {
+        let names = ZipArchive::list_names_only(Cursor::new(bytes.as_slice())).unwrap();
+        names.len()
+    });
+    bench.bytes = bytes.len() as u64;
+}
+
 const COMMENT_SIZE: usize = 50_000;
 
 fn generate_zip32_archive_with_random_comment(comment_length: usize) -> ZipResult<Vec<u8>> {
@@ -119,6 +129,7 @@ fn parse_stream_archive(bench: &mut Bencher) {
 benchmark_group!(
     benches,
     read_metadata,
+    list_names_only,
     parse_archive_with_comment,
     parse_zip64_archive_with_comment,
     parse_stream_archive,
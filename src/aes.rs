@@ -265,6 +265,10 @@ impl<W: Write> AesWriter<W> {
         })
     }
 
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
     pub fn finish(mut self) -> io::Result<W> {
         self.write_encrypted_file_header()?;
 
@@ -110,11 +110,9 @@ impl CompressionMethod {
         }
     }
 
-    /// Converts a u16 to its corresponding CompressionMethod
-    #[deprecated(
-        since = "0.5.7",
-        note = "use a constant to construct a compression method"
-    )]
+    /// Converts the numeric method code stored in a ZIP header to its corresponding
+    /// CompressionMethod. Codes this build doesn't support are preserved, so that
+    /// [`CompressionMethod::to_u16`] always returns the original value.
     pub const fn from_u16(val: u16) -> CompressionMethod {
         Self::parse_from_u16(val)
     }
@@ -139,14 +137,64 @@ impl CompressionMethod {
         }
     }
 
-    /// Converts a CompressionMethod to a u16
-    #[deprecated(
-        since = "0.5.7",
-        note = "to match on other compression methods, use a constant"
-    )]
+    /// Converts a CompressionMethod to the numeric method code stored in ZIP headers.
     pub const fn to_u16(self) -> u16 {
         self.serialize_to_u16()
     }
+
+    /// Returns the supported compression level closest to `level`, or `None` if this method
+    /// doesn't take a level, either because it doesn't compress or because this build can't
+    /// compress with it.
+    ///
+    /// The supported levels are:
+    /// * `Deflated`: 1 - 9 with flate2, and 10 - 264 with Zopfli, for 1 to 255 iterations
+    /// * `Bzip2`: 1 - 9
+    /// * `Zstd`: -131072 - 22, where negative levels trade ratio for speed
+    ///
+    /// [`ZipWriter::start_file`](crate::ZipWriter::start_file) rejects any other level.
+    ///
+    /// ```
+    /// # #[cfg(feature = "bzip2")] {
+    /// use zip::CompressionMethod;
+    ///
+    /// assert_eq!(CompressionMethod::Bzip2.clamp_level(12), Some(9));
+    /// assert_eq!(CompressionMethod::Stored.clamp_level(12), None);
+    /// # }
+    /// ```
+    pub fn clamp_level(&self, level: i64) -> Option<i64> {
+        let (min, max) = self.level_range()?;
+        Some(level.clamp(min, max))
+    }
+
+    fn level_range(self) -> Option<(i64, i64)> {
+        match self {
+            #[cfg(feature = "_deflate-any")]
+            CompressionMethod::Deflated => {
+                let min = if cfg!(feature = "deflate-flate2") {
+                    1
+                } else {
+                    10
+                };
+                let max = if cfg!(feature = "deflate-zopfli") {
+                    9 + u8::MAX as i64
+                } else {
+                    9
+                };
+                Some((min, max))
+            }
+            #[cfg(feature = "bzip2")]
+            CompressionMethod::Bzip2 => Some((
+                bzip2::Compression::fast().level() as i64,
+                bzip2::Compression::best().level() as i64,
+            )),
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd => {
+                let range = zstd::compression_level_range();
+                Some((*range.start() as i64, *range.end() as i64))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Default for CompressionMethod {
@@ -206,6 +254,42 @@ mod test {
         }
     }
 
+    #[test]
+    fn public_u16_round_trip() {
+        let known = [
+            (CompressionMethod::STORE, 0),
+            (CompressionMethod::SHRINK, 1),
+            (CompressionMethod::REDUCE_1, 2),
+            (CompressionMethod::REDUCE_2, 3),
+            (CompressionMethod::REDUCE_3, 4),
+            (CompressionMethod::REDUCE_4, 5),
+            (CompressionMethod::IMPLODE, 6),
+            (CompressionMethod::DEFLATE, 8),
+            (CompressionMethod::DEFLATE64, 9),
+            (CompressionMethod::PKWARE_IMPLODE, 10),
+            (CompressionMethod::BZIP2, 12),
+            (CompressionMethod::LZMA, 14),
+            (CompressionMethod::IBM_ZOS_CMPSC, 16),
+            (CompressionMethod::IBM_TERSE, 18),
+            (CompressionMethod::ZSTD_DEPRECATED, 20),
+            (CompressionMethod::ZSTD, 93),
+            (CompressionMethod::MP3, 94),
+            (CompressionMethod::XZ, 95),
+            (CompressionMethod::JPEG, 96),
+            (CompressionMethod::WAVPACK, 97),
+            (CompressionMethod::PPMD, 98),
+            (CompressionMethod::AES, 99),
+        ];
+        for (method, code) in known {
+            assert_eq!(method.to_u16(), code);
+            assert_eq!(CompressionMethod::from_u16(code), method);
+        }
+
+        let unknown = CompressionMethod::from_u16(0x1234);
+        assert!(!known.iter().any(|&(method, _)| method == unknown));
+        assert_eq!(unknown.to_u16(), 0x1234);
+    }
+
     #[test]
     fn to_display_fmt() {
         fn check_match(method: CompressionMethod) {
@@ -218,4 +302,26 @@ mod test {
             check_match(method);
         }
     }
+
+    #[test]
+    fn clamp_level() {
+        assert_eq!(CompressionMethod::Stored.clamp_level(5), None);
+        #[cfg(feature = "deflate-flate2")]
+        {
+            assert_eq!(CompressionMethod::Deflated.clamp_level(0), Some(1));
+            assert_eq!(CompressionMethod::Deflated.clamp_level(6), Some(6));
+        }
+        #[cfg(feature = "bzip2")]
+        {
+            assert_eq!(CompressionMethod::Bzip2.clamp_level(-3), Some(1));
+            assert_eq!(CompressionMethod::Bzip2.clamp_level(12), Some(9));
+        }
+        #[cfg(feature = "zstd")]
+        {
+            assert_eq!(CompressionMethod::Zstd.clamp_level(-7), Some(-7));
+            assert_eq!(CompressionMethod::Zstd.clamp_level(30), Some(22));
+        }
+        #[cfg(feature = "lzma")]
+        assert_eq!(CompressionMethod::Lzma.clamp_level(5), None);
+    }
 }
@@ -10,9 +10,10 @@ pub struct Crc32Reader<R> {
     inner: R,
     hasher: Hasher,
     check: u32,
-    /// Signals if `inner` stores aes encrypted data.
-    /// AE-2 encrypted data doesn't use crc and sets the value to 0.
-    ae2_encrypted: bool,
+    /// Signals that the checksum can't be checked, either because `inner` stores AE-2 encrypted
+    /// data, which doesn't use crc and sets the value to 0, or because part of the data was
+    /// skipped.
+    skip_check: bool,
 }
 
 impl<R> Crc32Reader<R> {
@@ -23,12 +24,27 @@ impl<R> Crc32Reader<R> {
             inner,
             hasher: Hasher::new(),
             check: checksum,
-            ae2_encrypted,
+            skip_check: ae2_encrypted,
         }
     }
 
+    /// Stops checking the checksum, since the data won't all be read in order; for example,
+    /// because the inner reader was moved with [`Crc32Reader::get_mut`].
+    pub(crate) fn disable_check(&mut self) {
+        self.skip_check = true;
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// The checksum of the data read so far.
+    pub(crate) fn crc32(&self) -> u32 {
+        self.hasher.clone().finalize()
+    }
+
     fn check_matches(&self) -> bool {
-        self.check == self.hasher.clone().finalize()
+        self.check == self.crc32()
     }
 
     pub fn into_inner(self) -> R {
@@ -38,7 +54,7 @@ impl<R> Crc32Reader<R> {
 
 impl<R: Read> Read for Crc32Reader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let invalid_check = !buf.is_empty() && !self.check_matches() && !self.ae2_encrypted;
+        let invalid_check = !buf.is_empty() && !self.check_matches() && !self.skip_check;
 
         let count = match self.inner.read(buf) {
             Ok(0) if invalid_check => {
@@ -96,4 +112,45 @@ mod test {
         assert_eq!(reader.read(&mut buf[..0]).unwrap(), 0);
         assert_eq!(reader.read(&mut buf).unwrap(), 4);
     }
+
+    /// Bit-by-bit CRC-32 with the reflected polynomial used by ZIP
+    fn reference_crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in data {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xedb88320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn matches_reference() {
+        let mut data = vec![0u8; 64 * 1024];
+        getrandom::getrandom(&mut data).unwrap();
+        assert_eq!(reference_crc32(b"1234"), 0x9be3e0a3);
+        // Odd lengths and offsets exercise the unaligned head and tail of the SIMD path
+        for (start, end) in [(0, 0), (0, 1), (3, 17), (1, 4097), (0, data.len())] {
+            let slice = &data[start..end];
+            let mut hasher = Hasher::new();
+            hasher.update(slice);
+            assert_eq!(hasher.finalize(), reference_crc32(slice), "{start}..{end}");
+
+            let mut reader = Crc32Reader::new(slice, reference_crc32(slice), false);
+            io::copy(&mut reader, &mut io::sink()).unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn hardware_accelerated() {
+        let supported = std::arch::is_x86_feature_detected!("pclmulqdq")
+            && std::arch::is_x86_feature_detected!("sse4.1");
+        assert_eq!(crate::unstable::crc32_is_hardware_accelerated(), supported);
+    }
 }
@@ -17,9 +17,11 @@ impl ExtraFieldVersion for LocalHeaderVersion {}
 impl ExtraFieldVersion for CentralHeaderVersion {}
 
 mod extended_timestamp;
+mod ntfs;
 mod zipinfo_utf8;
 
 pub use extended_timestamp::*;
+pub use ntfs::*;
 pub use zipinfo_utf8::*;
 
 /// contains one extra field
@@ -28,3 +30,14 @@ pub enum ExtraField {
     /// extended timestamp, as described in <https://libzip.org/specifications/extrafld.txt>
     ExtendedTimestamp(ExtendedTimestamp),
 }
+
+/// Extra fields that are parsed, but can't be added to [`ExtraField`] without breaking
+/// exhaustive matches on it
+#[derive(Debug, Clone)]
+pub(crate) enum UnlistedExtraField {
+    /// NTFS file times, as described in APPNOTE 4.5.5
+    Ntfs(Ntfs),
+    /// Korean code page information (0xe57a), naming the Windows code page that the entry's
+    /// name and comment are encoded in when they aren't UTF-8
+    KoreanCodePage(u16),
+}
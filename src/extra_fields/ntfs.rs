@@ -0,0 +1,66 @@
+use crate::result::ZipResult;
+use std::io::Read;
+
+/// NTFS file times, as described in APPNOTE 4.5.5
+///
+/// Each time is a Windows `FILETIME`: the number of 100-nanosecond intervals since
+/// January 1, 1601 (UTC).
+#[derive(Debug, Clone)]
+pub struct Ntfs {
+    mtime: u64,
+    atime: u64,
+    ctime: u64,
+}
+
+impl Ntfs {
+    /// creates an NTFS timestamp struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. All `len` bytes are consumed, and `None` is returned
+    /// if they don't contain a file times attribute (tag 1).
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Option<Self>>
+    where
+        R: Read,
+    {
+        let mut data = vec![0u8; len as usize];
+        reader.read_exact(&mut data)?;
+
+        // 4 reserved bytes, then tagged attributes
+        let mut attributes = data.get(4..).unwrap_or_default();
+        while let [tag_lo, tag_hi, size_lo, size_hi, rest @ ..] = attributes {
+            let tag = u16::from_le_bytes([*tag_lo, *tag_hi]);
+            let size = u16::from_le_bytes([*size_lo, *size_hi]) as usize;
+            if tag == 0x0001 && size >= 24 {
+                if let Some(times) = rest.get(..24) {
+                    let time = |i: usize| {
+                        let mut bytes = [0u8; 8];
+                        bytes.copy_from_slice(&times[i * 8..(i + 1) * 8]);
+                        u64::from_le_bytes(bytes)
+                    };
+                    return Ok(Some(Self {
+                        mtime: time(0),
+                        atime: time(1),
+                        ctime: time(2),
+                    }));
+                }
+            }
+            attributes = rest.get(size..).unwrap_or_default();
+        }
+        Ok(None)
+    }
+
+    /// returns the last modification time, in 100-nanosecond intervals since 1601-01-01 UTC
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    /// returns the last access time, in 100-nanosecond intervals since 1601-01-01 UTC
+    pub fn atime(&self) -> u64 {
+        self.atime
+    }
+
+    /// returns the creation time, in 100-nanosecond intervals since 1601-01-01 UTC
+    pub fn ctime(&self) -> u64 {
+        self.ctime
+    }
+}
@@ -30,7 +30,7 @@
 #![allow(unexpected_cfgs)] // Needed for cfg(fuzzing) on nightly as of 2024-05-06
 pub use crate::compression::{CompressionMethod, SUPPORTED_COMPRESSION_METHODS};
 pub use crate::read::ZipArchive;
-pub use crate::types::{AesMode, DateTime};
+pub use crate::types::{AesMode, AesVendorVersion, DateTime, DeflateLevelHint, System};
 pub use crate::write::ZipWriter;
 
 #[cfg(feature = "aes-crypto")]
@@ -41,6 +41,7 @@ mod compression;
 mod cp437;
 mod crc32;
 pub mod extra_fields;
+pub mod path;
 pub mod read;
 pub mod result;
 mod spec;
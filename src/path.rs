@@ -0,0 +1,114 @@
+//! Conversion of filesystem paths to ZIP entry names.
+
+use crate::result::{ZipError, ZipResult};
+use std::path::Path;
+
+/// Converts a relative filesystem path to the entry name that would identify it in a ZIP archive.
+///
+/// This lets callers predict and validate names before writing them. The rules are the same on
+/// every platform:
+///
+/// * The path must be valid UTF-8 and must not contain NUL characters.
+/// * Both `/` and `\` separate components, and the result always uses `/`.
+/// * Empty components (from repeated or trailing separators) and `.` components are dropped.
+/// * A `..` component removes the component before it. A `..` that would climb above the
+///   starting directory is rejected.
+/// * Absolute paths are rejected. These are paths starting with a separator (which includes
+///   Windows UNC and verbatim paths) and paths starting with a Windows drive letter such as `C:`.
+/// * A path with no components left, such as `""` or `"a/.."`, is rejected.
+///
+/// ```
+/// use std::path::Path;
+/// use zip::path::to_entry_name;
+///
+/// assert_eq!(to_entry_name(Path::new("dir/./sub/../file.txt"))?, "dir/file.txt");
+/// assert_eq!(to_entry_name(Path::new(r"dir\file.txt"))?, "dir/file.txt");
+/// assert!(to_entry_name(Path::new("/etc/passwd")).is_err());
+/// assert!(to_entry_name(Path::new("../file.txt")).is_err());
+/// # Ok::<(), zip::result::ZipError>(())
+/// ```
+pub fn to_entry_name(path: &Path) -> ZipResult<String> {
+    let Some(path) = path.to_str() else {
+        return Err(ZipError::InvalidArchive("Path isn't valid UTF-8"));
+    };
+    if path.contains('\0') {
+        return Err(ZipError::InvalidArchive("Path contains a NUL character"));
+    }
+    if path.starts_with(['/', '\\']) || has_drive_prefix(path) {
+        return Err(ZipError::InvalidArchive("Path is absolute"));
+    }
+    let mut components = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => (),
+            ".." => {
+                if components.pop().is_none() {
+                    return Err(ZipError::InvalidArchive(
+                        "Path refers to a location outside its starting directory",
+                    ));
+                }
+            }
+            _ => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        return Err(ZipError::InvalidArchive("Path has no components"));
+    }
+    Ok(components.join("/"))
+}
+
+fn has_drive_prefix(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_entry_name;
+    use std::path::Path;
+
+    fn entry_name(path: &str) -> Option<String> {
+        to_entry_name(Path::new(path)).ok()
+    }
+
+    #[test]
+    fn unix_paths() {
+        assert_eq!(entry_name("file.txt").as_deref(), Some("file.txt"));
+        assert_eq!(entry_name("dir/sub/file").as_deref(), Some("dir/sub/file"));
+        assert_eq!(entry_name("./dir//file/").as_deref(), Some("dir/file"));
+        assert_eq!(entry_name("dir/sub/../file").as_deref(), Some("dir/file"));
+        assert_eq!(entry_name("dir/...").as_deref(), Some("dir/..."));
+    }
+
+    #[test]
+    fn windows_paths() {
+        assert_eq!(entry_name(r"dir\file.txt").as_deref(), Some("dir/file.txt"));
+        assert_eq!(
+            entry_name(r".\dir\sub\..\file").as_deref(),
+            Some("dir/file")
+        );
+        assert_eq!(entry_name(r"dir/sub\file").as_deref(), Some("dir/sub/file"));
+    }
+
+    #[test]
+    fn rejected_paths() {
+        for path in [
+            "",
+            ".",
+            "dir/..",
+            "..",
+            "../file",
+            "dir/../../file",
+            r"dir\..\..\file",
+            "/etc/passwd",
+            r"\windows\system32",
+            r"C:\windows",
+            "c:file",
+            r"\\server\share\file",
+            r"\\?\C:\file",
+            "nul\0byte",
+        ] {
+            assert_eq!(entry_name(path), None, "{path:?}");
+        }
+    }
+}
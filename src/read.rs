@@ -3,28 +3,35 @@
 #[cfg(feature = "aes-crypto")]
 use crate::aes::{AesReader, AesReaderValid};
 use crate::compression::CompressionMethod;
-use crate::cp437::FromCp437;
 use crate::crc32::Crc32Reader;
-use crate::extra_fields::{ExtendedTimestamp, ExtraField};
+use crate::extra_fields::{ExtendedTimestamp, ExtraField, Ntfs, UnlistedExtraField};
 use crate::read::zip_archive::{Shared, SharedBuilder};
 use crate::result::{ZipError, ZipResult};
-use crate::spec::{self, FixedSizeBlock, Zip32CentralDirectoryEnd, ZIP64_ENTRY_THR};
+use crate::spec::{
+    self, FixedSizeBlock, Zip32CDEBlock, Zip32CentralDirectoryEnd, Zip64CDEBlock,
+    Zip64CDELocatorBlock, ZIP64_ENTRY_THR,
+};
 use crate::types::{
-    AesMode, AesVendorVersion, DateTime, System, ZipCentralEntryBlock, ZipFileData,
-    ZipLocalEntryBlock,
+    AesMode, AesVendorVersion, DateTime, DeflateLevelHint, System, ZipCentralEntryBlock,
+    ZipFileData, ZipLocalEntryBlock,
 };
 use crate::zipcrypto::{ZipCryptoReader, ZipCryptoReaderValid, ZipCryptoValidator};
 use indexmap::IndexMap;
 use std::borrow::Cow;
-use std::ffi::OsString;
-use std::fs::create_dir_all;
-use std::io::{self, copy, prelude::*, sink, SeekFrom};
+use std::collections::VecDeque;
+use std::io::{self, copy, prelude::*, sink, BufReader, Cursor, SeekFrom};
 use std::mem;
 use std::mem::size_of;
-use std::ops::Deref;
-use std::path::{Path, PathBuf};
+use std::num::NonZeroUsize;
+use std::ops::{Deref, Range};
+use std::path::{Component, Path, PathBuf};
 use std::rc::Rc;
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+
+#[cfg(feature = "digest")]
+use digest::{Digest, Output};
+#[cfg(feature = "digest")]
+use std::collections::BTreeMap;
 
 #[cfg(feature = "deflate-flate2")]
 use flate2::read::DeflateDecoder;
@@ -39,8 +46,10 @@ use bzip2::read::BzDecoder;
 use zstd::stream::read::Decoder as ZstdDecoder;
 
 mod config;
+mod extract;
 
 pub use config::*;
+pub use extract::{AppleDoublePolicy, ExtractOptions, Overwrite, UnsafePathPolicy, VirtualFs};
 
 /// Provides high level API for reading from a stream.
 pub(crate) mod stream;
@@ -59,8 +68,9 @@ pub(crate) mod zip_archive {
         pub(crate) files: super::IndexMap<Box<str>, super::ZipFileData>,
         pub(super) offset: u64,
         pub(super) dir_start: u64,
-        // This isn't yet used anywhere, but it is here for use cases in the future.
-        #[allow(dead_code)]
+        pub(super) eocd_start: u64,
+        pub(super) disk_info: super::DiskInfo,
+        #[cfg_attr(not(feature = "_deflate-any"), allow(dead_code))]
         pub(super) config: super::Config,
     }
 
@@ -69,8 +79,8 @@ pub(crate) mod zip_archive {
         pub(crate) files: Vec<super::ZipFileData>,
         pub(super) offset: u64,
         pub(super) dir_start: u64,
-        // This isn't yet used anywhere, but it is here for use cases in the future.
-        #[allow(dead_code)]
+        pub(super) eocd_start: u64,
+        pub(super) disk_info: super::DiskInfo,
         pub(super) config: super::Config,
     }
 
@@ -84,6 +94,8 @@ pub(crate) mod zip_archive {
                 files: index_map,
                 offset: self.offset,
                 dir_start: self.dir_start,
+                eocd_start: self.eocd_start,
+                disk_info: self.disk_info,
                 config: self.config,
             }
         }
@@ -124,17 +136,56 @@ use crate::extra_fields::UnicodeExtraField;
 use crate::read::lzma::LzmaDecoder;
 use crate::result::ZipError::{InvalidArchive, InvalidPassword, UnsupportedArchive};
 use crate::spec::is_dir;
-use crate::types::ffi::S_IFLNK;
+use crate::types::ffi::{S_IFDIR, S_IFLNK};
 use crate::unstable::{path_to_string, LittleEndianReadExt};
 pub use zip_archive::ZipArchive;
 
+/// The reader that an entry's data comes from. It can only seek if the entry was found through
+/// the central directory of a seekable reader, rather than in a stream.
+pub(crate) trait EntryReader: Read {
+    fn try_seek(&mut self, pos: SeekFrom) -> io::Result<u64>;
+}
+
+/// An [`EntryReader`] over a reader that can seek
+struct SeekableEntryReader<R>(R);
+
+impl<R: Read> Read for SeekableEntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R: Read + Seek> EntryReader for SeekableEntryReader<R> {
+    fn try_seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+/// An [`EntryReader`] over a stream, which can't seek
+struct StreamEntryReader<R>(R);
+
+impl<R: Read> Read for StreamEntryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R: Read> EntryReader for StreamEntryReader<R> {
+    fn try_seek(&mut self, _: SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Can't seek within an entry read from a stream",
+        ))
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum CryptoReader<'a> {
-    Plaintext(io::Take<&'a mut dyn Read>),
-    ZipCrypto(ZipCryptoReaderValid<io::Take<&'a mut dyn Read>>),
+    Plaintext(io::Take<Box<dyn EntryReader + 'a>>),
+    ZipCrypto(ZipCryptoReaderValid<io::Take<Box<dyn EntryReader + 'a>>>),
     #[cfg(feature = "aes-crypto")]
     Aes {
-        reader: AesReaderValid<io::Take<&'a mut dyn Read>>,
+        reader: AesReaderValid<io::Take<Box<dyn EntryReader + 'a>>>,
         vendor_version: AesVendorVersion,
     },
 }
@@ -152,7 +203,7 @@ impl<'a> Read for CryptoReader<'a> {
 
 impl<'a> CryptoReader<'a> {
     /// Consumes this decoder, returning the underlying reader.
-    pub fn into_inner(self) -> io::Take<&'a mut dyn Read> {
+    pub fn into_inner(self) -> io::Take<Box<dyn EntryReader + 'a>> {
         match self {
             CryptoReader::Plaintext(r) => r,
             CryptoReader::ZipCrypto(r) => r.into_inner(),
@@ -178,7 +229,7 @@ impl<'a> CryptoReader<'a> {
 
 pub(crate) enum ZipFileReader<'a> {
     NoReader,
-    Raw(io::Take<&'a mut dyn Read>),
+    Raw(io::Take<Box<dyn EntryReader + 'a>>),
     Stored(Crc32Reader<CryptoReader<'a>>),
     #[cfg(feature = "_deflate-any")]
     Deflated(Crc32Reader<DeflateDecoder<CryptoReader<'a>>>),
@@ -213,6 +264,42 @@ impl<'a> Read for ZipFileReader<'a> {
 }
 
 impl<'a> ZipFileReader<'a> {
+    /// Stops checking the checksum given when the reader was made.
+    fn disable_check(&mut self) {
+        match self {
+            ZipFileReader::NoReader | ZipFileReader::Raw(_) => {}
+            ZipFileReader::Stored(r) => r.disable_check(),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::Deflated(r) => r.disable_check(),
+            #[cfg(feature = "deflate64")]
+            ZipFileReader::Deflate64(r) => r.disable_check(),
+            #[cfg(feature = "bzip2")]
+            ZipFileReader::Bzip2(r) => r.disable_check(),
+            #[cfg(feature = "zstd")]
+            ZipFileReader::Zstd(r) => r.disable_check(),
+            #[cfg(feature = "lzma")]
+            ZipFileReader::Lzma(r) => r.disable_check(),
+        }
+    }
+
+    /// The checksum of the data decompressed so far, if it's being computed.
+    fn crc32(&self) -> Option<u32> {
+        match self {
+            ZipFileReader::NoReader | ZipFileReader::Raw(_) => None,
+            ZipFileReader::Stored(r) => Some(r.crc32()),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::Deflated(r) => Some(r.crc32()),
+            #[cfg(feature = "deflate64")]
+            ZipFileReader::Deflate64(r) => Some(r.crc32()),
+            #[cfg(feature = "bzip2")]
+            ZipFileReader::Bzip2(r) => Some(r.crc32()),
+            #[cfg(feature = "zstd")]
+            ZipFileReader::Zstd(r) => Some(r.crc32()),
+            #[cfg(feature = "lzma")]
+            ZipFileReader::Lzma(r) => Some(r.crc32()),
+        }
+    }
+
     /// Consumes this decoder, returning the underlying reader.
     pub fn drain(self) {
         let mut inner = match self {
@@ -246,12 +333,18 @@ pub struct ZipFile<'a> {
     pub(crate) data: Cow<'a, ZipFileData>,
     pub(crate) crypto_reader: Option<CryptoReader<'a>>,
     pub(crate) reader: ZipFileReader<'a>,
+    /// Where the data descriptor is recorded once found, for an entry being streamed by
+    /// [`read_zipfile_from_stream_with_data_descriptor`]
+    pub(crate) data_descriptor: Option<Arc<OnceLock<DataDescriptor>>>,
+    /// Whether this entry was read by a streaming reader, which needs the rest of its data
+    /// consumed when it's dropped so that the next entry is accessible
+    pub(crate) streaming: bool,
 }
 
 pub(crate) fn find_content<'a>(
     data: &ZipFileData,
     reader: &'a mut (impl Read + Seek),
-) -> ZipResult<io::Take<&'a mut dyn Read>> {
+) -> ZipResult<io::Take<Box<dyn EntryReader + 'a>>> {
     // TODO: use .get_or_try_init() once stabilized to provide a closure returning a Result!
     let data_start = match data.data_start.get() {
         Some(data_start) => *data_start,
@@ -259,7 +352,72 @@ pub(crate) fn find_content<'a>(
     };
 
     reader.seek(io::SeekFrom::Start(data_start))?;
-    Ok((reader as &mut dyn Read).take(data.compressed_size))
+    let reader = SeekableEntryReader(reader);
+    Ok((Box::new(reader) as Box<dyn EntryReader + 'a>).take(data.compressed_size))
+}
+
+/// Some streaming writers leave the sizes and CRC-32 in the central directory as zero when
+/// they're given in a data descriptor. For such an entry, returns a copy with the values from its
+/// signed data descriptor, unless what follows the start of its data shows it's really empty.
+fn recover_data_descriptor_sizes(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek),
+) -> ZipResult<Option<ZipFileData>> {
+    if !data.using_data_descriptor
+        || data.is_dir()
+        || data.compressed_size != 0
+        || data.uncompressed_size != 0
+        || data.crc32 != 0
+    {
+        return Ok(None);
+    }
+    // The layout of the data descriptor follows the local header, whose extra fields may differ
+    // from the central header's
+    reader.seek(SeekFrom::Start(data.header_start))?;
+    let block = ZipLocalEntryBlock::parse(reader)?;
+    reader.seek(SeekFrom::Current(block.file_name_length.into()))?;
+    let mut local_extra_field = vec![0u8; block.extra_field_length.into()];
+    reader.read_exact(&mut local_extra_field)?;
+    let large_file = extra_field_has_zip64(&local_extra_field);
+    let mut reader = BufReader::new(reader);
+    let mut first_bytes = [0u8; mem::size_of::<spec::Magic>()];
+    reader.read_exact(&mut first_bytes)?;
+    match spec::Magic::from_first_le_bytes(&first_bytes) {
+        // An empty entry whose data descriptor has no signature, or was left out
+        spec::Magic::LOCAL_FILE_HEADER_SIGNATURE
+        | spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
+        _ if first_bytes == [0u8; 4] => return Ok(None),
+        _ => {}
+    }
+    reader.seek_relative(-(first_bytes.len() as i64))?;
+    let (descriptor, _) = skip_to_data_descriptor(&mut reader, large_file)?;
+    let mut data = data.clone();
+    data.crc32 = descriptor.crc32;
+    data.compressed_size = descriptor.compressed_size;
+    data.uncompressed_size = descriptor.uncompressed_size;
+    Ok(Some(data))
+}
+
+/// Whether the data of an entry marked as stored fails its CRC-32 check, but passes it once
+/// inflated.
+#[cfg(feature = "_deflate-any")]
+fn stored_data_is_deflated(data: &ZipFileData, reader: &mut (impl Read + Seek)) -> ZipResult<bool> {
+    fn crc32_of(mut reader: impl Read) -> io::Result<u32> {
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(hasher.finalize()),
+                n => hasher.update(&buf[..n]),
+            }
+        }
+    }
+
+    if crc32_of(find_content(data, reader)?)? == data.crc32 {
+        return Ok(false);
+    }
+    let decoder = DeflateDecoder::new(find_content(data, reader)?);
+    Ok(crc32_of(decoder).is_ok_and(|crc32| crc32 == data.crc32))
 }
 
 fn find_data_start(
@@ -297,7 +455,7 @@ pub(crate) fn make_crypto_reader<'a>(
     crc32: u32,
     mut last_modified_time: Option<DateTime>,
     using_data_descriptor: bool,
-    reader: io::Take<&'a mut dyn Read>,
+    reader: io::Take<Box<dyn EntryReader + 'a>>,
     password: Option<&[u8]>,
     aes_info: Option<(AesMode, AesVendorVersion, CompressionMethod)>,
     #[cfg(feature = "aes-crypto")] compressed_size: u64,
@@ -338,6 +496,29 @@ pub(crate) fn make_crypto_reader<'a>(
     Ok(reader)
 }
 
+/// Like [`io::copy`], but through a heap buffer of the given size if there is one.
+pub(crate) fn copy_with_buffer_size<R: Read + ?Sized, W: Write + ?Sized>(
+    reader: &mut R,
+    writer: &mut W,
+    buffer_size: Option<NonZeroUsize>,
+) -> io::Result<u64> {
+    let Some(buffer_size) = buffer_size else {
+        return copy(reader, writer);
+    };
+    let mut buffer = vec![0u8; buffer_size.get()];
+    let mut copied = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(copied),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+}
+
 pub(crate) fn make_reader(
     compression_method: CompressionMethod,
     crc32: u32,
@@ -400,6 +581,36 @@ pub(crate) fn make_reader(
     }
 }
 
+/// Enforces [`Config::max_file_count`] before any entries are read.
+fn check_file_count(dir_info: &CentralDirectoryInfo, config: &Config) -> ZipResult<()> {
+    if config
+        .max_file_count
+        .is_some_and(|max| dir_info.number_of_files > max)
+    {
+        return Err(InvalidArchive("Too many files in the central directory"));
+    }
+    Ok(())
+}
+
+/// Enforces [`Config::max_central_directory_size`] on the part of the central directory read so
+/// far.
+fn check_central_directory_size(
+    dir_info: &CentralDirectoryInfo,
+    config: &Config,
+    reader: &mut impl Seek,
+) -> ZipResult<()> {
+    if let Some(max) = config.max_central_directory_size {
+        if reader
+            .stream_position()?
+            .saturating_sub(dir_info.directory_start)
+            > max
+        {
+            return Err(InvalidArchive("Central directory is too large"));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub(crate) struct CentralDirectoryInfo {
     pub(crate) archive_offset: u64,
@@ -408,6 +619,7 @@ pub(crate) struct CentralDirectoryInfo {
     pub(crate) number_of_files: usize,
     pub(crate) disk_number: u32,
     pub(crate) disk_with_central_directory: u32,
+    pub(crate) number_of_disks: u32,
     pub(crate) is_zip64: bool,
 }
 
@@ -417,6 +629,7 @@ impl<R> ZipArchive<R> {
         comment: Box<[u8]>,
         reader: R,
         central_start: u64,
+        eocd_start: u64,
     ) -> ZipResult<Self> {
         let initial_offset = match files.first() {
             Some((_, file)) => file.header_start,
@@ -426,8 +639,15 @@ impl<R> ZipArchive<R> {
             files,
             offset: initial_offset,
             dir_start: central_start,
+            eocd_start,
+            disk_info: DiskInfo {
+                disk_number: 0,
+                disk_with_central_directory: 0,
+                number_of_disks: 1,
+            },
             config: Config {
                 archive_offset: ArchiveOffset::Known(initial_offset),
+                ..Default::default()
             },
         });
         Ok(Self {
@@ -449,6 +669,38 @@ impl<R> ZipArchive<R> {
         }
         Some(total)
     }
+
+    /// Total compressed size of the files in the archive, if it can be known, under the same
+    /// conditions as [`ZipArchive::decompressed_size`]. Doesn't include headers or other metadata.
+    pub fn total_compressed_size(&self) -> Option<u128> {
+        let mut total = 0u128;
+        for file in self.shared.files.values() {
+            if file.using_data_descriptor {
+                return None;
+            }
+            total = total.checked_add(file.compressed_size as u128)?;
+        }
+        Some(total)
+    }
+
+    /// The ratio of [`ZipArchive::decompressed_size`] to [`ZipArchive::total_compressed_size`],
+    /// such as 4.0 when the files take a quarter of their size in the archive. `None` if either
+    /// size isn't known, or if the archive has no compressed data.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        let compressed = self.total_compressed_size()?;
+        if compressed == 0 {
+            return None;
+        }
+        Some(self.decompressed_size()? as f64 / compressed as f64)
+    }
+
+    /// Returns an iterator over the metadata of every entry, in the same order as
+    /// [`ZipArchive::by_index`]. Unlike opening each entry, this only needs `&self` and doesn't
+    /// read anything more from the archive, since it all comes from the central directory. Its
+    /// length is known, such as for showing progress as "entry i of N".
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = EntryInfo<'_>> {
+        self.shared.files.values().map(|data| EntryInfo { data })
+    }
 }
 
 impl<R: Read + Seek> ZipArchive<R> {
@@ -507,7 +759,7 @@ impl<R: Read + Seek> ZipArchive<R> {
          * handle, which it really shouldn't need to anyway. */
         let mut limited_raw = (&mut self.reader as &mut dyn Read).take(length_to_read);
         /* Copy over file data from source archive directly. */
-        io::copy(&mut limited_raw, &mut w)?;
+        copy_with_buffer_size(&mut limited_raw, &mut w, self.shared.config.io_buffer_size)?;
 
         /* Return the files we've just written to the data stream. */
         Ok(new_files)
@@ -561,6 +813,8 @@ impl<R: Read + Seek> ZipArchive<R> {
             number_of_files,
             disk_number: footer.disk_number as u32,
             disk_with_central_directory: footer.disk_with_central_directory as u32,
+            // The ZIP32 record has no disk count, but it's on the last disk
+            number_of_disks: footer.disk_number as u32 + 1,
             cde_position: cde_start_pos,
             is_zip64: false,
         })
@@ -665,6 +919,7 @@ impl<R: Read + Seek> ZipArchive<R> {
                         number_of_files: footer64.number_of_files as usize,
                         disk_number: footer64.disk_number,
                         disk_with_central_directory: footer64.disk_with_central_directory,
+                        number_of_disks: locator64.number_of_disks,
                         cde_position: cde_start_pos,
                         is_zip64: true,
                     })
@@ -679,12 +934,40 @@ impl<R: Read + Seek> ZipArchive<R> {
         config: Config,
         reader: &mut R,
     ) -> ZipResult<(Zip32CentralDirectoryEnd, Shared)> {
+        let (footer, shared) =
+            Self::find_central_directory(config, reader, Self::read_central_header, |shared| {
+                shared.files.len()
+            })?;
+        reader.seek(io::SeekFrom::Start(shared.dir_start))?;
+        Ok((footer, shared.build()))
+    }
+
+    /// Finds the end-of-central-directory record that best describes the archive, and reads
+    /// its central directory with `read_directory`. A candidate is only accepted if
+    /// `number_of_files` of what it reads matches the record.
+    fn find_central_directory<T>(
+        config: Config,
+        reader: &mut R,
+        mut read_directory: impl FnMut(CentralDirectoryInfo, Config, &mut R) -> ZipResult<T>,
+        number_of_files: impl Fn(&T) -> usize,
+    ) -> ZipResult<(Zip32CentralDirectoryEnd, T)> {
         let mut invalid_errors_32 = Vec::new();
         let mut unsupported_errors_32 = Vec::new();
         let mut invalid_errors_64 = Vec::new();
         let mut unsupported_errors_64 = Vec::new();
         let mut ok_results = Vec::new();
         let cde_locations = spec::Zip32CentralDirectoryEnd::find_and_parse(reader)?;
+        // The comments of candidates that end exactly at the end of the file. Another candidate
+        // inside one of them is most likely part of that comment rather than the real record.
+        let file_length = reader.seek(io::SeekFrom::End(0))?;
+        let comment_ranges: Vec<Range<u64>> = cde_locations
+            .iter()
+            .map(|(footer, cde_start_pos)| {
+                let comment_start = cde_start_pos + size_of::<Zip32CDEBlock>() as u64;
+                comment_start..comment_start + footer.zip_file_comment.len() as u64
+            })
+            .filter(|comment| comment.end == file_length)
+            .collect();
         cde_locations
             .into_vec()
             .into_iter()
@@ -723,6 +1006,10 @@ impl<R: Read + Seek> ZipArchive<R> {
             });
         ok_results.sort_by_key(|(_, result)| {
             (
+                // try those inside another one's comment last
+                comment_ranges
+                    .iter()
+                    .any(|comment| comment.contains(&result.cde_position)),
                 u64::MAX - result.cde_position, // try the last one first
                 !result.is_zip64,               // try ZIP64 first
             )
@@ -732,7 +1019,7 @@ impl<R: Read + Seek> ZipArchive<R> {
             let mut inner_result = Vec::with_capacity(1);
             let is_zip64 = result.is_zip64;
             Self::sort_result(
-                Self::read_central_header(result, config, reader),
+                read_directory(result, config, reader),
                 if is_zip64 {
                     &mut invalid_errors_64
                 } else {
@@ -746,11 +1033,11 @@ impl<R: Read + Seek> ZipArchive<R> {
                 &mut inner_result,
                 &(),
             );
-            if let Some((_, shared)) = inner_result.into_iter().next() {
-                if shared.files.len() == footer.number_of_files as usize
+            if let Some((_, directory)) = inner_result.into_iter().next() {
+                if number_of_files(&directory) == footer.number_of_files as usize
                     || (is_zip64 && footer.number_of_files == ZIP64_ENTRY_THR as u16)
                 {
-                    best_result = Some((footer, shared));
+                    best_result = Some((footer, directory));
                     break;
                 } else {
                     if is_zip64 {
@@ -762,7 +1049,7 @@ impl<R: Read + Seek> ZipArchive<R> {
                 }
             }
         }
-        let Some((footer, shared)) = best_result else {
+        let Some((footer, directory)) = best_result else {
             return Err(unsupported_errors_32
                 .into_iter()
                 .chain(unsupported_errors_64)
@@ -771,8 +1058,7 @@ impl<R: Read + Seek> ZipArchive<R> {
                 .next()
                 .unwrap());
         };
-        reader.seek(io::SeekFrom::Start(shared.dir_start))?;
-        Ok((Rc::try_unwrap(footer).unwrap(), shared.build()))
+        Ok((Rc::try_unwrap(footer).unwrap(), directory))
     }
 
     fn read_central_header(
@@ -790,20 +1076,96 @@ impl<R: Read + Seek> ZipArchive<R> {
         if dir_info.disk_number != dir_info.disk_with_central_directory {
             return unsupported_zip_error("Support for multi-disk files is not implemented");
         }
+        check_file_count(&dir_info, &config)?;
         let mut files = Vec::with_capacity(file_capacity);
         reader.seek(io::SeekFrom::Start(dir_info.directory_start))?;
         for _ in 0..dir_info.number_of_files {
-            let file = central_header_to_zip_file(reader, dir_info.archive_offset)?;
+            let file = central_header_to_zip_file(reader, dir_info.archive_offset, &config)?;
+            check_central_directory_size(&dir_info, &config, reader)?;
+            if config.strict_utf8_names
+                && file.is_utf8
+                && std::str::from_utf8(&file.file_name_raw).is_err()
+            {
+                return Err(InvalidArchive(
+                    "File name flagged as UTF-8 isn't valid UTF-8",
+                ));
+            }
             files.push(file);
         }
+        // Some tools write the central directory before the files rather than after them. Either
+        // way, a file's data mustn't start inside the central directory.
+        let directory_end = reader.stream_position()?;
+        for file in &files {
+            let data_start = file.data_start();
+            if data_start > file.central_header_start && file.header_start < directory_end {
+                return Err(InvalidArchive(
+                    "A file can't start after its central-directory header",
+                ));
+            }
+        }
+        if config.validate_entry_bounds {
+            let archive_end = reader.seek(io::SeekFrom::End(0))?;
+            for file in &files {
+                if file
+                    .data_start()
+                    .checked_add(file.compressed_size)
+                    .map_or(true, |end| end > archive_end)
+                {
+                    return Err(InvalidArchive(
+                        "A file's compressed data extends past the end of the archive",
+                    ));
+                }
+            }
+        }
         Ok(SharedBuilder {
             files,
             offset: dir_info.archive_offset,
             dir_start: dir_info.directory_start,
+            eocd_start: dir_info.cde_position,
+            disk_info: DiskInfo {
+                disk_number: dir_info.disk_number,
+                disk_with_central_directory: dir_info.disk_with_central_directory,
+                number_of_disks: dir_info.number_of_disks,
+            },
             config,
         })
     }
 
+    /// Reads only the file names from a central directory, skipping each entry's extra fields
+    /// and comment.
+    fn read_central_names(
+        dir_info: CentralDirectoryInfo,
+        config: Config,
+        reader: &mut R,
+    ) -> ZipResult<Vec<Box<str>>> {
+        let name_capacity = if dir_info.number_of_files > dir_info.directory_start as usize {
+            0
+        } else {
+            dir_info.number_of_files
+        };
+        if dir_info.disk_number != dir_info.disk_with_central_directory {
+            return unsupported_zip_error("Support for multi-disk files is not implemented");
+        }
+        check_file_count(&dir_info, &config)?;
+        let mut names = Vec::with_capacity(name_capacity);
+        reader.seek(io::SeekFrom::Start(dir_info.directory_start))?;
+        for _ in 0..dir_info.number_of_files {
+            let block = ZipCentralEntryBlock::parse(reader)?;
+            let file_name_raw =
+                read_variable_length_byte_field(reader, block.file_name_length as usize)?;
+            let skipped = block.extra_field_length as u64 + block.file_comment_length as u64;
+            if io::copy(&mut reader.by_ref().take(skipped), &mut io::sink())? != skipped {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            check_central_directory_size(&dir_info, &config, reader)?;
+            names.push(match block.flags & (1 << 11) != 0 {
+                true => String::from_utf8_lossy(&file_name_raw).into(),
+                false => config.legacy_encoding.decode(file_name_raw),
+            });
+        }
+        Ok(names)
+    }
+
     fn sort_result<T, U: Clone>(
         result: ZipResult<T>,
         invalid_errors: &mut Vec<ZipError>,
@@ -864,19 +1226,42 @@ impl<R: Read + Seek> ZipArchive<R> {
         Self::with_config(Default::default(), reader)
     }
 
+    /// Lists the names of the files in a ZIP archive, in central-directory order, without
+    /// opening it.
+    ///
+    /// This is faster than [`ZipArchive::new`] for large archives, because only the name of each
+    /// central-directory header is kept: extra fields, comments and local headers aren't read.
+    /// As a result, a name is never replaced by the one in an Info-ZIP Unicode Path extra field,
+    /// and the archive isn't validated beyond its central directory having the expected number
+    /// of entries.
+    pub fn list_names_only(mut reader: R) -> ZipResult<Vec<Box<str>>> {
+        reader.seek(SeekFrom::Start(0))?;
+        match Self::find_central_directory(
+            Config::default(),
+            &mut reader,
+            Self::read_central_names,
+            Vec::len,
+        ) {
+            Ok((_, names)) => Ok(names),
+            Err(e @ (InvalidArchive(_) | UnsupportedArchive(_))) => Err(e),
+            Err(_) => Err(InvalidArchive("No valid central directory found")),
+        }
+    }
+
     /// Read a ZIP archive providing a read configuration, collecting the files it contains.
     ///
     /// This uses the central directory record of the ZIP file, and ignores local file headers.
     pub fn with_config(config: Config, mut reader: R) -> ZipResult<ZipArchive<R>> {
         reader.seek(SeekFrom::Start(0))?;
-        if let Ok((footer, shared)) = Self::get_metadata(config, &mut reader) {
-            return Ok(ZipArchive {
+        match Self::get_metadata(config, &mut reader) {
+            Ok((footer, shared)) => Ok(ZipArchive {
                 reader,
                 shared: shared.into(),
                 comment: footer.zip_file_comment.into(),
-            });
+            }),
+            Err(e @ (InvalidArchive(_) | UnsupportedArchive(_))) => Err(e),
+            Err(_) => Err(InvalidArchive("No valid central directory found")),
         }
-        Err(InvalidArchive("No valid central directory found"))
     }
 
     /// Extract a Zip archive into a directory, overwriting files if they
@@ -890,104 +1275,256 @@ impl<R: Read + Seek> ZipArchive<R> {
     /// On Unix and Windows, symbolic links are extracted correctly. On other platforms such as
     /// WebAssembly, symbolic links aren't supported, so they're extracted as normal files
     /// containing the target path in UTF-8.
+    ///
+    /// On Unix, the set-user-ID, set-group-ID and sticky bits of stored modes are cleared; see
+    /// [`ExtractOptions::strip_special_bits`].
     pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
-        use std::fs;
-        #[cfg(unix)]
+        self.extract_with_options(directory, ExtractOptions::default())
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], with the given
+    /// options.
+    pub fn extract_with_options<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: ExtractOptions,
+    ) -> ZipResult<()> {
+        let buffer_size = self.shared.config.io_buffer_size;
+        self.extract_with_copier(directory, options, false, |file, outfile| {
+            copy_with_buffer_size(file, outfile, buffer_size)?;
+            Ok(())
+        })
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], but without the
+    /// directory that all of its entries are in, if there is one, as found by
+    /// [`ZipArchive::root_dir`]. So an archive of `project-1.2.3/src/lib.rs` and
+    /// `project-1.2.3/README.md` is extracted to `src/lib.rs` and `README.md` in `directory`.
+    /// If there's no such directory, everything is extracted as usual.
+    ///
+    /// An entry that would only be enclosed in `directory` with the root directory kept, such as
+    /// `project-1.2.3/../README.md`, is treated as having an unsafe path.
+    pub fn extract_stripping_root<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
+        let buffer_size = self.shared.config.io_buffer_size;
+        self.extract_with_copier(
+            directory,
+            ExtractOptions::default(),
+            true,
+            |file, outfile| {
+                copy_with_buffer_size(file, outfile, buffer_size)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], and return a digest of
+    /// each regular file's extracted contents, keyed by the entry's name.
+    ///
+    /// The digests are computed from the decompressed bytes as they're written to disk, so the
+    /// extracted files don't have to be read again to build a verification manifest.
+    #[cfg(feature = "digest")]
+    pub fn extract_with_digests<P: AsRef<Path>, D: Digest>(
+        &mut self,
+        directory: P,
+    ) -> ZipResult<BTreeMap<String, Output<D>>> {
+        let mut digests = BTreeMap::new();
+        let buffer_size = self.shared.config.io_buffer_size;
+        self.extract_with_copier(
+            directory,
+            ExtractOptions::default(),
+            false,
+            |file, outfile| {
+                let mut writer = DigestWriter {
+                    inner: outfile,
+                    hasher: D::new(),
+                };
+                copy_with_buffer_size(file, &mut writer, buffer_size)?;
+                digests.insert(file.name().to_string(), writer.hasher.finalize());
+                Ok(())
+            },
+        )?;
+        Ok(digests)
+    }
+
+    /// Extract a Zip archive into a [`VirtualFs`], such as one kept in memory, with the given
+    /// options. This works like [`ZipArchive::extract_with_options`], which uses it with the real
+    /// filesystem, but doesn't need `std::fs`: directories, regular files and symbolic links
+    /// are all created through `vfs`, and the modes of regular files are set through it once
+    /// every entry has been extracted. [`AppleDoublePolicy::Skip`] applies to AppleDouble entries
+    /// as usual, but their metadata can't be applied to a virtual filesystem, so `Apply` skips
+    /// them too.
+    pub fn extract_to_vfs<V: VirtualFs>(
+        &mut self,
+        vfs: &mut V,
+        options: ExtractOptions,
+    ) -> ZipResult<()> {
+        let buffer_size = self.shared.config.io_buffer_size;
+        self.extract_to_vfs_with_copier(
+            vfs,
+            options,
+            false,
+            |file, outfile| {
+                copy_with_buffer_size(file, outfile, buffer_size)?;
+                Ok(())
+            },
+            |_, _| Ok(()),
+        )
+    }
+
+    fn extract_with_copier<P: AsRef<Path>, F>(
+        &mut self,
+        directory: P,
+        options: ExtractOptions,
+        strip_root: bool,
+        copy_file: F,
+    ) -> ZipResult<()>
+    where
+        F: FnMut(&mut ZipFile<'_>, &mut std::fs::File) -> ZipResult<()>,
+    {
+        let root = directory.as_ref();
+        self.extract_to_vfs_with_copier(
+            &mut extract::DirectoryFs { root },
+            options,
+            strip_root,
+            copy_file,
+            #[allow(unused_variables)]
+            |_, apple_double_data| {
+                #[cfg(target_os = "macos")]
+                for (path, data) in apple_double_data {
+                    // Metadata for a file that wasn't extracted has nothing to apply to
+                    let path = root.join(path);
+                    if path.symlink_metadata().is_ok() {
+                        extract::apply_apple_double(&path, &data)?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Extracts every entry into `vfs`, copying the contents of regular files with `copy_file`.
+    /// The AppleDouble data collected for [`AppleDoublePolicy::Apply`] is passed to
+    /// `apply_apple_double` before any permissions are set. If `strip_root` is set, the
+    /// archive's [`ZipArchive::root_dir`] is removed from every path.
+    fn extract_to_vfs_with_copier<V: VirtualFs, F, A>(
+        &mut self,
+        vfs: &mut V,
+        options: ExtractOptions,
+        strip_root: bool,
+        mut copy_file: F,
+        apply_apple_double: A,
+    ) -> ZipResult<()>
+    where
+        F: FnMut(&mut ZipFile<'_>, &mut V::File) -> ZipResult<()>,
+        A: FnOnce(&mut V, Vec<(PathBuf, Vec<u8>)>) -> ZipResult<()>,
+    {
         let mut files_by_unix_mode = Vec::new();
+        #[allow(unused_mut)]
+        let mut apple_double_data = Vec::new();
+        let shared = self.shared.clone();
+        let root_dir =
+            common_root_dir(shared.files.keys().map(|name| &**name)).filter(|_| strip_root);
         for i in 0..self.len() {
+            // Only AppleDouble entries are read here, and only on macOS
+            #[allow(unused_mut)]
             let mut file = self.by_index(i)?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
-
-            let outpath = directory.as_ref().join(filepath);
-
-            if file.is_dir() {
-                Self::make_writable_dir_all(&outpath)?;
-                continue;
-            }
-            let symlink_target = if file.is_symlink() && (cfg!(unix) || cfg!(windows)) {
-                let mut target = Vec::with_capacity(file.size() as usize);
-                file.read_exact(&mut target)?;
-                Some(target)
-            } else {
-                None
-            };
-            drop(file);
-            if let Some(p) = outpath.parent() {
-                Self::make_writable_dir_all(p)?;
-            }
-            if let Some(target) = symlink_target {
-                #[cfg(unix)]
-                {
-                    use std::os::unix::ffi::OsStringExt;
-                    let target = OsString::from_vec(target);
-                    let target_path = directory.as_ref().join(target);
-                    std::os::unix::fs::symlink(target_path, outpath.as_path())?;
+            let filepath = match (file.enclosed_name(), options.on_unsafe_path) {
+                (Some(filepath), _) => filepath,
+                (None, UnsafePathPolicy::Abort) => {
+                    return Err(ZipError::InvalidArchive("Invalid file path"))
                 }
-                #[cfg(windows)]
-                {
-                    let Ok(target) = String::from_utf8(target) else {
-                        return Err(ZipError::InvalidArchive("Invalid UTF-8 as symlink target"));
-                    };
-                    let target = target.into_boxed_str();
-                    let target_is_dir_from_archive =
-                        self.shared.files.contains_key(&target) && is_dir(&target);
-                    let target_path = directory.as_ref().join(OsString::from(target.to_string()));
-                    let target_is_dir = if target_is_dir_from_archive {
-                        true
-                    } else if let Ok(meta) = std::fs::metadata(&target_path) {
-                        meta.is_dir()
-                    } else {
-                        false
-                    };
-                    if target_is_dir {
-                        std::os::windows::fs::symlink_dir(target_path, outpath.as_path())?;
-                    } else {
-                        std::os::windows::fs::symlink_file(target_path, outpath.as_path())?;
+                (None, UnsafePathPolicy::Skip) => continue,
+                (None, UnsafePathPolicy::Sanitize) => {
+                    let filepath = file.mangled_name();
+                    if filepath.as_os_str().is_empty() {
+                        continue;
                     }
+                    filepath
                 }
-                continue;
-            }
-            let mut file = self.by_index(i)?;
-            let mut outfile = fs::File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
-            #[cfg(unix)]
-            {
-                // Check for real permissions, which we'll set in a second pass
-                if let Some(mode) = file.unix_mode() {
-                    files_by_unix_mode.push((outpath.clone(), mode));
+            };
+            let filepath = match root_dir {
+                None => filepath,
+                Some(root_dir) => match strip_root_dir(&filepath, root_dir) {
+                    Some(filepath) if filepath.as_os_str().is_empty() => continue,
+                    Some(filepath) => filepath,
+                    None if options.on_unsafe_path == UnsafePathPolicy::Abort => {
+                        return Err(ZipError::InvalidArchive("Invalid file path"))
+                    }
+                    None => continue,
+                },
+            };
+
+            if options.apple_double != AppleDoublePolicy::Keep {
+                match extract::AppleDoubleEntry::classify(&filepath) {
+                    extract::AppleDoubleEntry::None => {}
+                    #[cfg(target_os = "macos")]
+                    extract::AppleDoubleEntry::Metadata(target)
+                        if options.apple_double == AppleDoublePolicy::Apply =>
+                    {
+                        let data =
+                            extract::read_apple_double(&mut file, extract::MAX_APPLE_DOUBLE_SIZE)?;
+                        apple_double_data.push((target, data));
+                        continue;
+                    }
+                    _ => continue,
                 }
             }
-        }
-        #[cfg(unix)]
-        {
-            use std::cmp::Reverse;
-            use std::os::unix::fs::PermissionsExt;
 
-            if files_by_unix_mode.len() > 1 {
-                // Ensure we update children's permissions before making a parent unwritable
-                files_by_unix_mode.sort_by_key(|(path, _)| Reverse(path.clone()));
-            }
-            for (path, mode) in files_by_unix_mode.into_iter() {
-                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+            // Check for real permissions, which we'll set in a second pass
+            if let Some(mode) =
+                extract_file_to_vfs(file, &filepath, vfs, &options, &mut copy_file, &shared)?
+            {
+                files_by_unix_mode.push((filepath, mode));
             }
         }
+        apply_apple_double(vfs, apple_double_data)?;
+        if files_by_unix_mode.len() > 1 {
+            // Ensure we update children's permissions before making a parent unwritable
+            files_by_unix_mode.sort_by_key(|(path, _)| std::cmp::Reverse(path.clone()));
+        }
+        for (path, mode) in files_by_unix_mode.into_iter() {
+            vfs.set_permissions(&path, mode)?;
+        }
         Ok(())
     }
 
-    fn make_writable_dir_all<T: AsRef<Path>>(outpath: T) -> Result<(), ZipError> {
-        create_dir_all(outpath.as_ref())?;
-        #[cfg(unix)]
+    /// Extract a single entry to `dest`, which becomes the extracted file, directory or symbolic
+    /// link, whatever the entry's name. Missing parent directories of `dest` are created.
+    ///
+    /// This handles the entry like [`ZipArchive::extract`] with the default [`ExtractOptions`]:
+    /// an entry whose name isn't safe to extract, as determined by [`ZipFile::enclosed_name`], is
+    /// rejected, symbolic links are created as links, and the stored Unix mode is applied after
+    /// the contents are written. Nothing is written anywhere but `dest` and its parents, which
+    /// means `dest` must end with a file name.
+    pub fn extract_entry_to_path<P: AsRef<Path>>(
+        &mut self,
+        index: usize,
+        dest: P,
+    ) -> ZipResult<()> {
+        let dest = dest.as_ref();
+        let (Some(root), Some(name)) = (dest.parent(), dest.file_name()) else {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Destination must end with a file name",
+            )));
+        };
+        let options = ExtractOptions::default();
+        let buffer_size = self.shared.config.io_buffer_size;
+        let shared = self.shared.clone();
+        let file = self.by_index(index)?;
+        if file.enclosed_name().is_none() {
+            return Err(ZipError::InvalidArchive("Invalid file path"));
+        }
+        let mut vfs = extract::DirectoryFs { root };
+        let mut copy_file = |file: &mut ZipFile<'_>, outfile: &mut std::fs::File| {
+            copy_with_buffer_size(file, outfile, buffer_size)?;
+            Ok(())
+        };
+        let path = Path::new(name);
+        if let Some(mode) =
+            extract_file_to_vfs(file, path, &mut vfs, &options, &mut copy_file, &shared)?
         {
-            // Dirs must be writable until all normal files are extracted
-            use std::os::unix::fs::PermissionsExt;
-            std::fs::set_permissions(
-                outpath.as_ref(),
-                std::fs::Permissions::from_mode(
-                    0o700 | std::fs::metadata(outpath.as_ref())?.permissions().mode(),
-                ),
-            )?;
+            vfs.set_permissions(path, mode)?;
         }
         Ok(())
     }
@@ -1015,11 +1552,62 @@ impl<R: Read + Seek> ZipArchive<R> {
         &self.comment
     }
 
+    /// Returns the name of the directory that every entry is in, if there is one, such as
+    /// `project-1.2.3` for an archive of a release that puts everything in `project-1.2.3/`.
+    /// Either `/` or `\` may follow it in the entries' names. Returns `None` if the archive is
+    /// empty or any entry is at the top level, other than the directory itself.
+    pub fn root_dir(&self) -> Option<&str> {
+        common_root_dir(self.shared.files.keys().map(|name| &**name))
+    }
+
     /// Returns an iterator over all the file and directory names in this archive.
     pub fn file_names(&self) -> impl Iterator<Item = &str> {
         self.shared.files.keys().map(|s| s.as_ref())
     }
 
+    /// Returns an iterator over the indices of the entries that have an extra field with the given
+    /// header ID, such as `0x9901` for AES encryption or `0x000a` for NTFS timestamps. Only the
+    /// metadata read with the central directory is checked, so no entry has to be opened.
+    pub fn entries_with_extra_field(&self, tag: u16) -> impl Iterator<Item = usize> + '_ {
+        self.shared
+            .files
+            .values()
+            .enumerate()
+            .filter(move |(_, data)| has_extra_field(data, tag))
+            .map(|(index, _)| index)
+    }
+
+    /// Write the archive's table of contents to `out` as CSV, for auditing in a spreadsheet.
+    ///
+    /// The first row is a header, followed by one row per entry in central directory order with
+    /// the columns `name`, `size`, `compressed_size`, `method`, `crc32` (as 8 hexadecimal digits),
+    /// `modified`, `is_dir` and `unix_mode` (in octal). The modification time and Unix mode are
+    /// left empty when they aren't known. Fields are quoted as described in RFC 4180 when needed,
+    /// and rows end with CRLF.
+    pub fn write_toc_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(b"name,size,compressed_size,method,crc32,modified,is_dir,unix_mode\r\n")?;
+        for data in self.shared.files.values() {
+            write_csv_field(out, &data.file_name)?;
+            let modified = data
+                .last_modified_time
+                .map(|time| time.to_string())
+                .unwrap_or_default();
+            let unix_mode = data
+                .unix_mode()
+                .map(|mode| format!("{mode:o}"))
+                .unwrap_or_default();
+            write!(out, ",{},{},", data.uncompressed_size, data.compressed_size)?;
+            write_csv_field(out, &data.compression_method.to_string())?;
+            write!(
+                out,
+                ",{:08x},{modified},{},{unix_mode}\r\n",
+                data.crc32,
+                data.is_dir()
+            )?;
+        }
+        Ok(())
+    }
+
     /// Search for a file entry by name, decrypt with given password
     ///
     /// # Warning
@@ -1033,15 +1621,39 @@ impl<R: Read + Seek> ZipArchive<R> {
     /// There are many passwords out there that will also pass the validity checks
     /// we are able to perform. This is a weakness of the ZipCrypto algorithm,
     /// due to its fairly primitive approach to cryptography.
-    pub fn by_name_decrypt(&mut self, name: &str, password: &[u8]) -> ZipResult<ZipFile> {
+    pub fn by_name_decrypt(&mut self, name: &str, password: &[u8]) -> ZipResult<ZipFile<'_>> {
         self.by_name_with_optional_password(name, Some(password))
     }
 
     /// Search for a file entry by name
-    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile> {
+    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
         self.by_name_with_optional_password(name, None)
     }
 
+    /// Read the whole contents of the file entry with the given name, checking its CRC-32.
+    ///
+    /// The buffer is allocated up front from the entry's uncompressed size, up to a limit, since
+    /// the size in a malicious archive can't be trusted.
+    pub fn read_name(&mut self, name: &str) -> ZipResult<Vec<u8>> {
+        Self::read_whole_file(self.by_name(name)?)
+    }
+
+    /// Read the whole contents of the file entry with the given name, decrypting it with the
+    /// given password and checking its CRC-32.
+    ///
+    /// The same caveats as for [`ZipArchive::by_name_decrypt`] apply.
+    pub fn read_name_decrypt(&mut self, name: &str, password: &[u8]) -> ZipResult<Vec<u8>> {
+        Self::read_whole_file(self.by_name_decrypt(name, password)?)
+    }
+
+    fn read_whole_file(mut file: ZipFile<'_>) -> ZipResult<Vec<u8>> {
+        /// The most that's allocated before any data is read
+        const MAX_PREALLOCATION: u64 = 1 << 24;
+        let mut contents = Vec::with_capacity(file.size().min(MAX_PREALLOCATION) as usize);
+        file.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
     /// Get the index of a file entry by name, if it's present.
     #[inline(always)]
     pub fn index_for_name(&self, name: &str) -> Option<usize> {
@@ -1054,6 +1666,35 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.index_for_name(&path_to_string(path))
     }
 
+    /// Returns whether the archive contains an entry with the given name.
+    #[inline(always)]
+    pub fn contains_name(&self, name: &str) -> bool {
+        self.shared.files.contains_key(name)
+    }
+
+    /// Returns whether the archive contains an entry with the given path.
+    #[inline(always)]
+    pub fn contains_path<T: AsRef<Path>>(&self, path: T) -> bool {
+        self.contains_name(&path_to_string(path))
+    }
+
+    /// Returns whether the archive contains a directory entry with the given name. The trailing
+    /// `/` is optional. An entry counts as a directory if its name ends with `/` or its Unix mode
+    /// says it's one.
+    pub fn contains_dir(&self, name: &str) -> bool {
+        let trimmed = name.trim_end_matches('/');
+        let files = &self.shared.files;
+        files
+            .get(format!("{trimmed}/").as_str())
+            .or_else(|| files.get(trimmed))
+            .is_some_and(|data| {
+                data.is_dir()
+                    || data
+                        .unix_mode()
+                        .is_some_and(|mode| mode & 0o170000 == S_IFDIR)
+            })
+    }
+
     /// Get the name of a file entry, if it's present.
     #[inline(always)]
     pub fn name_for_index(&self, index: usize) -> Option<&str> {
@@ -1100,6 +1741,49 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.by_index_with_optional_password(file_number, None)
     }
 
+    /// Get a contained file by index, wrapped in a [`BufReader`] so that its decompressed contents
+    /// can be read with [`BufRead`] methods such as [`BufRead::lines`]. The buffer's capacity is
+    /// [`Config::io_buffer_size`] if that's set, and the default capacity of [`BufReader`]
+    /// otherwise.
+    pub fn by_index_buffered(&mut self, file_number: usize) -> ZipResult<BufReader<ZipFile<'_>>> {
+        let buffer_size = self.shared.config.io_buffer_size;
+        let file = self.by_index(file_number)?;
+        Ok(match buffer_size {
+            Some(capacity) => BufReader::with_capacity(capacity.get(), file),
+            None => BufReader::new(file),
+        })
+    }
+
+    /// Get a contained file by index, along with its decompressed length if that's known up
+    /// front, such as for a `Content-Length` header when the contents are streamed.
+    pub fn by_index_sized(&mut self, file_number: usize) -> ZipResult<SizedRead<'_>> {
+        let file = self.by_index(file_number)?;
+        let len = match file.data.using_data_descriptor {
+            true => None,
+            false => Some(file.data.uncompressed_size),
+        };
+        Ok(SizedRead { file, len })
+    }
+
+    /// Get a contained file by index, with a reader that fails once more than `max_uncompressed`
+    /// bytes have been decompressed, to stop decompression bombs whatever size the entry claims.
+    ///
+    /// Reading past the limit returns an [`io::Error`] of kind [`io::ErrorKind::InvalidData`]
+    /// wrapping [`ZipError::InvalidArchive`] with the message
+    /// [`LimitedRead::LIMIT_EXCEEDED`], rather than silently truncating the contents as
+    /// [`Read::take`] would.
+    pub fn by_index_with_limit(
+        &mut self,
+        file_number: usize,
+        max_uncompressed: u64,
+    ) -> ZipResult<LimitedRead<'_>> {
+        let file = self.by_index(file_number)?;
+        Ok(LimitedRead {
+            file,
+            remaining: max_uncompressed,
+        })
+    }
+
     /// Get a contained file by index without decompressing it
     pub fn by_index_raw(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
         let reader = &mut self.reader;
@@ -1112,6 +1796,8 @@ impl<R: Read + Seek> ZipArchive<R> {
             crypto_reader: None,
             reader: ZipFileReader::Raw(find_content(data, reader)?),
             data: Cow::Borrowed(data),
+            data_descriptor: None,
+            streaming: false,
         })
     }
 
@@ -1131,7 +1817,27 @@ impl<R: Read + Seek> ZipArchive<R> {
             (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
             _ => {}
         }
-        let limit_reader = find_content(data, &mut self.reader)?;
+        let data = match recover_data_descriptor_sizes(data, &mut self.reader)? {
+            Some(recovered) => Cow::Owned(recovered),
+            None => Cow::Borrowed(data),
+        };
+        #[cfg(feature = "_deflate-any")]
+        if self.shared.config.sniff_compression
+            && password.is_none()
+            && data.compression_method == CompressionMethod::Stored
+            && stored_data_is_deflated(&data, &mut self.reader)?
+        {
+            let limit_reader = find_content(&data, &mut self.reader)?;
+            let reader = CryptoReader::Plaintext(limit_reader);
+            return Ok(ZipFile {
+                crypto_reader: None,
+                reader: make_reader(CompressionMethod::Deflated, data.crc32, reader)?,
+                data,
+                data_descriptor: None,
+                streaming: false,
+            });
+        }
+        let limit_reader = find_content(&data, &mut self.reader)?;
 
         let crypto_reader = make_crypto_reader(
             data.compression_method,
@@ -1147,196 +1853,1263 @@ impl<R: Read + Seek> ZipArchive<R> {
         Ok(ZipFile {
             crypto_reader: Some(crypto_reader),
             reader: ZipFileReader::NoReader,
-            data: Cow::Borrowed(data),
+            data,
+            data_descriptor: None,
+            streaming: false,
         })
     }
 
-    /// Unwrap and return the inner reader object
+    /// Compare each entry's local file header against its central directory record.
     ///
-    /// The position of the reader is undefined.
-    pub fn into_inner(self) -> R {
-        self.reader
-    }
-}
-
-/// Holds the AES information of a file in the zip archive
-#[derive(Debug)]
-#[cfg(feature = "aes-crypto")]
-pub struct AesInfo {
-    /// The AES encryption mode
-    pub aes_mode: AesMode,
-    /// The verification key
-    pub verification_value: [u8; PWD_VERIFY_LENGTH],
-    /// The salt
-    pub salt: Vec<u8>,
-}
+    /// Reading an archive only uses the central directory, so an archive whose local headers were
+    /// tampered with or written inconsistently can still be read without error. This parses every
+    /// local header and reports each field that disagrees with the central directory. The CRC and
+    /// sizes are only compared when the local header doesn't defer them to a data descriptor.
+    pub fn validate_local_headers(&mut self) -> ZipResult<Vec<LocalHeaderDiscrepancy>> {
+        let mut discrepancies = Vec::new();
+        for (index, central) in self.shared.files.values().enumerate() {
+            self.reader.seek(SeekFrom::Start(central.header_start))?;
+            let block = ZipLocalEntryBlock::parse(&mut self.reader)?;
+            let mut local = ZipFileData::from_local_block(block, &mut self.reader)?;
+            match parse_extra_field(&mut local, &self.shared.config) {
+                Ok(..) | Err(ZipError::Io(..)) => {}
+                Err(e) => return Err(e),
+            }
+            let mut report = |mismatch| {
+                discrepancies.push(LocalHeaderDiscrepancy {
+                    index,
+                    name: central.file_name.clone(),
+                    mismatch,
+                })
+            };
+            if local.compression_method != central.compression_method {
+                report(LocalHeaderMismatch::CompressionMethod {
+                    local: local.compression_method,
+                    central: central.compression_method,
+                });
+            }
+            if !local.using_data_descriptor {
+                if local.crc32 != central.crc32 {
+                    report(LocalHeaderMismatch::Crc32 {
+                        local: local.crc32,
+                        central: central.crc32,
+                    });
+                }
+                if local.compressed_size != central.compressed_size {
+                    report(LocalHeaderMismatch::CompressedSize {
+                        local: local.compressed_size,
+                        central: central.compressed_size,
+                    });
+                }
+                if local.uncompressed_size != central.uncompressed_size {
+                    report(LocalHeaderMismatch::UncompressedSize {
+                        local: local.uncompressed_size,
+                        central: central.uncompressed_size,
+                    });
+                }
+            }
+            if local.file_name_raw != central.file_name_raw {
+                report(LocalHeaderMismatch::FileName {
+                    local: local.file_name_raw,
+                    central: central.file_name_raw.clone(),
+                });
+            }
+        }
+        Ok(discrepancies)
+    }
 
-const fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
-    Err(ZipError::UnsupportedArchive(detail))
-}
+    /// Open the entry at `file_number` as a ZIP archive in its own right, e.g. a JAR inside a WAR.
+    ///
+    /// The entry must be stored without compression or encryption, so that its contents can be
+    /// read in place. The nested archive reads through this archive's reader, so it borrows it
+    /// for as long as it's in use.
+    pub fn nested_archive(
+        &mut self,
+        file_number: usize,
+    ) -> ZipResult<ZipArchive<NestedArchiveReader<'_, R>>> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.compression_method != CompressionMethod::Stored || data.encrypted {
+            return unsupported_zip_error(
+                "Only stored, unencrypted entries can be opened as nested archives",
+            );
+        }
+        let start = match data.data_start.get() {
+            Some(data_start) => *data_start,
+            None => find_data_start(data, &mut self.reader)?,
+        };
+        let len = data.compressed_size;
+        ZipArchive::new(NestedArchiveReader {
+            inner: &mut self.reader,
+            start,
+            len,
+            pos: 0,
+        })
+    }
 
-/// Parse a central directory entry to collect the information for the file.
-pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
-    reader: &mut R,
-    archive_offset: u64,
-) -> ZipResult<ZipFileData> {
-    let central_header_start = reader.stream_position()?;
+    /// Returns whether the entry at `file_number` has a data descriptor, i.e. whether its CRC-32
+    /// and sizes were written after its data instead of in its local header.
+    pub fn entry_data_descriptor_present(&self, file_number: usize) -> ZipResult<bool> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        Ok(data.using_data_descriptor)
+    }
 
-    // Parse central header
-    let block = ZipCentralEntryBlock::parse(reader)?;
-    let file =
-        central_header_to_zip_file_inner(reader, archive_offset, central_header_start, block)?;
-    let central_header_end = reader.stream_position()?;
-    let data_start = find_data_start(&file, reader)?;
-    if data_start > central_header_start {
-        return Err(InvalidArchive(
-            "A file can't start after its central-directory header",
-        ));
+    /// Read the data descriptor that follows the data of the entry at `file_number`.
+    ///
+    /// Returns `None` if the entry has no data descriptor. The descriptor's sizes are read as
+    /// 8-byte values if the entry is in ZIP64 format.
+    ///
+    /// The descriptor's signature is optional, and a descriptor without one may have a CRC-32
+    /// equal to the signature. So when the first 4 bytes match the signature, the descriptor is
+    /// parsed both with and without it, and whichever reading agrees with the central directory
+    /// is returned, preferring the signed one. If neither agrees, the signature is assumed to be
+    /// present. Apart from that, the values aren't checked against the central directory; that's
+    /// left to the caller.
+    pub fn read_data_descriptor(
+        &mut self,
+        file_number: usize,
+    ) -> ZipResult<Option<DataDescriptor>> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if !data.using_data_descriptor {
+            return Ok(None);
+        }
+        let data_start = match data.data_start.get() {
+            Some(data_start) => *data_start,
+            None => find_data_start(data, &mut self.reader)?,
+        };
+        let descriptor_start = data_start
+            .checked_add(data.compressed_size)
+            .ok_or(InvalidArchive("Data descriptor offset is too large"))?;
+        self.reader.seek(SeekFrom::Start(descriptor_start))?;
+        let unsigned_len = if data.large_file { 20 } else { 12 };
+        let mut bytes = Vec::with_capacity(unsigned_len + 4);
+        (&mut self.reader)
+            .take(unsigned_len as u64 + 4)
+            .read_to_end(&mut bytes)?;
+        let unsigned = DataDescriptor::parse(&bytes, data.large_file);
+        if bytes[..4.min(bytes.len())] != spec::Magic::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes() {
+            return unsigned.map(Some);
+        }
+        let signed = DataDescriptor::parse(&bytes[4..], data.large_file);
+        let matches_central_directory = |descriptor: &ZipResult<DataDescriptor>| {
+            descriptor.as_ref().is_ok_and(|descriptor| {
+                descriptor.crc32 == data.crc32
+                    && descriptor.compressed_size == data.compressed_size
+                    && descriptor.uncompressed_size == data.uncompressed_size
+            })
+        };
+        if !matches_central_directory(&signed) && matches_central_directory(&unsigned) {
+            unsigned.map(Some)
+        } else {
+            signed.map(Some)
+        }
     }
-    file.data_start.get_or_init(|| data_start);
-    reader.seek(SeekFrom::Start(central_header_end))?;
-    Ok(file)
-}
 
-#[inline]
-fn read_variable_length_byte_field<R: Read>(reader: &mut R, len: usize) -> io::Result<Box<[u8]>> {
-    let mut data = vec![0; len].into_boxed_slice();
-    reader.read_exact(&mut data)?;
-    Ok(data)
-}
+    /// Get the disk-number fields of the archive's end-of-central-directory records, for
+    /// diagnostics such as reporting how many disks an archive claims to span.
+    pub fn disk_info(&self) -> DiskInfo {
+        self.shared.disk_info
+    }
 
-/// Parse a central directory entry to collect the information for the file.
-fn central_header_to_zip_file_inner<R: Read>(
-    reader: &mut R,
-    archive_offset: u64,
-    central_header_start: u64,
-    block: ZipCentralEntryBlock,
-) -> ZipResult<ZipFileData> {
-    let ZipCentralEntryBlock {
-        // magic,
-        version_made_by,
-        // version_to_extract,
-        flags,
-        compression_method,
-        last_mod_time,
-        last_mod_date,
-        crc32,
-        compressed_size,
-        uncompressed_size,
-        file_name_length,
-        extra_field_length,
-        file_comment_length,
-        // disk_number,
-        // internal_file_attributes,
-        external_file_attributes,
-        offset,
-        ..
-    } = block;
+    /// Read the raw end-of-central-directory records of the archive: the ZIP32 record along with
+    /// the archive comment and, if present, the ZIP64 record and its locator. The bytes are read
+    /// from the underlying reader exactly as stored, which is useful for forensics and for signing
+    /// schemes that cover the whole archive structure.
+    pub fn eocd_bytes(&mut self) -> ZipResult<EocdBytes> {
+        let eocd_start = self.shared.eocd_start;
+        self.reader.seek(SeekFrom::Start(eocd_start))?;
+        let block = Zip32CDEBlock::parse(&mut self.reader)?;
+        let eocd_len = size_of::<Zip32CDEBlock>() as u64 + block.zip_file_comment_length as u64;
+        let eocd = RecordBytes::read(&mut self.reader, eocd_start, eocd_len)?;
+
+        let locator_len = size_of::<Zip64CDELocatorBlock>() as u64;
+        let Some(locator_start) = eocd_start.checked_sub(locator_len) else {
+            return Ok(EocdBytes {
+                eocd,
+                zip64_eocd: None,
+                zip64_locator: None,
+            });
+        };
+        self.reader.seek(SeekFrom::Start(locator_start))?;
+        let Ok(locator) = Zip64CDELocatorBlock::parse(&mut self.reader) else {
+            return Ok(EocdBytes {
+                eocd,
+                zip64_eocd: None,
+                zip64_locator: None,
+            });
+        };
+        let zip64_start = locator
+            .end_of_central_directory_offset
+            .checked_add(self.shared.offset)
+            .filter(|start| *start < locator_start)
+            .ok_or(InvalidArchive(
+                "Invalid ZIP64 end of central directory offset",
+            ))?;
+        self.reader.seek(SeekFrom::Start(zip64_start))?;
+        let block = Zip64CDEBlock::parse(&mut self.reader)?;
+        // The record size excludes the signature and the size field itself
+        let zip64_len = block
+            .record_size
+            .checked_add(12)
+            .filter(|len| zip64_start + len <= locator_start)
+            .ok_or(InvalidArchive(
+                "Invalid ZIP64 end of central directory size",
+            ))?;
+        Ok(EocdBytes {
+            eocd,
+            zip64_eocd: Some(RecordBytes::read(&mut self.reader, zip64_start, zip64_len)?),
+            zip64_locator: Some(RecordBytes::read(
+                &mut self.reader,
+                locator_start,
+                locator_len,
+            )?),
+        })
+    }
 
-    let encrypted = flags & 1 == 1;
-    let is_utf8 = flags & (1 << 11) != 0;
-    let using_data_descriptor = flags & (1 << 3) != 0;
+    /// Check the archive for structures that are valid enough to read but typical of zip bombs,
+    /// zip quines and other malicious archives, for security scanning. This reads every local
+    /// header, but no file data.
+    ///
+    /// Overlapping entries are reported pairwise, each with the entry that reaches furthest among
+    /// those starting before it, so a chain of overlaps is reported once per entry. An entry whose
+    /// local header can't be parsed is reported as such, and isn't checked for overlaps.
+    pub fn detect_anomalies(&mut self) -> ZipResult<Vec<ArchiveAnomaly>> {
+        let mut anomalies = Vec::new();
+        let mut extents: Vec<(u64, u64, usize)> = Vec::with_capacity(self.len());
+        for (index, data) in self.shared.files.values().enumerate() {
+            let data_start = match data.data_start.get() {
+                Some(data_start) => *data_start,
+                None => match find_data_start(data, &mut self.reader) {
+                    Ok(data_start) => data_start,
+                    Err(ZipError::InvalidArchive(_)) => {
+                        anomalies.push(ArchiveAnomaly::InvalidLocalHeader { index });
+                        continue;
+                    }
+                    Err(ZipError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                        anomalies.push(ArchiveAnomaly::InvalidLocalHeader { index });
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                },
+            };
+            let end = data_start.saturating_add(data.compressed_size);
+            extents.push((data.header_start, end, index));
+            if data.header_start < self.shared.eocd_start && self.shared.dir_start < end {
+                anomalies.push(ArchiveAnomaly::EntryContainsCentralDirectory { index });
+            }
+        }
 
-    let file_name_raw = read_variable_length_byte_field(reader, file_name_length as usize)?;
-    let extra_field = read_variable_length_byte_field(reader, extra_field_length as usize)?;
-    let file_comment_raw = read_variable_length_byte_field(reader, file_comment_length as usize)?;
-    let file_name: Box<str> = match is_utf8 {
-        true => String::from_utf8_lossy(&file_name_raw).into(),
-        false => file_name_raw.clone().from_cp437(),
-    };
-    let file_comment: Box<str> = match is_utf8 {
-        true => String::from_utf8_lossy(&file_comment_raw).into(),
-        false => file_comment_raw.from_cp437(),
-    };
+        extents.sort_unstable();
+        let mut furthest: Option<(u64, usize)> = None;
+        for (start, end, index) in extents {
+            match furthest {
+                Some((furthest_end, other)) if start < furthest_end => {
+                    anomalies.push(ArchiveAnomaly::OverlappingEntries {
+                        first: other.min(index),
+                        second: other.max(index),
+                    });
+                    if end > furthest_end {
+                        furthest = Some((end, index));
+                    }
+                }
+                Some((furthest_end, _)) if end <= furthest_end => {}
+                _ => furthest = Some((end, index)),
+            }
+        }
 
-    // Construct the result
-    let mut result = ZipFileData {
-        system: System::from((version_made_by >> 8) as u8),
-        /* NB: this strips the top 8 bits! */
-        version_made_by: version_made_by as u8,
-        encrypted,
-        using_data_descriptor,
-        is_utf8,
-        compression_method: CompressionMethod::parse_from_u16(compression_method),
-        compression_level: None,
-        last_modified_time: DateTime::try_from_msdos(last_mod_date, last_mod_time).ok(),
-        crc32,
-        compressed_size: compressed_size.into(),
-        uncompressed_size: uncompressed_size.into(),
-        file_name,
-        file_name_raw,
-        extra_field: Some(Arc::new(extra_field.to_vec())),
-        central_extra_field: None,
-        file_comment,
-        header_start: offset.into(),
-        extra_data_start: None,
-        central_header_start,
-        data_start: OnceLock::new(),
-        external_attributes: external_file_attributes,
-        large_file: false,
-        aes_mode: None,
-        aes_extra_data_start: 0,
-        extra_fields: Vec::new(),
-    };
-    match parse_extra_field(&mut result) {
-        Ok(stripped_extra_field) => {
-            result.extra_field = stripped_extra_field;
+        let (uncompressed_size, compressed_size) =
+            self.shared
+                .files
+                .values()
+                .fold((0u64, 0u64), |(uncompressed, compressed), data| {
+                    (
+                        uncompressed.saturating_add(data.uncompressed_size),
+                        compressed.saturating_add(data.compressed_size),
+                    )
+                });
+        if uncompressed_size > compressed_size.saturating_mul(ArchiveAnomaly::SUSPICIOUS_RATIO) {
+            anomalies.push(ArchiveAnomaly::HighCompressionRatio {
+                uncompressed_size,
+                compressed_size,
+            });
         }
-        Err(ZipError::Io(..)) => {}
-        Err(e) => return Err(e),
+        Ok(anomalies)
     }
 
-    let aes_enabled = result.compression_method == CompressionMethod::AES;
-    if aes_enabled && result.aes_mode.is_none() {
-        return Err(ZipError::InvalidArchive(
-            "AES encryption without AES extra data field",
-        ));
+    /// Count the bytes between the start of the archive and its central directory that don't
+    /// belong to any entry, such as those left by entries removed without rewriting the archive.
+    /// This is the sum of the gaps between the end of each entry, including its data descriptor,
+    /// and the local header of the next, plus the gaps before the first entry and after the last
+    /// one. It helps to decide whether an archive is worth repacking.
+    ///
+    /// Padding added by [`FileOptions::with_alignment`](crate::write::FileOptions::with_alignment)
+    /// is part of an entry's extra field, so it isn't counted. Overlapping entries count only
+    /// once, and entries after the central directory aren't counted.
+    pub fn dead_space(&mut self) -> ZipResult<u64> {
+        let mut extents: Vec<(u64, u64)> = Vec::with_capacity(self.len());
+        for data in self.shared.files.values() {
+            let data_start = match data.data_start.get() {
+                Some(data_start) => *data_start,
+                None => find_data_start(data, &mut self.reader)?,
+            };
+            let mut end = data_start
+                .checked_add(data.compressed_size)
+                .ok_or(InvalidArchive(
+                    "Entry data extends past the end of the archive",
+                ))?;
+            if data.using_data_descriptor {
+                let unsigned_len = if data.large_file { 20 } else { 12 };
+                self.reader.seek(SeekFrom::Start(end))?;
+                let mut signature = [0u8; mem::size_of::<spec::Magic>()];
+                self.reader.read_exact(&mut signature)?;
+                end += unsigned_len;
+                if spec::Magic::from_le_bytes(signature) == spec::Magic::DATA_DESCRIPTOR_SIGNATURE {
+                    end += signature.len() as u64;
+                }
+            }
+            extents.push((data.header_start, end));
+        }
+        extents.sort_unstable();
+        let mut dead_space = 0;
+        let mut position = self.shared.offset;
+        for (start, end) in extents {
+            if start >= self.shared.dir_start {
+                break;
+            }
+            dead_space += start.saturating_sub(position);
+            position = position.max(end);
+        }
+        dead_space += self.shared.dir_start.saturating_sub(position);
+        Ok(dead_space)
     }
 
-    // Account for shifted zip offsets.
-    result.header_start = result
-        .header_start
-        .checked_add(archive_offset)
-        .ok_or(ZipError::InvalidArchive("Archive header is too large"))?;
+    /// Estimate how large each file would be if recompressed with `target`, without recompressing
+    /// any file in full.
+    ///
+    /// Up to the first 64 KiB of each file is compressed at the default level of `target`, and the
+    /// ratio achieved on that sample is extrapolated to the rest of the file. The estimate is exact
+    /// for files no larger than the sample. Only compressed data is counted, not headers. Encrypted
+    /// files can't be sampled, so they're predicted to keep their current size.
+    pub fn recompression_estimate(
+        &mut self,
+        target: CompressionMethod,
+    ) -> ZipResult<RecompressionEstimate> {
+        const SAMPLE_LEN: u64 = 64 * 1024;
+        let mut entries = Vec::with_capacity(self.len());
+        for index in 0..self.len() {
+            let data = &self.shared.files[index];
+            let current_compressed_size = data.compressed_size;
+            let uncompressed_size = data.uncompressed_size;
+            let (predicted_compressed_size, exact) = if data.encrypted {
+                (current_compressed_size, false)
+            } else if data.is_dir() {
+                (0, true)
+            } else {
+                let mut sample = self.by_index(index)?.take(SAMPLE_LEN);
+                let compressed = crate::write::compressed_len(&mut sample, target)?;
+                let sample_len = SAMPLE_LEN - sample.limit();
+                if sample_len < SAMPLE_LEN || sample_len >= uncompressed_size {
+                    (compressed, true)
+                } else {
+                    let predicted = (compressed as u128 * uncompressed_size as u128)
+                        .div_ceil(sample_len as u128);
+                    (predicted.try_into().unwrap_or(u64::MAX), false)
+                }
+            };
+            entries.push(EntryEstimate {
+                current_compressed_size,
+                predicted_compressed_size,
+                exact,
+            });
+        }
+        Ok(RecompressionEstimate { entries })
+    }
 
-    Ok(result)
+    /// Call `f` on every entry in the archive, one at a time in central-directory order, and
+    /// return the first error encountered. This is the sequential counterpart of
+    /// [`ZipArchive::for_each_entry_parallel`], for readers that can't be cloned.
+    pub fn for_each_entry<F>(&mut self, mut f: F) -> ZipResult<()>
+    where
+        F: FnMut(ZipFile<'_>),
+    {
+        for index in 0..self.len() {
+            f(self.by_index(index)?);
+        }
+        Ok(())
+    }
+
+    /// Unwrap and return the inner reader object
+    ///
+    /// The position of the reader is undefined.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Convert this archive into one that reads through a [`SharedReader`], so that it can be
+    /// cloned even if `R` can't, and its clones used from several threads at once.
+    ///
+    /// All clones read from the same `R`, which is locked for the duration of each read call.
+    /// Since the decompressors read in bounded chunks, readers of different entries take turns
+    /// rather than waiting for each other to finish.
+    pub fn shared_reader(self) -> ZipArchive<SharedReader<R>> {
+        ZipArchive {
+            reader: SharedReader::new(self.reader),
+            shared: self.shared,
+            comment: self.comment,
+        }
+    }
 }
 
-pub(crate) fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<Option<Arc<Vec<u8>>>> {
-    let Some(ref extra_field) = file.extra_field else {
-        return Ok(None);
-    };
-    let extra_field = extra_field.clone();
-    let mut processed_extra_field = extra_field.clone();
-    let len = extra_field.len();
-    let mut reader = io::Cursor::new(&**extra_field);
+impl<R: Read + Seek + Clone + Send + Sync> ZipArchive<R> {
+    /// Call `f` on every entry in the archive, reading entries concurrently on the rayon thread
+    /// pool if the `rayon` feature is enabled, and one at a time otherwise.
+    ///
+    /// Each worker reads through its own clone of the reader, while the parsed central directory
+    /// is shared, so this is only cheap when cloning `R` is (e.g. `Cursor<Arc<[u8]>>`, or a
+    /// `Cursor<Vec<u8>>` for small archives). Readers that aren't `Clone` can be read in parallel
+    /// through [`ZipArchive::shared_reader`], or sequentially with
+    /// [`ZipArchive::for_each_entry`]. Entries are visited in no particular order, and the first
+    /// error encountered is returned.
+    pub fn for_each_entry_parallel<F>(&self, f: F) -> ZipResult<()>
+    where
+        F: Fn(ZipFile<'_>) + Sync,
+    {
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
 
-    /* TODO: codify this structure into Zip64ExtraFieldBlock fields! */
-    let mut position = reader.position() as usize;
-    while (position) < len {
-        let old_position = position;
-        let remove = parse_single_extra_field(file, &mut reader, position as u64, false)?;
-        position = reader.position() as usize;
-        if remove {
-            let remaining = len - (position - old_position);
-            if remaining == 0 {
-                return Ok(None);
-            }
-            let mut new_extra_field = Vec::with_capacity(remaining);
-            new_extra_field.extend_from_slice(&extra_field[0..old_position]);
-            new_extra_field.extend_from_slice(&extra_field[position..]);
-            processed_extra_field = Arc::new(new_extra_field);
+            (0..self.len()).into_par_iter().try_for_each_init(
+                || self.clone(),
+                |archive, index| {
+                    f(archive.by_index(index)?);
+                    Ok(())
+                },
+            )
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            self.clone().for_each_entry(f)
         }
     }
-    Ok(Some(processed_extra_field))
 }
 
-pub(crate) fn parse_single_extra_field<R: Read>(
-    file: &mut ZipFileData,
-    reader: &mut R,
-    bytes_already_read: u64,
-    disallow_zip64: bool,
-) -> ZipResult<bool> {
+impl<R: Read + Seek + Clone> ZipArchive<R> {
+    /// Iterate over the entries of the archive, reading each one through its own clone of the
+    /// underlying reader.
+    ///
+    /// Entries returned by [`ZipArchive::by_index`] borrow the archive mutably, since they're all
+    /// read by seeking the same reader. The entries yielded here only borrow it immutably, so
+    /// several can be held at once and read in an interleaved order. This requires `R: Clone`,
+    /// where each clone keeps its own position, as [`io::Cursor`] does. An entry's local header
+    /// is only read, and its reader only created, when the iterator reaches it.
+    ///
+    /// Encrypted entries are yielded as errors.
+    pub fn iter_entries_cloned(&self) -> impl Iterator<Item = ZipResult<ZipFile<'_>>> + '_ {
+        self.shared.files.values().map(|data| {
+            if data.encrypted {
+                return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED));
+            }
+            let mut reader = self.reader.clone();
+            let data_start = match data.data_start.get() {
+                Some(data_start) => *data_start,
+                None => find_data_start(data, &mut reader)?,
+            };
+            reader.seek(SeekFrom::Start(data_start))?;
+            let reader = SeekableEntryReader(reader);
+            let limit_reader =
+                (Box::new(reader) as Box<dyn EntryReader>).take(data.compressed_size);
+            let crypto_reader = make_crypto_reader(
+                data.compression_method,
+                data.crc32,
+                data.last_modified_time,
+                data.using_data_descriptor,
+                limit_reader,
+                None,
+                data.aes_mode,
+                #[cfg(feature = "aes-crypto")]
+                data.compressed_size,
+            )?;
+            Ok(ZipFile {
+                crypto_reader: Some(crypto_reader),
+                reader: ZipFileReader::NoReader,
+                data: Cow::Borrowed(data),
+                data_descriptor: None,
+                streaming: false,
+            })
+        })
+    }
+}
+
+impl<R: Read + Write + Seek> ZipArchive<R> {
+    /// Replace the comment of the archive in `handle`, rewriting only its end-of-central-directory
+    /// record and the comment that follows it.
+    ///
+    /// Since the comment is the last thing in an archive, nothing else needs to move. Returns the
+    /// new length of the archive; when the comment gets shorter, the caller must truncate the
+    /// underlying storage to that length (for example with [`std::fs::File::set_len`]), since
+    /// that isn't possible through the `Write` and `Seek` traits. An archive with data after its
+    /// comment is rejected rather than overwritten.
+    pub fn update_comment(mut handle: R, comment: &[u8]) -> ZipResult<u64> {
+        let comment_length: u16 = comment
+            .len()
+            .try_into()
+            .map_err(|_| InvalidArchive("File comment must be less than 64 KiB"))?;
+        let (footer, _) = Self::get_metadata(Config::default(), &mut handle)?;
+        let file_length = handle.seek(SeekFrom::End(0))?;
+        let footer_start = file_length
+            .checked_sub(size_of::<Zip32CDEBlock>() as u64 + footer.zip_file_comment.len() as u64)
+            .ok_or(InvalidArchive("Archive is shorter than its footer"))?;
+        handle.seek(SeekFrom::Start(footer_start))?;
+        let mut block = Zip32CDEBlock::parse(&mut handle)
+            .map_err(|_| InvalidArchive("Archive has data after its comment"))?;
+        if block.zip_file_comment_length as usize != footer.zip_file_comment.len() {
+            return Err(InvalidArchive("Archive has data after its comment"));
+        }
+        block.zip_file_comment_length = comment_length;
+        handle.seek(SeekFrom::Start(footer_start))?;
+        block.write(&mut handle)?;
+        handle.write_all(comment)?;
+        Ok(handle.stream_position()?)
+    }
+}
+
+/// Holds the AES information of a file in the zip archive
+#[derive(Debug)]
+#[cfg(feature = "aes-crypto")]
+pub struct AesInfo {
+    /// The AES encryption mode
+    pub aes_mode: AesMode,
+    /// The verification key
+    pub verification_value: [u8; PWD_VERIFY_LENGTH],
+    /// The salt
+    pub salt: Vec<u8>,
+}
+
+/// An entry whose local file header disagrees with its central directory record, as reported by
+/// [`ZipArchive::validate_local_headers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalHeaderDiscrepancy {
+    /// The index of the entry in the central directory
+    pub index: usize,
+    /// The name of the entry according to the central directory
+    pub name: Box<str>,
+    /// The field that differs
+    pub mismatch: LocalHeaderMismatch,
+}
+
+/// A field whose value in a local file header differs from the central directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LocalHeaderMismatch {
+    /// The compression method differs
+    CompressionMethod {
+        /// Value in the local file header
+        local: CompressionMethod,
+        /// Value in the central directory
+        central: CompressionMethod,
+    },
+    /// The CRC32 checksum differs
+    Crc32 {
+        /// Value in the local file header
+        local: u32,
+        /// Value in the central directory
+        central: u32,
+    },
+    /// The compressed size differs
+    CompressedSize {
+        /// Value in the local file header
+        local: u64,
+        /// Value in the central directory
+        central: u64,
+    },
+    /// The uncompressed size differs
+    UncompressedSize {
+        /// Value in the local file header
+        local: u64,
+        /// Value in the central directory
+        central: u64,
+    },
+    /// The raw file name differs
+    FileName {
+        /// Value in the local file header
+        local: Box<[u8]>,
+        /// Value in the central directory
+        central: Box<[u8]>,
+    },
+}
+
+/// The contents of a data descriptor, as returned by [`ZipArchive::read_data_descriptor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataDescriptor {
+    /// CRC-32 checksum of the uncompressed data
+    pub crc32: u32,
+    /// Size of the compressed data
+    pub compressed_size: u64,
+    /// Size of the uncompressed data
+    pub uncompressed_size: u64,
+}
+
+impl DataDescriptor {
+    /// Parses a data descriptor without its signature.
+    fn parse(bytes: &[u8], large_file: bool) -> ZipResult<Self> {
+        let mut reader = bytes;
+        let crc32 = reader.read_u32_le()?;
+        let (compressed_size, uncompressed_size) = if large_file {
+            (reader.read_u64_le()?, reader.read_u64_le()?)
+        } else {
+            (reader.read_u32_le()?.into(), reader.read_u32_le()?.into())
+        };
+        Ok(DataDescriptor {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+        })
+    }
+}
+
+/// Predicted sizes after recompressing an archive, as returned by
+/// [`ZipArchive::recompression_estimate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecompressionEstimate {
+    /// The estimate for each entry, in central directory order
+    pub entries: Vec<EntryEstimate>,
+}
+
+impl RecompressionEstimate {
+    /// Total compressed size of all entries as they are now
+    pub fn current_total(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.current_compressed_size)
+            .sum()
+    }
+
+    /// Predicted total compressed size of all entries after recompressing
+    pub fn predicted_total(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.predicted_compressed_size)
+            .sum()
+    }
+}
+
+/// Predicted size of one entry after recompressing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryEstimate {
+    /// Compressed size of the entry as it is now
+    pub current_compressed_size: u64,
+    /// Predicted compressed size after recompressing
+    pub predicted_compressed_size: u64,
+    /// Whether the whole entry was compressed, rather than a sample of it
+    pub exact: bool,
+}
+
+/// The raw bytes of a record in an archive, as returned by [`ZipArchive::eocd_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordBytes {
+    /// Offset of the record from the start of the reader
+    pub start: u64,
+    /// The record, signature included
+    pub bytes: Box<[u8]>,
+}
+
+impl RecordBytes {
+    fn read<R: Read + Seek>(reader: &mut R, start: u64, len: u64) -> ZipResult<Self> {
+        reader.seek(SeekFrom::Start(start))?;
+        let mut bytes = Vec::new();
+        reader.take(len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < len {
+            return Err(ZipError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        Ok(RecordBytes {
+            start,
+            bytes: bytes.into_boxed_slice(),
+        })
+    }
+
+    /// The range of offsets the record occupies in the reader
+    pub fn range(&self) -> Range<u64> {
+        self.start..self.start + self.bytes.len() as u64
+    }
+}
+
+/// The disk-number fields of an archive's end-of-central-directory records, as returned by
+/// [`ZipArchive::disk_info`]. Archives spanning several disks aren't supported, but these help
+/// explain why one was rejected or where it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DiskInfo {
+    /// The number of the disk holding the end-of-central-directory record, counting from 0
+    pub disk_number: u32,
+    /// The number of the disk on which the central directory starts
+    pub disk_with_central_directory: u32,
+    /// The total number of disks: from the ZIP64 locator if there is one, otherwise one more
+    /// than `disk_number`, since the end-of-central-directory record is on the last disk
+    pub number_of_disks: u32,
+}
+
+/// The end-of-central-directory records of an archive, as returned by
+/// [`ZipArchive::eocd_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EocdBytes {
+    /// The ZIP32 end-of-central-directory record, followed by the archive comment
+    pub eocd: RecordBytes,
+    /// The ZIP64 end-of-central-directory record, including any extensible data sector
+    pub zip64_eocd: Option<RecordBytes>,
+    /// The ZIP64 end-of-central-directory locator
+    pub zip64_locator: Option<RecordBytes>,
+}
+
+/// A suspicious structure found by [`ZipArchive::detect_anomalies`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ArchiveAnomaly {
+    /// The local headers or data of two entries, given by their indices, overlap. Zip bombs use
+    /// this to reuse one compressed stream for many entries, and zip quines to make an entry
+    /// contain the archive itself.
+    OverlappingEntries {
+        /// The entry with the lower index
+        first: usize,
+        /// The entry with the higher index
+        second: usize,
+    },
+    /// The local header or data of the entry with the given index overlaps the central
+    /// directory.
+    EntryContainsCentralDirectory {
+        /// Index of the entry
+        index: usize,
+    },
+    /// The local header of the entry with the given index doesn't have the right signature, or
+    /// runs past the end of the archive.
+    InvalidLocalHeader {
+        /// Index of the entry
+        index: usize,
+    },
+    /// The files' total uncompressed size is more than
+    /// [`ArchiveAnomaly::SUSPICIOUS_RATIO`] times their total compressed size.
+    HighCompressionRatio {
+        /// Total uncompressed size of the files
+        uncompressed_size: u64,
+        /// Total compressed size of the files
+        compressed_size: u64,
+    },
+}
+
+impl ArchiveAnomaly {
+    /// The ratio of uncompressed to compressed size above which an archive is reported, which is
+    /// about as much as Deflate can achieve on a single stream of zeros.
+    pub const SUSPICIOUS_RATIO: u64 = 1000;
+}
+
+/// An entry found by [`list_from_local_headers`]. Its sizes and CRC-32 are taken from its data
+/// descriptor if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalEntryInfo {
+    /// Offset of the local file header from the start of the reader
+    pub header_start: u64,
+    /// The name of the entry
+    pub name: Box<str>,
+    /// The compression method
+    pub compression_method: CompressionMethod,
+    /// CRC-32 checksum of the uncompressed data
+    pub crc32: u32,
+    /// Size of the compressed data
+    pub compressed_size: u64,
+    /// Size of the uncompressed data
+    pub uncompressed_size: u64,
+    /// Last modified time, if the stored value is valid
+    pub last_modified_time: Option<DateTime>,
+    /// Whether the entry is encrypted
+    pub encrypted: bool,
+    /// Whether the entry has a data descriptor
+    pub using_data_descriptor: bool,
+}
+
+/// Metadata of an entry from the central directory, as returned by [`ZipArchive::entries`]. See
+/// [`crate::write::EntryMetadata`] for an owned version used by [`crate::write::repack`].
+#[derive(Clone, Copy, Debug)]
+pub struct EntryInfo<'a> {
+    data: &'a ZipFileData,
+}
+
+impl<'a> EntryInfo<'a> {
+    /// Get the name of the entry. The same warnings apply as for [`ZipFile::name`].
+    pub fn name(&self) -> &'a str {
+        &self.data.file_name
+    }
+
+    /// Get the compression method used to store the entry
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the size of the entry, in bytes, in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the entry, in bytes, when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+
+    /// Get the CRC32 hash of the original entry
+    pub fn crc32(&self) -> u32 {
+        self.data.crc32
+    }
+
+    /// Get the time the entry was last modified
+    pub fn last_modified(&self) -> Option<DateTime> {
+        self.data.last_modified_time
+    }
+
+    /// Returns whether the entry is a directory
+    pub fn is_dir(&self) -> bool {
+        self.data.is_dir()
+    }
+}
+
+/// Reader over the decompressed contents of an entry that knows their total length, as returned
+/// by [`ZipArchive::by_index_sized`].
+pub struct SizedRead<'a> {
+    file: ZipFile<'a>,
+    len: Option<u64>,
+}
+
+impl<'a> SizedRead<'a> {
+    /// The total decompressed length of the entry, as declared in the central directory, or
+    /// `None` if the entry has a data descriptor, in which case it may not be reliable. Like
+    /// [`ZipArchive::decompressed_size`], this doesn't change as the contents are read.
+    pub const fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Whether the entry is known to be empty.
+    pub const fn is_empty(&self) -> bool {
+        matches!(self.len, Some(0))
+    }
+
+    /// Returns the entry being read.
+    pub fn into_inner(self) -> ZipFile<'a> {
+        self.file
+    }
+}
+
+impl Read for SizedRead<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+/// Reader over the decompressed contents of an entry that fails rather than produce more than a
+/// given number of bytes, as returned by [`ZipArchive::by_index_with_limit`].
+pub struct LimitedRead<'a> {
+    file: ZipFile<'a>,
+    remaining: u64,
+}
+
+impl<'a> LimitedRead<'a> {
+    /// The message of the [`ZipError::InvalidArchive`] returned once the limit is exceeded.
+    pub const LIMIT_EXCEEDED: &'static str = "Decompressed size limit exceeded";
+
+    /// The number of bytes that can still be read before the limit is exceeded.
+    pub const fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Returns the entry being read.
+    pub fn into_inner(self) -> ZipFile<'a> {
+        self.file
+    }
+}
+
+impl Read for LimitedRead<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Ask for one byte more than allowed, so that exceeding the limit is noticed
+        let max = usize::try_from(self.remaining.saturating_add(1)).unwrap_or(usize::MAX);
+        let len = buf.len().min(max);
+        let count = self.file.read(&mut buf[..len])?;
+        if count as u64 > self.remaining {
+            self.remaining = 0;
+            return Err(InvalidArchive(Self::LIMIT_EXCEEDED).into());
+        }
+        self.remaining -= count as u64;
+        Ok(count)
+    }
+}
+
+/// Reader over the contents of a stored entry, as used by [`ZipArchive::nested_archive`].
+#[derive(Debug)]
+pub struct NestedArchiveReader<'a, R> {
+    inner: &'a mut R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl<'a, R: Read + Seek> Read for NestedArchiveReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let max = remaining.min(buf.len() as u64) as usize;
+        self.inner.seek(SeekFrom::Start(self.start + self.pos))?;
+        let count = self.inner.read(&mut buf[..max])?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for NestedArchiveReader<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// A reader shared between clones through an `Arc<Mutex<R>>`, as used by
+/// [`ZipArchive::shared_reader`].
+///
+/// Each clone has its own position: every read locks the underlying reader, seeks it to that
+/// position, reads once and releases the lock.
+#[derive(Debug)]
+pub struct SharedReader<R> {
+    inner: Arc<Mutex<R>>,
+    pos: u64,
+}
+
+impl<R> SharedReader<R> {
+    /// Wrap `inner` so that it can be shared, starting at position 0.
+    pub fn new(inner: R) -> Self {
+        SharedReader {
+            inner: Arc::new(Mutex::new(inner)),
+            pos: 0,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, R> {
+        // Every access seeks before reading, so a panic while the lock was held can't have left
+        // the reader in a state that matters
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<R> Clone for SharedReader<R> {
+    fn clone(&self) -> Self {
+        SharedReader {
+            inner: self.inner.clone(),
+            pos: self.pos,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = {
+            let mut inner = self.lock();
+            inner.seek(SeekFrom::Start(self.pos))?;
+            inner.read(buf)?
+        };
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Seek> Seek for SharedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => Some(self.lock().seek(SeekFrom::End(offset))?),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// Writer that feeds everything written through it into a [`Digest`].
+#[cfg(feature = "digest")]
+struct DigestWriter<W, D> {
+    inner: W,
+    hasher: D,
+}
+
+#[cfg(feature = "digest")]
+impl<W: Write, D: Digest> Write for DigestWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.hasher.update(&buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+const fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
+    Err(ZipError::UnsupportedArchive(detail))
+}
+
+/// Parse a central directory entry to collect the information for the file.
+pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
+    reader: &mut R,
+    archive_offset: u64,
+    config: &Config,
+) -> ZipResult<ZipFileData> {
+    let central_header_start = reader.stream_position()?;
+
+    // Parse central header
+    let block = ZipCentralEntryBlock::parse(reader)?;
+    let file = central_header_to_zip_file_inner(
+        reader,
+        archive_offset,
+        central_header_start,
+        block,
+        config,
+    )?;
+    let central_header_end = reader.stream_position()?;
+    let data_start = find_data_start(&file, reader)?;
+    file.data_start.get_or_init(|| data_start);
+    reader.seek(SeekFrom::Start(central_header_end))?;
+    Ok(file)
+}
+
+/// Parse `count` central directory entries stored apart from the archive in `reader`, as in an
+/// [`IndexSnapshot`](crate::write::IndexSnapshot). The local headers are read from `reader` to
+/// find where each file's data starts.
+pub(crate) fn detached_central_directory_to_files<R: Read + Seek>(
+    central_directory: &[u8],
+    count: u64,
+    archive_offset: u64,
+    reader: &mut R,
+) -> ZipResult<IndexMap<Box<str>, ZipFileData>> {
+    let mut central_directory = Cursor::new(central_directory);
+    let mut files = IndexMap::new();
+    for _ in 0..count {
+        let block = ZipCentralEntryBlock::parse(&mut central_directory)?;
+        let file = central_header_to_zip_file_inner(
+            &mut central_directory,
+            archive_offset,
+            0,
+            block,
+            &Config::default(),
+        )?;
+        find_data_start(&file, reader)?;
+        if files.insert(file.file_name.clone(), file).is_some() {
+            return Err(ZipError::InvalidArchive("Duplicate filename"));
+        }
+    }
+    Ok(files)
+}
+
+#[inline]
+fn read_variable_length_byte_field<R: Read>(reader: &mut R, len: usize) -> io::Result<Box<[u8]>> {
+    let mut data = vec![0; len].into_boxed_slice();
+    reader.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Parse a central directory entry to collect the information for the file.
+fn central_header_to_zip_file_inner<R: Read>(
+    reader: &mut R,
+    archive_offset: u64,
+    central_header_start: u64,
+    block: ZipCentralEntryBlock,
+    config: &Config,
+) -> ZipResult<ZipFileData> {
+    let legacy_encoding = config.legacy_encoding;
+    let ZipCentralEntryBlock {
+        // magic,
+        version_made_by,
+        // version_to_extract,
+        flags,
+        compression_method,
+        last_mod_time,
+        last_mod_date,
+        crc32,
+        compressed_size,
+        uncompressed_size,
+        file_name_length,
+        extra_field_length,
+        file_comment_length,
+        // disk_number,
+        // internal_file_attributes,
+        external_file_attributes,
+        offset,
+        ..
+    } = block;
+
+    let encrypted = flags & 1 == 1;
+    let is_utf8 = flags & (1 << 11) != 0;
+    let using_data_descriptor = flags & (1 << 3) != 0;
+
+    let file_name_raw = read_variable_length_byte_field(reader, file_name_length as usize)?;
+    let extra_field = read_variable_length_byte_field(reader, extra_field_length as usize)?;
+    let file_comment_raw = read_variable_length_byte_field(reader, file_comment_length as usize)?;
+    let file_name: Box<str> = match is_utf8 {
+        true => String::from_utf8_lossy(&file_name_raw).into(),
+        false => legacy_encoding.decode(file_name_raw.clone()),
+    };
+    let file_comment: Box<str> = match is_utf8 {
+        true => String::from_utf8_lossy(&file_comment_raw).into(),
+        false => legacy_encoding.decode(file_comment_raw.clone()),
+    };
+
+    // Construct the result
+    let mut result = ZipFileData {
+        system: System::from((version_made_by >> 8) as u8),
+        /* NB: this strips the top 8 bits! */
+        version_made_by: version_made_by as u8,
+        encrypted,
+        using_data_descriptor,
+        is_utf8,
+        compression_method: CompressionMethod::parse_from_u16(compression_method),
+        compression_level: None,
+        deflate_level_hint: DeflateLevelHint::from_flags(
+            CompressionMethod::parse_from_u16(compression_method),
+            flags,
+        ),
+        last_modified_time: DateTime::try_from_msdos(last_mod_date, last_mod_time).ok(),
+        crc32,
+        compressed_size: compressed_size.into(),
+        uncompressed_size: uncompressed_size.into(),
+        file_name,
+        file_name_raw,
+        extra_field: Some(Arc::new(extra_field.to_vec())),
+        central_extra_field: None,
+        local_extra_field: None,
+        file_comment,
+        header_start: offset.into(),
+        extra_data_start: None,
+        central_header_start,
+        data_start: OnceLock::new(),
+        external_attributes: external_file_attributes,
+        large_file: false,
+        aes_mode: None,
+        aes_extra_data_start: 0,
+        version_needed_override: None,
+        extra_fields: Vec::new(),
+        unlisted_extra_fields: Vec::new(),
+    };
+    match parse_extra_field(&mut result, config) {
+        Ok(stripped_extra_field) => {
+            result.extra_field = stripped_extra_field;
+        }
+        Err(ZipError::Io(..)) => {}
+        Err(e) => return Err(e),
+    }
+    if legacy_encoding == LegacyEncoding::Auto && !result.is_utf8 {
+        if let Some(code_page) = result.code_page() {
+            if let Some(file_name) =
+                LegacyEncoding::decode_code_page(code_page, &result.file_name_raw)
+            {
+                result.file_name = file_name;
+            }
+            if let Some(file_comment) =
+                LegacyEncoding::decode_code_page(code_page, &file_comment_raw)
+            {
+                result.file_comment = file_comment;
+            }
+        }
+    }
+
+    let aes_enabled = result.compression_method == CompressionMethod::AES;
+    if aes_enabled && result.aes_mode.is_none() {
+        return Err(ZipError::InvalidArchive(
+            "AES encryption without AES extra data field",
+        ));
+    }
+    if !result.large_file
+        && (result.uncompressed_size == spec::ZIP64_BYTES_THR
+            || result.compressed_size == spec::ZIP64_BYTES_THR)
+    {
+        return Err(InvalidArchive(
+            "ZIP64 size sentinel without ZIP64 extra field",
+        ));
+    }
+
+    // Account for shifted zip offsets.
+    result.header_start = result
+        .header_start
+        .checked_add(archive_offset)
+        .ok_or(ZipError::InvalidArchive("Archive header is too large"))?;
+
+    Ok(result)
+}
+
+pub(crate) fn parse_extra_field(
+    file: &mut ZipFileData,
+    config: &Config,
+) -> ZipResult<Option<Arc<Vec<u8>>>> {
+    let Some(ref extra_field) = file.extra_field else {
+        return Ok(None);
+    };
+    let extra_field = extra_field.clone();
+    let mut processed_extra_field = extra_field.clone();
+    let len = extra_field.len();
+    let mut reader = io::Cursor::new(&**extra_field);
+
+    /* TODO: codify this structure into Zip64ExtraFieldBlock fields! */
+    let mut position = reader.position() as usize;
+    let mut field_count = 0;
+    while (position) < len {
+        if field_count == config.max_extra_fields_per_entry {
+            if config.truncate_extra_fields {
+                break;
+            }
+            return Err(InvalidArchive("Entry has too many extra fields"));
+        }
+        field_count += 1;
+        let old_position = position;
+        let remove = parse_single_extra_field(file, &mut reader, position as u64, false)?;
+        position = reader.position() as usize;
+        if remove {
+            let remaining = len - (position - old_position);
+            if remaining == 0 {
+                return Ok(None);
+            }
+            let mut new_extra_field = Vec::with_capacity(remaining);
+            new_extra_field.extend_from_slice(&extra_field[0..old_position]);
+            new_extra_field.extend_from_slice(&extra_field[position..]);
+            processed_extra_field = Arc::new(new_extra_field);
+        }
+    }
+    Ok(Some(processed_extra_field))
+}
+
+pub(crate) fn parse_single_extra_field<R: Read>(
+    file: &mut ZipFileData,
+    reader: &mut R,
+    bytes_already_read: u64,
+    disallow_zip64: bool,
+) -> ZipResult<bool> {
     let kind = reader.read_u16_le()?;
     let len = reader.read_u16_le()?;
     match kind {
@@ -1344,624 +3117,2791 @@ pub(crate) fn parse_single_extra_field<R: Read>(
         0x0001 => {
             if disallow_zip64 {
                 return Err(InvalidArchive(
-                    "Can't write a custom field using the ZIP64 ID",
+                    "Can't write a custom field using the ZIP64 ID",
+                ));
+            }
+            let mut consumed_len = 0;
+            if len >= 24 || file.uncompressed_size == spec::ZIP64_BYTES_THR {
+                file.large_file = true;
+                file.uncompressed_size = reader.read_u64_le()?;
+                consumed_len += size_of::<u64>();
+            }
+            if len >= 24 || file.compressed_size == spec::ZIP64_BYTES_THR {
+                file.large_file = true;
+                file.compressed_size = reader.read_u64_le()?;
+                consumed_len += size_of::<u64>();
+            }
+            if len >= 24 || file.header_start == spec::ZIP64_BYTES_THR {
+                file.header_start = reader.read_u64_le()?;
+                consumed_len += size_of::<u64>();
+            }
+            let Some(leftover_len) = (len as usize).checked_sub(consumed_len) else {
+                return Err(InvalidArchive("ZIP64 extra-data field is the wrong length"));
+            };
+            reader.read_exact(&mut vec![0u8; leftover_len])?;
+            return Ok(true);
+        }
+        0x9901 => {
+            // AES
+            if len != 7 {
+                return Err(ZipError::UnsupportedArchive(
+                    "AES extra data field has an unsupported length",
+                ));
+            }
+            let vendor_version = reader.read_u16_le()?;
+            let vendor_id = reader.read_u16_le()?;
+            let mut out = [0u8];
+            reader.read_exact(&mut out)?;
+            let aes_mode = out[0];
+            let compression_method = CompressionMethod::parse_from_u16(reader.read_u16_le()?);
+
+            if vendor_id != 0x4541 {
+                return Err(ZipError::InvalidArchive("Invalid AES vendor"));
+            }
+            let vendor_version = match vendor_version {
+                0x0001 => AesVendorVersion::Ae1,
+                0x0002 => AesVendorVersion::Ae2,
+                _ => return Err(ZipError::InvalidArchive("Invalid AES vendor version")),
+            };
+            match aes_mode {
+                0x01 => file.aes_mode = Some((AesMode::Aes128, vendor_version, compression_method)),
+                0x02 => file.aes_mode = Some((AesMode::Aes192, vendor_version, compression_method)),
+                0x03 => file.aes_mode = Some((AesMode::Aes256, vendor_version, compression_method)),
+                _ => return Err(ZipError::InvalidArchive("Invalid AES encryption strength")),
+            };
+            file.compression_method = compression_method;
+            file.aes_extra_data_start = bytes_already_read;
+        }
+        0x5455 => {
+            // extended timestamp
+            // https://libzip.org/specifications/extrafld.txt
+
+            file.extra_fields.push(ExtraField::ExtendedTimestamp(
+                ExtendedTimestamp::try_from_reader(reader, len)?,
+            ));
+        }
+        0x000a => {
+            // NTFS timestamps
+            // APPNOTE 4.5.5
+            if let Some(ntfs) = Ntfs::try_from_reader(reader, len)? {
+                file.unlisted_extra_fields
+                    .push(UnlistedExtraField::Ntfs(ntfs));
+            }
+        }
+        0x6375 => {
+            // Info-ZIP Unicode Comment Extra Field
+            // APPNOTE 4.6.8 and https://libzip.org/specifications/extrafld.txt
+            file.file_comment = String::from_utf8(
+                UnicodeExtraField::try_from_reader(reader, len)?
+                    .unwrap_valid(file.file_comment.as_bytes())?
+                    .into_vec(),
+            )?
+            .into();
+        }
+        0xe57a => {
+            // Korean code page information
+            if len < 2 {
+                return Err(InvalidArchive("Korean code page extra field is too small"));
+            }
+            let code_page = reader.read_u16_le()?;
+            reader.read_exact(&mut vec![0u8; len as usize - 2])?;
+            file.unlisted_extra_fields
+                .push(UnlistedExtraField::KoreanCodePage(code_page));
+        }
+        0x7075 => {
+            // Info-ZIP Unicode Path Extra Field
+            // APPNOTE 4.6.9 and https://libzip.org/specifications/extrafld.txt
+            file.file_name_raw = UnicodeExtraField::try_from_reader(reader, len)?
+                .unwrap_valid(&file.file_name_raw)?;
+            file.file_name =
+                String::from_utf8(file.file_name_raw.clone().into_vec())?.into_boxed_str();
+            file.is_utf8 = true;
+        }
+        _ => {
+            reader.read_exact(&mut vec![0u8; len as usize])?;
+            // Other fields are ignored
+        }
+    }
+    Ok(false)
+}
+
+/// Methods for retrieving information on zip files
+impl<'a> ZipFile<'a> {
+    fn get_reader(&mut self) -> ZipResult<&mut ZipFileReader<'a>> {
+        if let ZipFileReader::NoReader = self.reader {
+            let data = &self.data;
+            let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
+            self.reader = make_reader(data.compression_method, data.crc32, crypto_reader)?;
+        }
+        Ok(&mut self.reader)
+    }
+
+    pub(crate) fn get_raw_reader(&mut self) -> &mut dyn Read {
+        if let ZipFileReader::NoReader = self.reader {
+            let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
+            self.reader = ZipFileReader::Raw(crypto_reader.into_inner())
+        }
+        &mut self.reader
+    }
+
+    /// Get the version of the file
+    pub fn version_made_by(&self) -> (u8, u8) {
+        (
+            self.data.version_made_by / 10,
+            self.data.version_made_by % 10,
+        )
+    }
+
+    /// Get the name of the file
+    ///
+    /// # Warnings
+    ///
+    /// It is dangerous to use this name directly when extracting an archive.
+    /// It may contain an absolute path (`/etc/shadow`), or break out of the
+    /// current directory (`../runtime`). Carelessly writing to these paths
+    /// allows an attacker to craft a ZIP archive that will overwrite critical
+    /// files.
+    ///
+    /// You can use the [`ZipFile::enclosed_name`] method to validate the name
+    /// as a safe path.
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// Get the name of the file, in the raw (internal) byte representation.
+    ///
+    /// The encoding of this data is currently undefined.
+    pub fn name_raw(&self) -> &[u8] {
+        &self.data.file_name_raw
+    }
+
+    /// Get the name of the file in a sanitized form. It truncates the name to the first NULL byte,
+    /// removes a leading '/' and removes '..' parts.
+    #[deprecated(
+        since = "0.5.7",
+        note = "by stripping `..`s from the path, the meaning of paths can change.
+                `mangled_name` can be used if this behaviour is desirable"
+    )]
+    pub fn sanitized_name(&self) -> PathBuf {
+        self.mangled_name()
+    }
+
+    /// Rewrite the path, ignoring any path components with special meaning.
+    ///
+    /// - Absolute paths are made relative
+    /// - [`ParentDir`]s are ignored
+    /// - Truncates the filename at a NULL byte
+    ///
+    /// This is appropriate if you need to be able to extract *something* from
+    /// any archive, but will easily misrepresent trivial paths like
+    /// `foo/../bar` as `foo/bar` (instead of `bar`). Because of this,
+    /// [`ZipFile::enclosed_name`] is the better option in most scenarios.
+    ///
+    /// [`ParentDir`]: `Component::ParentDir`
+    pub fn mangled_name(&self) -> PathBuf {
+        self.data.file_name_sanitized()
+    }
+
+    /// Ensure the file path is safe to use as a [`Path`].
+    ///
+    /// - It can't contain NULL bytes
+    /// - It can't resolve to a path outside the current directory
+    ///   > `foo/../bar` is fine, `foo/../../bar` is not.
+    /// - It can't be an absolute path
+    ///
+    /// This will read well-formed ZIP files correctly, and is resistant
+    /// to path-based exploits. It is recommended over
+    /// [`ZipFile::mangled_name`].
+    pub fn enclosed_name(&self) -> Option<PathBuf> {
+        self.data.enclosed_name()
+    }
+
+    /// Get the comment of the file
+    pub fn comment(&self) -> &str {
+        &self.data.file_comment
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the compression level hint from the general purpose flags
+    ///
+    /// Returns `None` unless the file is compressed with Deflate or Deflate64.
+    pub fn deflate_level_hint(&self) -> Option<DeflateLevelHint> {
+        self.data.deflate_level_hint
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+
+    /// Get the time the file was last modified
+    pub fn last_modified(&self) -> Option<DateTime> {
+        self.data.last_modified_time
+    }
+    /// Returns whether the file is actually a directory
+    pub fn is_dir(&self) -> bool {
+        is_dir(self.name())
+    }
+
+    /// Returns whether the file is actually a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.unix_mode()
+            .is_some_and(|mode| mode & S_IFLNK == S_IFLNK)
+    }
+
+    /// Returns whether the file is a normal file (i.e. not a directory or symlink)
+    pub fn is_file(&self) -> bool {
+        !self.is_dir() && !self.is_symlink()
+    }
+
+    /// Get unix mode for the file
+    pub fn unix_mode(&self) -> Option<u32> {
+        self.data.unix_mode()
+    }
+
+    /// Get the CRC32 hash of the original file
+    pub fn crc32(&self) -> u32 {
+        self.data.crc32
+    }
+
+    /// Returns the AES vendor version of the file, or `None` if it isn't encrypted with AES
+    pub fn aes_vendor_version(&self) -> Option<AesVendorVersion> {
+        self.data
+            .aes_mode
+            .map(|(_, vendor_version, _)| vendor_version)
+    }
+
+    /// Returns whether [`ZipFile::crc32`] can be trusted for a file encrypted with AES: `true`
+    /// for AE-1, and `false` for AE-2, which doesn't store a CRC-32. Returns `None` if the file
+    /// isn't encrypted with AES.
+    pub fn aes_crc_is_meaningful(&self) -> Option<bool> {
+        self.aes_vendor_version()
+            .map(|vendor_version| vendor_version == AesVendorVersion::Ae1)
+    }
+
+    /// Returns the data descriptor that followed the file's data, for a file read by
+    /// [`read_zipfile_from_stream_with_data_descriptor`]. This is `None` until the file has been
+    /// read to the end, and for files read any other way; for those in a [`ZipArchive`], use
+    /// [`ZipArchive::read_data_descriptor`].
+    pub fn data_descriptor(&self) -> Option<DataDescriptor> {
+        self.data_descriptor.as_ref()?.get().copied()
+    }
+
+    /// Get the extra data of the zip header for this file
+    pub fn extra_data(&self) -> Option<&[u8]> {
+        self.data.extra_field.as_ref().map(|v| v.deref().deref())
+    }
+
+    /// Get the starting offset of the data of the compressed file
+    pub fn data_start(&self) -> u64 {
+        *self.data.data_start.get().unwrap()
+    }
+
+    /// Get the starting offset of the zip header for this file
+    pub fn header_start(&self) -> u64 {
+        self.data.header_start
+    }
+    /// Get the starting offset of the zip header in the central directory for this file
+    pub fn central_header_start(&self) -> u64 {
+        self.data.central_header_start
+    }
+
+    /// iterate through all extra fields
+    pub fn extra_data_fields(&self) -> impl Iterator<Item = &ExtraField> {
+        self.data.extra_fields.iter()
+    }
+
+    /// Returns the entry's extended timestamp (0x5455) extra field, if it has one. Its times are
+    /// UTC, unlike [`ZipFile::last_modified`].
+    pub fn extended_timestamp(&self) -> Option<&ExtendedTimestamp> {
+        self.data
+            .extra_fields
+            .iter()
+            .map(|field| match field {
+                ExtraField::ExtendedTimestamp(timestamp) => timestamp,
+            })
+            .next()
+    }
+
+    /// Returns the entry's NTFS (0x000a) file times, if it has them. These are UTC and have a
+    /// resolution of 100 ns, rather than the 2 seconds of [`ZipFile::last_modified`].
+    pub fn ntfs_times(&self) -> Option<&Ntfs> {
+        self.data
+            .unlisted_extra_fields
+            .iter()
+            .find_map(|field| match field {
+                UnlistedExtraField::Ntfs(ntfs) => Some(ntfs),
+                _ => None,
+            })
+    }
+
+    /// Returns the Windows code page named by the entry's Korean code page (0xe57a) extra field,
+    /// if it has one, such as 949 for Korean. A name or comment that isn't flagged as UTF-8 is
+    /// encoded in this code page, which is only used to decode it with [`LegacyEncoding::Auto`].
+    pub fn code_page(&self) -> Option<u16> {
+        self.data.code_page()
+    }
+
+    /// The entry's modification time, preferring the UTC times of extra fields to the MS-DOS one.
+    fn modified_system_time(&self) -> Option<std::time::SystemTime> {
+        if let Some(ntfs) = self.ntfs_times() {
+            return extract::ntfs_to_system_time(ntfs.mtime());
+        }
+        if let Some(mod_time) = self.extended_timestamp().and_then(|t| t.mod_time()) {
+            return std::time::SystemTime::UNIX_EPOCH
+                .checked_add(std::time::Duration::from_secs(mod_time.into()));
+        }
+        extract::msdos_to_system_time(self.last_modified()?)
+    }
+
+    /// Returns whether the entry has an authoritative UTC modification time in an extra field:
+    /// an extended timestamp with a modification time, an NTFS timestamp, or a PKWARE or Info-ZIP
+    /// Unix field. Otherwise, [`ZipFile::last_modified`] is the only modification time, and it's
+    /// an MS-DOS timestamp in an unknown time zone, usually the local time of the machine that
+    /// wrote the archive.
+    ///
+    /// The times themselves are available from [`ZipFile::extended_timestamp`] and
+    /// [`ZipFile::ntfs_times`].
+    pub fn modified_time_is_utc(&self) -> bool {
+        let has_extended_mod_time = self.data.extra_fields.iter().any(|field| match field {
+            ExtraField::ExtendedTimestamp(timestamp) => timestamp.mod_time().is_some(),
+        });
+        has_extended_mod_time
+            || self.ntfs_times().is_some()
+            || self
+                .data
+                .extra_field
+                .as_deref()
+                .is_some_and(|extra| has_utc_mod_time_field(extra))
+    }
+}
+
+/// Finds the first path component shared by all of `names`, as returned by
+/// [`ZipArchive::root_dir`].
+fn common_root_dir<'a>(mut names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let (root_dir, _) = names.next()?.split_once(['/', '\\'])?;
+    if matches!(root_dir, "" | "." | "..") {
+        return None;
+    }
+    names
+        .all(|name| {
+            name.split_once(['/', '\\'])
+                .is_some_and(|(dir, _)| dir == root_dir)
+        })
+        .then_some(root_dir)
+}
+
+/// Removes `root_dir` and the separator after it from the start of `path`. Returns `None` if
+/// the rest of the path would leave the directory it's extracted to.
+fn strip_root_dir(path: &Path, root_dir: &str) -> Option<PathBuf> {
+    let mut rest = path.to_str()?.strip_prefix(root_dir)?;
+    if !rest.is_empty() {
+        rest = rest.strip_prefix(['/', '\\'])?;
+    }
+    let rest = Path::new(rest);
+    let mut depth = 0usize;
+    for component in rest.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return None,
+            Component::ParentDir => depth = depth.checked_sub(1)?,
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+        }
+    }
+    Some(rest.to_path_buf())
+}
+
+/// Extracts `file` to `filepath` in `vfs`, as a directory, a symbolic link or a regular file
+/// written by `copy_file`. Returns the mode to give it if it's a regular file, which should be
+/// set once every file is extracted, so that a parent directory isn't made read-only too early.
+fn extract_file_to_vfs<V: VirtualFs, F>(
+    mut file: ZipFile<'_>,
+    filepath: &Path,
+    vfs: &mut V,
+    options: &ExtractOptions,
+    copy_file: &mut F,
+    shared: &Shared,
+) -> ZipResult<Option<u32>>
+where
+    F: FnMut(&mut ZipFile<'_>, &mut V::File) -> ZipResult<()>,
+{
+    let owner = match options.restore_ownership {
+        true => [&file.data.extra_field, &file.data.central_extra_field]
+            .into_iter()
+            .flatten()
+            .find_map(|extra| extract::unix_owner(extra)),
+        false => None,
+    };
+    let restore_owner = |vfs: &mut V| {
+        let Some((uid, gid)) = owner else {
+            return Ok(());
+        };
+        match vfs.set_owner(filepath, uid, gid) {
+            Err(ZipError::Io(e))
+                if e.kind() == io::ErrorKind::PermissionDenied && !options.strict_ownership =>
+            {
+                Ok(())
+            }
+            result => result,
+        }
+    };
+    if file.is_dir() {
+        vfs.create_dir(filepath)?;
+        restore_owner(vfs)?;
+        return Ok(None);
+    }
+    if file.is_symlink() {
+        let mut target = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut target)?;
+        drop(file);
+        #[cfg(unix)]
+        let target = {
+            use std::os::unix::ffi::OsStringExt;
+            PathBuf::from(std::ffi::OsString::from_vec(target))
+        };
+        #[cfg(not(unix))]
+        let target = match String::from_utf8(target) {
+            Ok(target) => PathBuf::from(target),
+            Err(_) => return Err(ZipError::InvalidArchive("Invalid UTF-8 as symlink target")),
+        };
+        let target_is_dir = target
+            .to_str()
+            .is_some_and(|target| shared.files.contains_key(target) && is_dir(target));
+        vfs.create_symlink(filepath, &target, target_is_dir)?;
+        restore_owner(vfs)?;
+        return Ok(None);
+    }
+    if options.overwrite != Overwrite::Always {
+        if let Some(existing) = vfs.modified(filepath)? {
+            let archive_newer = options.overwrite == Overwrite::IfArchiveNewer
+                && file
+                    .modified_system_time()
+                    .is_some_and(|time| time > existing);
+            if !archive_newer {
+                return Ok(None);
+            }
+        }
+    }
+    let mut outfile = vfs.create_file(filepath)?;
+    copy_file(&mut file, &mut outfile)?;
+    drop(outfile);
+    restore_owner(vfs)?;
+    Ok(options.effective_mode(file.unix_mode()))
+}
+
+/// Write `field` as a CSV field, quoting it if it contains a separator, quote or line break.
+fn write_csv_field(out: &mut impl Write, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\r', '\n']) {
+        write!(out, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        out.write_all(field.as_bytes())
+    }
+}
+
+/// Checks whether an entry has an extra field with the given header ID, either among those
+/// parsed from its central-directory header or in its raw extra data.
+fn has_extra_field(data: &ZipFileData, tag: u16) -> bool {
+    let parsed = data.extra_fields.iter().any(|field| match field {
+        ExtraField::ExtendedTimestamp(_) => tag == 0x5455,
+    }) || data.unlisted_extra_fields.iter().any(|field| match field {
+        UnlistedExtraField::Ntfs(_) => tag == 0x000a,
+        UnlistedExtraField::KoreanCodePage(_) => tag == 0xe57a,
+    });
+    let raw = [&data.extra_field, &data.central_extra_field]
+        .into_iter()
+        .flatten()
+        .any(|extra| {
+            let mut extra = extra.as_slice();
+            while let [kind_lo, kind_hi, len_lo, len_hi, rest @ ..] = extra {
+                if u16::from_le_bytes([*kind_lo, *kind_hi]) == tag {
+                    return true;
+                }
+                let len = u16::from_le_bytes([*len_lo, *len_hi]) as usize;
+                extra = rest.get(len..).unwrap_or_default();
+            }
+            false
+        });
+    parsed || raw || (tag == 0x9901 && data.aes_mode.is_some())
+}
+
+/// Checks raw extra data for the fields besides the extended timestamp that hold a UTC
+/// modification time.
+fn has_utc_mod_time_field(mut extra: &[u8]) -> bool {
+    while let [kind_lo, kind_hi, len_lo, len_hi, rest @ ..] = extra {
+        let kind = u16::from_le_bytes([*kind_lo, *kind_hi]);
+        let len = u16::from_le_bytes([*len_lo, *len_hi]) as usize;
+        let Some(data) = rest.get(..len) else {
+            return false;
+        };
+        match kind {
+            // NTFS: 4 reserved bytes, then tagged attributes; tag 1 holds the file times
+            0x000a => {
+                let mut attributes = data.get(4..).unwrap_or_default();
+                while let [tag_lo, tag_hi, size_lo, size_hi, rest @ ..] = attributes {
+                    let size = u16::from_le_bytes([*size_lo, *size_hi]) as usize;
+                    if u16::from_le_bytes([*tag_lo, *tag_hi]) == 0x0001 && size >= 8 {
+                        return true;
+                    }
+                    attributes = rest.get(size..).unwrap_or_default();
+                }
+            }
+            // PKWARE Unix and Info-ZIP Unix (type 1): access time, then modification time
+            0x000d | 0x5855 if len >= 8 => return true,
+            _ => {}
+        }
+        extra = &rest[len..];
+    }
+    false
+}
+
+impl<'a> Read for ZipFile<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.get_reader()?.read(buf)?;
+        if count == 0 && !buf.is_empty() {
+            if let Some(data_descriptor) = &self.data_descriptor {
+                // The CRC-32 wasn't known when the reader was made, so it's checked here instead
+                let Some(descriptor) = data_descriptor.get() else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Data descriptor not found",
+                    ));
+                };
+                if self
+                    .reader
+                    .crc32()
+                    .is_some_and(|crc32| crc32 != descriptor.crc32)
+                {
+                    return Err(io::Error::new(io::ErrorKind::Other, "Invalid checksum"));
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Seeking is only supported within a stored, unencrypted entry, whose data is read directly
+/// from the archive, and only within the data; other entries fail with
+/// [`io::ErrorKind::Unsupported`]. The CRC-32 isn't checked once the entry has been seeked.
+impl<'a> Seek for ZipFile<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let stored = self.data.compression_method == CompressionMethod::Stored;
+        if stored && matches!(self.reader, ZipFileReader::NoReader) {
+            self.get_reader()?;
+        }
+        let reader = match &mut self.reader {
+            ZipFileReader::Stored(reader) => match reader.get_mut() {
+                CryptoReader::Plaintext(reader) => reader,
+                _ => return Err(seek_unsupported()),
+            },
+            ZipFileReader::Raw(reader) if stored && !self.data.encrypted => reader,
+            _ => return Err(seek_unsupported()),
+        };
+        let len = self.data.compressed_size;
+        let position = len - reader.limit();
+        let new_position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => position.checked_add_signed(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+        }
+        .filter(|new_position| *new_position <= len)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Can't seek outside the data of an entry",
+            )
+        })?;
+        let data_start = *self.data.data_start.get().unwrap();
+        reader
+            .get_mut()
+            .try_seek(SeekFrom::Start(data_start + new_position))?;
+        reader.set_limit(len - new_position);
+        if let ZipFileReader::Stored(reader) = &mut self.reader {
+            reader.disable_check();
+        }
+        Ok(new_position)
+    }
+}
+
+fn seek_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Can only seek within a stored, unencrypted entry",
+    )
+}
+
+impl<'a> Drop for ZipFile<'a> {
+    fn drop(&mut self) {
+        // This reader is constructed by a streaming reader.
+        // In this case, we want to exhaust the reader so that the next file is accessible.
+        if self.streaming {
+            // Get the inner `Take` reader so all decryption, decompression and CRC calculation is skipped.
+            match &mut self.reader {
+                ZipFileReader::NoReader => {
+                    let innerreader = self.crypto_reader.take();
+                    let _ = copy(
+                        &mut innerreader.expect("Invalid reader state").into_inner(),
+                        &mut sink(),
+                    );
+                }
+                reader => {
+                    let innerreader = std::mem::replace(reader, ZipFileReader::NoReader);
+                    innerreader.drain();
+                }
+            };
+        }
+    }
+}
+
+/// Read ZipFile structures from a non-seekable reader.
+///
+/// This is an alternative method to read a zip file. If possible, use the ZipArchive functions
+/// as some information will be missing when reading this manner.
+///
+/// Reads a file header from the start of the stream. Will return `Ok(Some(..))` if a file is
+/// present at the start of the stream. Returns `Ok(None)` if the start of the central directory
+/// is encountered. No more files should be read after this.
+///
+/// The Drop implementation of ZipFile ensures that the reader will be correctly positioned after
+/// the structure is done.
+///
+/// Missing fields are:
+/// * `comment`: set to an empty string
+/// * `data_start`: set to 0
+/// * `external_attributes`: `unix_mode()`: will return None
+pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'a>>> {
+    read_zipfile_from_stream_with_optional_password(reader, None, false)
+}
+
+/// Read ZipFile structures from a non-seekable reader, including those whose sizes and CRC-32 are
+/// only given in a data descriptor after their data.
+///
+/// This works like [`read_zipfile_from_stream`], but rather than failing on an entry with a data
+/// descriptor, its data is read up to the first signed data descriptor whose compressed size
+/// matches the number of bytes read, in either the ZIP64 or the 32-bit format as given by the
+/// local header. Once the entry has been read to the end, [`ZipFile::data_descriptor`] returns
+/// the descriptor's sizes and CRC-32, and the CRC-32 has been checked against the data. The
+/// [`ZipFile::size`], [`ZipFile::compressed_size`] and [`ZipFile::crc32`] of such an entry are
+/// those in its local header, which are usually zero.
+///
+/// Data descriptors without the optional signature can't be located this way and result in an
+/// error, as do AES-encrypted entries with a data descriptor. Since the data is read a byte at a
+/// time while scanning for the data descriptor, `reader` should be buffered.
+pub fn read_zipfile_from_stream_with_data_descriptor<'a, R: Read>(
+    reader: &'a mut R,
+) -> ZipResult<Option<ZipFile<'a>>> {
+    read_zipfile_from_stream_with_optional_password(reader, None, true)
+}
+
+/// Read an encrypted ZipFile from a non-seekable reader, decrypting it with `password`.
+///
+/// This works like [`read_zipfile_from_stream`], but entries encrypted with ZipCrypto or AES
+/// are decrypted. Since the central directory isn't available, the encryption method is taken
+/// from the local header's flags and AES extra field. Unencrypted entries are read as usual.
+///
+/// Returns `Err(ZipError::InvalidPassword)` if the password is detected to be wrong; as with
+/// [`ZipArchive::by_name_decrypt`], a wrong ZipCrypto password is only detected with a
+/// probability of 255/256.
+pub fn read_encrypted_zipfile_from_stream<'a, R: Read>(
+    reader: &'a mut R,
+    password: &[u8],
+) -> ZipResult<Option<ZipFile<'a>>> {
+    read_zipfile_from_stream_with_optional_password(reader, Some(password), false)
+}
+
+/// List the entries of an archive by walking its local file headers from the start of `reader`,
+/// without consulting the central directory.
+///
+/// This is a diagnostic counterpart to [`ZipArchive`] for archives whose central directory is
+/// missing or corrupt. The data of each entry is skipped using the compressed size in its local
+/// header or, for entries with a data descriptor, by scanning for a signed data descriptor whose
+/// compressed size matches the number of bytes skipped. Data descriptors without the optional
+/// signature can't be located this way and result in an error.
+///
+/// Listing stops at the first central directory header or end-of-central-directory record, or
+/// when the reader ends between two entries. Since the data is read a byte at a time while
+/// scanning for data descriptors, `reader` should be buffered.
+pub fn list_from_local_headers<R: Read>(reader: &mut R) -> ZipResult<Vec<LocalEntryInfo>> {
+    let mut entries = Vec::new();
+    let mut offset = 0u64;
+    loop {
+        let mut block = [0u8; mem::size_of::<ZipLocalEntryBlock>()];
+        let signature_len = mem::size_of::<spec::Magic>();
+        match reader.read_exact(&mut block[..signature_len]) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        match spec::Magic::from_first_le_bytes(&block) {
+            spec::Magic::LOCAL_FILE_HEADER_SIGNATURE => {}
+            spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE
+            | spec::Magic::CENTRAL_DIRECTORY_END_SIGNATURE
+            | spec::Magic::ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE => break,
+            _ => return Err(ZipLocalEntryBlock::WRONG_MAGIC_ERROR),
+        }
+        reader.read_exact(&mut block[signature_len..])?;
+        let block = ZipLocalEntryBlock::interpret(&block)?;
+        let mut data = ZipFileData::from_local_block(block, reader)?;
+        match parse_extra_field(&mut data, &Config::default()) {
+            Ok(..) | Err(ZipError::Io(..)) => {}
+            Err(e) => return Err(e),
+        }
+        data.large_file |= has_zip64_extra_field(&data);
+        let header_len = mem::size_of::<ZipLocalEntryBlock>() as u64
+            + data.file_name_raw.len() as u64
+            + data.extra_field_len() as u64;
+
+        let data_len = if data.using_data_descriptor {
+            let (descriptor, data_len) = skip_to_data_descriptor(reader, data.large_file)?;
+            data.crc32 = descriptor.crc32;
+            data.compressed_size = descriptor.compressed_size;
+            data.uncompressed_size = descriptor.uncompressed_size;
+            data_len
+        } else {
+            let skipped = copy(&mut reader.take(data.compressed_size), &mut sink())?;
+            if skipped != data.compressed_size {
+                return Err(InvalidArchive(
+                    "Entry data extends past the end of the archive",
                 ));
             }
-            let mut consumed_len = 0;
-            if len >= 24 || file.uncompressed_size == spec::ZIP64_BYTES_THR {
-                file.large_file = true;
-                file.uncompressed_size = reader.read_u64_le()?;
-                consumed_len += size_of::<u64>();
+            skipped
+        };
+
+        entries.push(LocalEntryInfo {
+            header_start: offset,
+            name: data.file_name,
+            compression_method: data.compression_method,
+            crc32: data.crc32,
+            compressed_size: data.compressed_size,
+            uncompressed_size: data.uncompressed_size,
+            last_modified_time: data.last_modified_time,
+            encrypted: data.encrypted,
+            using_data_descriptor: data.using_data_descriptor,
+        });
+        offset += header_len + data_len;
+    }
+    Ok(entries)
+}
+
+/// Whether a local header has a ZIP64 extra field. If it does, the sizes in the entry's data
+/// descriptor are 8 bytes long, even if the header's own size fields aren't ZIP64 sentinels, as
+/// when they're zero because the sizes are only in the data descriptor.
+fn has_zip64_extra_field(data: &ZipFileData) -> bool {
+    data.extra_field
+        .as_deref()
+        .is_some_and(|extra_field| extra_field_has_zip64(extra_field))
+}
+
+/// Whether raw extra data has a ZIP64 extra field.
+fn extra_field_has_zip64(mut extra_field: &[u8]) -> bool {
+    while extra_field.len() >= 4 {
+        let kind = u16::from_le_bytes([extra_field[0], extra_field[1]]);
+        if kind == 0x0001 {
+            return true;
+        }
+        let len = u16::from_le_bytes([extra_field[2], extra_field[3]]) as usize;
+        extra_field = extra_field.get(4 + len..).unwrap_or_default();
+    }
+    false
+}
+
+/// Skip entry data up to and including its signed data descriptor, returning the descriptor and
+/// the total number of bytes consumed.
+fn skip_to_data_descriptor<R: Read>(
+    reader: &mut R,
+    large_file: bool,
+) -> ZipResult<(DataDescriptor, u64)> {
+    let mut scanner = DataDescriptorScanner::new(reader, large_file);
+    let data_len = copy(&mut scanner, &mut sink()).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => InvalidArchive("Data descriptor not found"),
+        _ => e.into(),
+    })?;
+    let descriptor = *scanner
+        .descriptor
+        .get()
+        .expect("Data descriptor should be found when the data ends");
+    Ok((descriptor, data_len + scanner.descriptor_len() as u64))
+}
+
+/// Reads entry data whose length isn't known in advance, up to a signed data descriptor whose
+/// compressed size matches the number of bytes read. The bytes that might belong to the
+/// descriptor are held back until the next byte shows they don't.
+struct DataDescriptorScanner<R> {
+    inner: R,
+    large_file: bool,
+    window: VecDeque<u8>,
+    data_len: u64,
+    /// Set once the data descriptor has been read, which ends the data
+    descriptor: Arc<OnceLock<DataDescriptor>>,
+}
+
+impl<R: Read> DataDescriptorScanner<R> {
+    fn new(inner: R, large_file: bool) -> Self {
+        DataDescriptorScanner {
+            inner,
+            large_file,
+            window: VecDeque::new(),
+            data_len: 0,
+            descriptor: Arc::new(OnceLock::new()),
+        }
+    }
+
+    fn descriptor_len(&self) -> usize {
+        let size_len = if self.large_file { 8 } else { 4 };
+        2 * mem::size_of::<u32>() + 2 * size_len
+    }
+
+    /// Fills the window, and checks whether it holds the data descriptor.
+    fn scan(&mut self) -> io::Result<()> {
+        if self.descriptor.get().is_some() {
+            return Ok(());
+        }
+        while self.window.len() < self.descriptor_len() {
+            let mut byte = [0u8];
+            self.inner.read_exact(&mut byte)?;
+            self.window.push_back(byte[0]);
+        }
+        let window = self.window.make_contiguous();
+        if spec::Magic::from_first_le_bytes(window) != spec::Magic::DATA_DESCRIPTOR_SIGNATURE {
+            return Ok(());
+        }
+        let fields = &window[mem::size_of::<spec::Magic>()..];
+        if let Ok(descriptor) = DataDescriptor::parse(fields, self.large_file) {
+            if descriptor.compressed_size == self.data_len {
+                let _ = self.descriptor.set(descriptor);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DataDescriptorScanner<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.scan()?;
+        let mut count = 0;
+        while count < buf.len() && self.descriptor.get().is_none() {
+            buf[count] = self
+                .window
+                .pop_front()
+                .expect("Window should be full after scanning");
+            count += 1;
+            self.data_len += 1;
+            // Scanning straight away means the descriptor is found as soon as the last byte of
+            // data is read, even by a decompressor that doesn't read any further. A failure will
+            // recur on the next call.
+            if self.scan().is_err() {
+                break;
+            }
+        }
+        Ok(count)
+    }
+}
+
+fn read_zipfile_from_stream_with_optional_password<'a, R: Read>(
+    reader: &'a mut R,
+    password: Option<&[u8]>,
+    allow_data_descriptor: bool,
+) -> ZipResult<Option<ZipFile<'a>>> {
+    // We can't use the typical ::parse() method, as we follow separate code paths depending on the
+    // "magic" value (since the magic value will be from the central directory header if we've
+    // finished iterating over all the actual files).
+    /* TODO: smallvec? */
+    let mut block = [0u8; mem::size_of::<ZipLocalEntryBlock>()];
+    reader.read_exact(&mut block)?;
+    let block: Box<[u8]> = block.into();
+
+    let signature = spec::Magic::from_first_le_bytes(&block);
+
+    match signature {
+        spec::Magic::LOCAL_FILE_HEADER_SIGNATURE => (),
+        spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
+        _ => return Err(ZipLocalEntryBlock::WRONG_MAGIC_ERROR),
+    }
+
+    let block = ZipLocalEntryBlock::interpret(&block)?;
+
+    let mut result = ZipFileData::from_local_block(block, reader)?;
+    if result.encrypted && password.is_none() {
+        return unsupported_zip_error("Encrypted files are not supported");
+    }
+    if result.using_data_descriptor && !allow_data_descriptor {
+        return unsupported_zip_error("The file length is not available in the local header");
+    }
+
+    match parse_extra_field(&mut result, &Config::default()) {
+        Ok(..) | Err(ZipError::Io(..)) => {}
+        Err(e) => return Err(e),
+    }
+    result.large_file |= has_zip64_extra_field(&result);
+
+    let (limit_reader, data_descriptor) = if result.using_data_descriptor {
+        if result.aes_mode.is_some() {
+            return unsupported_zip_error(
+                "AES encrypted files with a data descriptor can't be streamed",
+            );
+        }
+        let scanner = DataDescriptorScanner::new(reader, result.large_file);
+        let data_descriptor = scanner.descriptor.clone();
+        let reader = Box::new(StreamEntryReader(scanner)) as Box<dyn EntryReader + 'a>;
+        (reader.take(u64::MAX), Some(data_descriptor))
+    } else {
+        let reader = Box::new(StreamEntryReader(reader)) as Box<dyn EntryReader + 'a>;
+        (reader.take(result.compressed_size), None)
+    };
+
+    let result_crc32 = result.crc32;
+    let result_compression_method = result.compression_method;
+    let crypto_reader = make_crypto_reader(
+        result_compression_method,
+        result_crc32,
+        result.last_modified_time,
+        result.using_data_descriptor,
+        limit_reader,
+        password.filter(|_| result.encrypted),
+        result.aes_mode,
+        #[cfg(feature = "aes-crypto")]
+        result.compressed_size,
+    )?;
+
+    let mut reader = make_reader(result_compression_method, result_crc32, crypto_reader)?;
+    if data_descriptor.is_some() {
+        reader.disable_check();
+    }
+    Ok(Some(ZipFile {
+        data: Cow::Owned(result),
+        crypto_reader: None,
+        reader,
+        data_descriptor,
+        streaming: true,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::result::ZipResult;
+    use crate::write::SimpleFileOptions;
+    use crate::CompressionMethod::Stored;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn invalid_offset() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/invalid_offset.zip"));
+        let reader = ZipArchive::new(Cursor::new(v));
+        assert!(reader.is_err());
+    }
+
+    #[test]
+    fn invalid_offset2() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/invalid_offset2.zip"));
+        let reader = ZipArchive::new(Cursor::new(v));
+        assert!(reader.is_err());
+    }
+
+    #[test]
+    fn zip64_size_sentinel_without_extra_field() {
+        use super::ZipArchive;
+        use crate::result::ZipError;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!(
+            "../tests/data/zip64_sentinel_without_extra_field.zip"
+        ));
+        match ZipArchive::new(Cursor::new(v)) {
+            Err(ZipError::InvalidArchive(msg)) => {
+                assert_eq!(msg, "ZIP64 size sentinel without ZIP64 extra field")
             }
-            if len >= 24 || file.compressed_size == spec::ZIP64_BYTES_THR {
-                file.large_file = true;
-                file.compressed_size = reader.read_u64_le()?;
-                consumed_len += size_of::<u64>();
+            Err(e) => panic!("Unexpected error: {e:?}"),
+            Ok(_) => panic!("Archive should be rejected"),
+        }
+    }
+
+    #[test]
+    fn strict_utf8_names() {
+        use super::{Config, ZipArchive};
+        use crate::result::ZipError;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/invalid_utf8_name.zip"));
+        let archive = ZipArchive::new(Cursor::new(v.clone())).unwrap();
+        assert_eq!(archive.name_for_index(0), Some("bad\u{FFFD}.txt"));
+
+        let config = Config {
+            strict_utf8_names: true,
+            ..Default::default()
+        };
+        match ZipArchive::with_config(config, Cursor::new(v)) {
+            Err(ZipError::InvalidArchive(msg)) => {
+                assert_eq!(msg, "File name flagged as UTF-8 isn't valid UTF-8")
             }
-            if len >= 24 || file.header_start == spec::ZIP64_BYTES_THR {
-                file.header_start = reader.read_u64_le()?;
-                consumed_len += size_of::<u64>();
+            Err(e) => panic!("Unexpected error: {e:?}"),
+            Ok(_) => panic!("Archive should be rejected"),
+        }
+    }
+
+    #[test]
+    fn legacy_encoding() -> ZipResult<()> {
+        use super::{Config, LegacyEncoding};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("@@.txt", SimpleFileOptions::default())?;
+        let mut bytes = writer.finish()?.into_inner();
+        let mut replaced = 0;
+        for i in 0..bytes.len() - 1 {
+            if bytes[i..].starts_with(b"@@.txt") {
+                bytes[i..i + 2].copy_from_slice(&[0x80, 0xe9]);
+                replaced += 1;
             }
-            let Some(leftover_len) = (len as usize).checked_sub(consumed_len) else {
-                return Err(InvalidArchive("ZIP64 extra-data field is the wrong length"));
+        }
+        assert_eq!(replaced, 2);
+
+        for (legacy_encoding, expected) in [
+            (LegacyEncoding::Cp437, "\u{c7}\u{398}.txt"),
+            (LegacyEncoding::Latin1, "\u{80}\u{e9}.txt"),
+            (LegacyEncoding::Windows1252, "\u{20ac}\u{e9}.txt"),
+        ] {
+            let config = Config {
+                legacy_encoding,
+                ..Default::default()
             };
-            reader.read_exact(&mut vec![0u8; leftover_len])?;
-            return Ok(true);
+            let mut archive = ZipArchive::with_config(config, Cursor::new(bytes.clone()))?;
+            assert_eq!(archive.name_for_index(0), Some(expected));
+            assert_eq!(archive.by_index(0)?.name_raw(), b"\x80\xe9.txt");
         }
-        0x9901 => {
-            // AES
-            if len != 7 {
-                return Err(ZipError::UnsupportedArchive(
-                    "AES extra data field has an unsupported length",
-                ));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "_deflate-any")]
+    fn sniff_compression() -> ZipResult<()> {
+        use super::Config;
+
+        let contents = b"repetitive contents ".repeat(50);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "file",
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Deflated),
+        )?;
+        writer.write_all(&contents)?;
+        let mut bytes = writer.finish()?.into_inner();
+        // Mark the entry as stored in both its local and central headers, like a broken writer
+        let archive = ZipArchive::new(Cursor::new(&bytes))?;
+        let (local, central) = {
+            let file = archive.shared.files.get_index(0).unwrap().1;
+            (
+                file.header_start as usize,
+                file.central_header_start as usize,
+            )
+        };
+        bytes[local + 8..local + 10].copy_from_slice(&0u16.to_le_bytes());
+        bytes[central + 10..central + 12].copy_from_slice(&0u16.to_le_bytes());
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        let mut read = Vec::new();
+        assert!(archive.by_index(0)?.read_to_end(&mut read).is_err());
+
+        let config = Config {
+            sniff_compression: true,
+            ..Default::default()
+        };
+        let mut archive = ZipArchive::with_config(config, Cursor::new(bytes))?;
+        let mut read = Vec::new();
+        archive.by_index(0)?.read_to_end(&mut read)?;
+        assert_eq!(read, contents);
+        Ok(())
+    }
+
+    #[test]
+    fn zip64_with_leading_junk() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/zip64_demo.zip"));
+        let reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        assert_eq!(reader.len(), 1);
+    }
+
+    #[test]
+    fn zip_contents() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
+        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        assert_eq!(reader.comment(), b"");
+        assert_eq!(reader.by_index(0).unwrap().central_header_start(), 77);
+    }
+
+    #[test]
+    fn zip_read_streaming() {
+        use super::read_zipfile_from_stream;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
+        let mut reader = Cursor::new(v);
+        loop {
+            if read_zipfile_from_stream(&mut reader).unwrap().is_none() {
+                break;
             }
-            let vendor_version = reader.read_u16_le()?;
-            let vendor_id = reader.read_u16_le()?;
-            let mut out = [0u8];
-            reader.read_exact(&mut out)?;
-            let aes_mode = out[0];
-            let compression_method = CompressionMethod::parse_from_u16(reader.read_u16_le()?);
+        }
+    }
+
+    #[test]
+    fn zip_clone() {
+        use super::ZipArchive;
+        use std::io::Read;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
+        let mut reader1 = ZipArchive::new(Cursor::new(v)).unwrap();
+        let mut reader2 = reader1.clone();
+
+        let mut file1 = reader1.by_index(0).unwrap();
+        let mut file2 = reader2.by_index(0).unwrap();
+
+        let t = file1.last_modified().unwrap();
+        assert_eq!(
+            (
+                t.year(),
+                t.month(),
+                t.day(),
+                t.hour(),
+                t.minute(),
+                t.second()
+            ),
+            (1980, 1, 1, 0, 0, 0)
+        );
+
+        let mut buf1 = [0; 5];
+        let mut buf2 = [0; 5];
+        let mut buf3 = [0; 5];
+        let mut buf4 = [0; 5];
+
+        file1.read_exact(&mut buf1).unwrap();
+        file2.read_exact(&mut buf2).unwrap();
+        file1.read_exact(&mut buf3).unwrap();
+        file2.read_exact(&mut buf4).unwrap();
+
+        assert_eq!(buf1, buf2);
+        assert_eq!(buf3, buf4);
+        assert_ne!(buf1, buf3);
+    }
+
+    #[test]
+    fn file_and_dir_predicates() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/files_and_dirs.zip"));
+        let mut zip = ZipArchive::new(Cursor::new(v)).unwrap();
+
+        for i in 0..zip.len() {
+            let zip_file = zip.by_index(i).unwrap();
+            let full_name = zip_file.enclosed_name().unwrap();
+            let file_name = full_name.file_name().unwrap().to_str().unwrap();
+            assert!(
+                (file_name.starts_with("dir") && zip_file.is_dir())
+                    || (file_name.starts_with("file") && zip_file.is_file())
+            );
+        }
+    }
+
+    #[test]
+    fn zip64_magic_in_filenames() {
+        let files = vec![
+            include_bytes!("../tests/data/zip64_magic_in_filename_1.zip").to_vec(),
+            include_bytes!("../tests/data/zip64_magic_in_filename_2.zip").to_vec(),
+            include_bytes!("../tests/data/zip64_magic_in_filename_3.zip").to_vec(),
+            include_bytes!("../tests/data/zip64_magic_in_filename_4.zip").to_vec(),
+            include_bytes!("../tests/data/zip64_magic_in_filename_5.zip").to_vec(),
+        ];
+        // Although we don't allow adding files whose names contain the ZIP64 CDB-end or
+        // CDB-end-locator signatures, we still read them when they aren't genuinely ambiguous.
+        for file in files {
+            ZipArchive::new(Cursor::new(file)).unwrap();
+        }
+    }
+
+    /// test case to ensure we don't preemptively over allocate based on the
+    /// declared number of files in the CDE of an invalid zip when the number of
+    /// files declared is more than the alleged offset in the CDE
+    #[test]
+    fn invalid_cde_number_of_files_allocation_smaller_offset() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!(
+            "../tests/data/invalid_cde_number_of_files_allocation_smaller_offset.zip"
+        ));
+        let reader = ZipArchive::new(Cursor::new(v));
+        assert!(reader.is_err() || reader.unwrap().is_empty());
+    }
+
+    /// test case to ensure we don't preemptively over allocate based on the
+    /// declared number of files in the CDE of an invalid zip when the number of
+    /// files declared is less than the alleged offset in the CDE
+    #[test]
+    fn invalid_cde_number_of_files_allocation_greater_offset() {
+        use super::ZipArchive;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!(
+            "../tests/data/invalid_cde_number_of_files_allocation_greater_offset.zip"
+        ));
+        let reader = ZipArchive::new(Cursor::new(v));
+        assert!(reader.is_err());
+    }
+
+    #[cfg(feature = "deflate64")]
+    #[test]
+    fn deflate64_index_out_of_bounds() -> std::io::Result<()> {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!(
+            "../tests/data/raw_deflate64_index_out_of_bounds.zip"
+        ));
+        let mut reader = ZipArchive::new(Cursor::new(v))?;
+        std::io::copy(&mut reader.by_index(0)?, &mut std::io::sink()).expect_err("Invalid file");
+        Ok(())
+    }
 
-            if vendor_id != 0x4541 {
-                return Err(ZipError::InvalidArchive("Invalid AES vendor"));
+    #[cfg(feature = "deflate64")]
+    #[test]
+    fn deflate64_not_enough_space() {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/deflate64_issue_25.zip"));
+        ZipArchive::new(Cursor::new(v)).expect_err("Invalid file");
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn test_read_with_data_descriptor() {
+        use std::io::Read;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/data_descriptor.zip"));
+        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        let mut decompressed = [0u8; 16];
+        let mut file = reader.by_index(0).unwrap();
+        assert_eq!(file.read(&mut decompressed).unwrap(), 12);
+    }
+
+    #[test]
+    fn read_data_descriptor() -> ZipResult<()> {
+        use super::DataDescriptor;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/data_descriptor.zip"));
+        let mut archive = ZipArchive::new(Cursor::new(v))?;
+        assert!(archive.entry_data_descriptor_present(0)?);
+        let descriptor = archive.read_data_descriptor(0)?.unwrap();
+        assert_eq!(
+            descriptor,
+            DataDescriptor {
+                crc32: 0xb095e5e3,
+                compressed_size: 14,
+                uncompressed_size: 12,
             }
-            let vendor_version = match vendor_version {
-                0x0001 => AesVendorVersion::Ae1,
-                0x0002 => AesVendorVersion::Ae2,
-                _ => return Err(ZipError::InvalidArchive("Invalid AES vendor version")),
-            };
-            match aes_mode {
-                0x01 => file.aes_mode = Some((AesMode::Aes128, vendor_version, compression_method)),
-                0x02 => file.aes_mode = Some((AesMode::Aes192, vendor_version, compression_method)),
-                0x03 => file.aes_mode = Some((AesMode::Aes256, vendor_version, compression_method)),
-                _ => return Err(ZipError::InvalidArchive("Invalid AES encryption strength")),
-            };
-            file.compression_method = compression_method;
-            file.aes_extra_data_start = bytes_already_read;
-        }
-        0x5455 => {
-            // extended timestamp
-            // https://libzip.org/specifications/extrafld.txt
+        );
+        let file = archive.by_index_raw(0)?;
+        assert_eq!(descriptor.crc32, file.crc32());
+        assert_eq!(descriptor.compressed_size, file.compressed_size());
+        assert_eq!(descriptor.uncompressed_size, file.size());
+        drop(file);
 
-            file.extra_fields.push(ExtraField::ExtendedTimestamp(
-                ExtendedTimestamp::try_from_reader(reader, len)?,
-            ));
-        }
-        0x6375 => {
-            // Info-ZIP Unicode Comment Extra Field
-            // APPNOTE 4.6.8 and https://libzip.org/specifications/extrafld.txt
-            file.file_comment = String::from_utf8(
-                UnicodeExtraField::try_from_reader(reader, len)?
-                    .unwrap_valid(file.file_comment.as_bytes())?
-                    .into_vec(),
-            )?
-            .into();
-        }
-        0x7075 => {
-            // Info-ZIP Unicode Path Extra Field
-            // APPNOTE 4.6.9 and https://libzip.org/specifications/extrafld.txt
-            file.file_name_raw = UnicodeExtraField::try_from_reader(reader, len)?
-                .unwrap_valid(&file.file_name_raw)?;
-            file.file_name =
-                String::from_utf8(file.file_name_raw.clone().into_vec())?.into_boxed_str();
-            file.is_utf8 = true;
-        }
-        _ => {
-            reader.read_exact(&mut vec![0u8; len as usize])?;
-            // Other fields are ignored
-        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("no_descriptor", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+        assert!(!archive.entry_data_descriptor_present(0)?);
+        assert_eq!(archive.read_data_descriptor(0)?, None);
+        Ok(())
     }
-    Ok(false)
-}
 
-/// Methods for retrieving information on zip files
-impl<'a> ZipFile<'a> {
-    fn get_reader(&mut self) -> ZipResult<&mut ZipFileReader<'a>> {
-        if let ZipFileReader::NoReader = self.reader {
-            let data = &self.data;
-            let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader = make_reader(data.compression_method, data.crc32, crypto_reader)?;
-        }
-        Ok(&mut self.reader)
+    #[test]
+    fn contains_name_path_and_dir() {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/files_and_dirs.zip"));
+        let archive = ZipArchive::new(Cursor::new(v)).unwrap();
+        assert!(archive.contains_name("file0.txt"));
+        assert!(archive.contains_name("dir1/"));
+        assert!(!archive.contains_name("dir1"));
+        assert!(!archive.contains_name("missing.txt"));
+        assert!(archive.contains_path(std::path::Path::new("dir2").join("..").join("file0.txt")));
+        assert!(!archive.contains_path(std::path::Path::new("missing")));
+        assert!(archive.contains_dir("dir1"));
+        assert!(archive.contains_dir("dir2/"));
+        assert!(!archive.contains_dir("file0.txt"));
+        assert!(!archive.contains_dir("missing"));
     }
 
-    pub(crate) fn get_raw_reader(&mut self) -> &mut dyn Read {
-        if let ZipFileReader::NoReader = self.reader {
-            let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader = ZipFileReader::Raw(crypto_reader.into_inner())
+    #[test]
+    fn nested_archive() -> ZipResult<()> {
+        let mut inner = ZipWriter::new(Cursor::new(Vec::new()));
+        inner.start_file("inner.txt", SimpleFileOptions::default())?;
+        inner.write_all(b"nested contents")?;
+        let inner = inner.finish()?.into_inner();
+
+        let mut outer = ZipWriter::new(Cursor::new(Vec::new()));
+        outer.start_file("padding.txt", SimpleFileOptions::default())?;
+        outer.write_all(b"some data before the nested archive")?;
+        outer.start_file(
+            "lib.jar",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        outer.write_all(&inner)?;
+        let mut outer = outer.finish_into_readable()?;
+
+        let mut nested = outer.nested_archive(1)?;
+        assert_eq!(nested.len(), 1);
+        let mut contents = String::new();
+        nested.by_name("inner.txt")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "nested contents");
+        drop(nested);
+        // Either compressed or not an archive, depending on the enabled features
+        assert!(outer.nested_archive(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_symlink() -> std::io::Result<()> {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/symlink.zip"));
+        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        assert!(reader.by_index(0).unwrap().is_symlink());
+        let tempdir = TempDir::new("test_is_symlink")?;
+        reader.extract(&tempdir).unwrap();
+        assert!(tempdir.path().join("bar").is_symlink());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "digest")]
+    fn extract_with_digests() -> ZipResult<()> {
+        use sha2::{Digest, Sha256};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("dir", options)?;
+        writer.start_file("dir/a.txt", options)?;
+        writer.write_all(b"hello")?;
+        writer.start_file("b.txt", options)?;
+        writer.write_all(b"world")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let tempdir = TempDir::new("extract_with_digests")?;
+        let digests = archive.extract_with_digests::<_, Sha256>(&tempdir)?;
+        assert_eq!(digests.len(), 2);
+        for name in ["dir/a.txt", "b.txt"] {
+            let extracted = std::fs::read(tempdir.path().join(name))?;
+            assert_eq!(digests[name], Sha256::digest(extracted));
         }
-        &mut self.reader
+        assert_eq!(digests["b.txt"], Sha256::digest(b"world"));
+        Ok(())
     }
 
-    /// Get the version of the file
-    pub fn version_made_by(&self) -> (u8, u8) {
-        (
-            self.data.version_made_by / 10,
-            self.data.version_made_by % 10,
-        )
+    #[test]
+    fn for_each_entry_parallel() -> ZipResult<()> {
+        use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for i in 0..64 {
+            writer.start_file(format!("{i}.txt"), options)?;
+            writer.write_all(&vec![b'x'; i])?;
+        }
+        let archive = writer.finish_into_readable()?;
+
+        let total_size = AtomicU64::new(0);
+        let total_read = AtomicUsize::new(0);
+        archive.for_each_entry_parallel(|mut file| {
+            total_size.fetch_add(file.size(), Ordering::Relaxed);
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            total_read.fetch_add(contents.len(), Ordering::Relaxed);
+        })?;
+        let expected = (0..64).sum::<usize>();
+        assert_eq!(total_size.into_inner(), expected as u64);
+        assert_eq!(total_read.into_inner(), expected);
+
+        // A reader that can't be cloned is read sequentially
+        let mut reader = archive.into_inner();
+        let mut archive = ZipArchive::new(&mut reader)?;
+        let mut names = Vec::new();
+        archive.for_each_entry(|file| names.push(file.name().to_owned()))?;
+        assert_eq!(names.len(), 64);
+        assert_eq!(names[10], "10.txt");
+        Ok(())
     }
 
-    /// Get the name of the file
-    ///
-    /// # Warnings
-    ///
-    /// It is dangerous to use this name directly when extracting an archive.
-    /// It may contain an absolute path (`/etc/shadow`), or break out of the
-    /// current directory (`../runtime`). Carelessly writing to these paths
-    /// allows an attacker to craft a ZIP archive that will overwrite critical
-    /// files.
-    ///
-    /// You can use the [`ZipFile::enclosed_name`] method to validate the name
-    /// as a safe path.
-    pub fn name(&self) -> &str {
-        &self.data.file_name
+    #[test]
+    #[cfg(feature = "_deflate-any")]
+    fn test_utf8_extra_field() {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/chinese.zip"));
+        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        reader.by_name("七个房间.txt").unwrap();
     }
 
-    /// Get the name of the file, in the raw (internal) byte representation.
-    ///
-    /// The encoding of this data is currently undefined.
-    pub fn name_raw(&self) -> &[u8] {
-        &self.data.file_name_raw
+    #[test]
+    fn test_utf8() {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/linux-7z.zip"));
+        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        reader.by_name("你好.txt").unwrap();
     }
 
-    /// Get the name of the file in a sanitized form. It truncates the name to the first NULL byte,
-    /// removes a leading '/' and removes '..' parts.
-    #[deprecated(
-        since = "0.5.7",
-        note = "by stripping `..`s from the path, the meaning of paths can change.
-                `mangled_name` can be used if this behaviour is desirable"
-    )]
-    pub fn sanitized_name(&self) -> PathBuf {
-        self.mangled_name()
+    #[test]
+    fn test_utf8_2() {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/windows-7zip.zip"));
+        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
+        reader.by_name("你好.txt").unwrap();
     }
 
-    /// Rewrite the path, ignoring any path components with special meaning.
-    ///
-    /// - Absolute paths are made relative
-    /// - [`ParentDir`]s are ignored
-    /// - Truncates the filename at a NULL byte
-    ///
-    /// This is appropriate if you need to be able to extract *something* from
-    /// any archive, but will easily misrepresent trivial paths like
-    /// `foo/../bar` as `foo/bar` (instead of `bar`). Because of this,
-    /// [`ZipFile::enclosed_name`] is the better option in most scenarios.
-    ///
-    /// [`ParentDir`]: `Component::ParentDir`
-    pub fn mangled_name(&self) -> PathBuf {
-        self.data.file_name_sanitized()
+    #[test]
+    fn validate_local_headers_reports_crc_mismatch() -> ZipResult<()> {
+        use super::{LocalHeaderDiscrepancy, LocalHeaderMismatch};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("first.txt", options)?;
+        writer.write_all(b"first")?;
+        writer.start_file("second.txt", options)?;
+        writer.write_all(b"second")?;
+        let mut archive = writer.finish_into_readable()?;
+        assert!(archive.validate_local_headers()?.is_empty());
+        let header_start = archive.by_name("second.txt")?.header_start() as usize;
+        let central_crc32 = archive.by_name("second.txt")?.crc32();
+
+        // Corrupt the CRC in the local header only
+        let mut bytes = archive.into_inner().into_inner();
+        const CRC32_OFFSET: usize = 14;
+        bytes[header_start + CRC32_OFFSET] ^= 0xff;
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(
+            archive.validate_local_headers()?,
+            vec![LocalHeaderDiscrepancy {
+                index: 1,
+                name: "second.txt".into(),
+                mismatch: LocalHeaderMismatch::Crc32 {
+                    local: central_crc32 ^ 0xff,
+                    central: central_crc32,
+                },
+            }]
+        );
+        Ok(())
     }
 
-    /// Ensure the file path is safe to use as a [`Path`].
-    ///
-    /// - It can't contain NULL bytes
-    /// - It can't resolve to a path outside the current directory
-    ///   > `foo/../bar` is fine, `foo/../../bar` is not.
-    /// - It can't be an absolute path
-    ///
-    /// This will read well-formed ZIP files correctly, and is resistant
-    /// to path-based exploits. It is recommended over
-    /// [`ZipFile::mangled_name`].
-    pub fn enclosed_name(&self) -> Option<PathBuf> {
-        self.data.enclosed_name()
+    #[test]
+    fn test_64k_files() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions {
+            compression_method: Stored,
+            ..Default::default()
+        };
+        for i in 0..=u16::MAX {
+            let file_name = format!("{i}.txt");
+            writer.start_file(&*file_name, options)?;
+            writer.write_all(i.to_string().as_bytes())?;
+        }
+
+        let mut reader = ZipArchive::new(writer.finish()?)?;
+        for i in 0..=u16::MAX {
+            let expected_name = format!("{i}.txt");
+            let expected_contents = i.to_string();
+            let expected_contents = expected_contents.as_bytes();
+            let mut file = reader.by_name(&expected_name)?;
+            let mut contents = Vec::with_capacity(expected_contents.len());
+            file.read_to_end(&mut contents)?;
+            assert_eq!(contents, expected_contents);
+            drop(file);
+            contents.clear();
+            let mut file = reader.by_index(i as usize)?;
+            file.read_to_end(&mut contents)?;
+            assert_eq!(contents, expected_contents);
+        }
+        Ok(())
     }
 
-    /// Get the comment of the file
-    pub fn comment(&self) -> &str {
-        &self.data.file_comment
+    #[test]
+    fn list_from_local_headers_matches_central_directory() -> ZipResult<()> {
+        use super::list_from_local_headers;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir", SimpleFileOptions::default())?;
+        writer.start_file(
+            "dir/stored.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"stored contents")?;
+        writer.start_file("compressed.txt", SimpleFileOptions::default())?;
+        writer.write_all(&[b'a'; 1000])?;
+        let written = writer.finish()?.into_inner();
+
+        for bytes in [
+            written,
+            include_bytes!("../tests/data/data_descriptor.zip").to_vec(),
+        ] {
+            let local = list_from_local_headers(&mut std::io::BufReader::new(Cursor::new(&bytes)))?;
+            let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+            assert_eq!(local.len(), archive.len());
+            for (i, entry) in local.iter().enumerate() {
+                let file = archive.by_index_raw(i)?;
+                assert_eq!(&*entry.name, file.name());
+                assert_eq!(entry.header_start, file.header_start());
+                assert_eq!(entry.compression_method, file.compression());
+                assert_eq!(entry.crc32, file.crc32());
+                assert_eq!(entry.compressed_size, file.compressed_size());
+                assert_eq!(entry.uncompressed_size, file.size());
+            }
+        }
+        Ok(())
     }
 
-    /// Get the compression method used to store the file
-    pub fn compression(&self) -> CompressionMethod {
-        self.data.compression_method
+    #[test]
+    fn iter_entries_cloned_interleaved() -> ZipResult<()> {
+        let first: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let second: Vec<u8> = first.iter().rev().copied().collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first", SimpleFileOptions::default())?;
+        writer.write_all(&first)?;
+        writer.start_file(
+            "second",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&second)?;
+        let archive = writer.finish_into_readable()?;
+
+        let mut entries = archive
+            .iter_entries_cloned()
+            .collect::<ZipResult<Vec<_>>>()?;
+        let (mut first_read, mut second_read) = (Vec::new(), Vec::new());
+        let mut chunk = [0u8; 100];
+        loop {
+            let n1 = entries[0].read(&mut chunk)?;
+            first_read.extend_from_slice(&chunk[..n1]);
+            let n2 = entries[1].read(&mut chunk)?;
+            second_read.extend_from_slice(&chunk[..n2]);
+            if n1 == 0 && n2 == 0 {
+                break;
+            }
+        }
+        assert_eq!(entries[0].name(), "first");
+        assert_eq!(first_read, first);
+        assert_eq!(entries[1].name(), "second");
+        assert_eq!(second_read, second);
+        Ok(())
     }
 
-    /// Get the size of the file, in bytes, in the archive
-    pub fn compressed_size(&self) -> u64 {
-        self.data.compressed_size
+    #[test]
+    fn update_comment_in_place() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("original comment");
+        writer.start_file("file", SimpleFileOptions::default())?;
+        writer.write_all(b"file contents")?;
+        let mut handle = writer.finish()?;
+
+        let long_comment = b"a much longer replacement comment".as_slice();
+        let length = ZipArchive::update_comment(&mut handle, long_comment)?;
+        assert_eq!(length, handle.get_ref().len() as u64);
+        let mut archive = ZipArchive::new(handle)?;
+        assert_eq!(archive.comment(), long_comment);
+        let mut contents = String::new();
+        archive.by_name("file")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "file contents");
+
+        let mut handle = archive.into_inner();
+        let length = ZipArchive::update_comment(&mut handle, b"short")?;
+        handle.get_mut().truncate(length as usize);
+        let archive = ZipArchive::new(handle)?;
+        assert_eq!(archive.comment(), b"short");
+        assert_eq!(archive.len(), 1);
+
+        let mut handle = archive.into_inner();
+        handle.get_mut().extend_from_slice(b"trailing data");
+        assert!(ZipArchive::update_comment(&mut handle, b"comment").is_err());
+        Ok(())
     }
 
-    /// Get the size of the file, in bytes, when uncompressed
-    pub fn size(&self) -> u64 {
-        self.data.uncompressed_size
+    #[test]
+    fn shared_reader_concurrent_reads() -> ZipResult<()> {
+        let first = b"first contents ".repeat(1000);
+        let second = b"second contents ".repeat(1000);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first", SimpleFileOptions::default())?;
+        writer.write_all(&first)?;
+        writer.start_file(
+            "second",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&second)?;
+        let archive = writer.finish_into_readable()?.shared_reader();
+
+        std::thread::scope(|scope| {
+            let threads = [("first", &first), ("second", &second)].map(|(name, expected)| {
+                let mut archive = archive.clone();
+                scope.spawn(move || -> ZipResult<()> {
+                    let mut contents = Vec::new();
+                    archive.by_name(name)?.read_to_end(&mut contents)?;
+                    assert_eq!(&contents, expected);
+                    Ok(())
+                })
+            });
+            threads
+                .into_iter()
+                .try_for_each(|thread| thread.join().unwrap())
+        })
     }
 
-    /// Get the time the file was last modified
-    pub fn last_modified(&self) -> Option<DateTime> {
-        self.data.last_modified_time
-    }
-    /// Returns whether the file is actually a directory
-    pub fn is_dir(&self) -> bool {
-        is_dir(self.name())
-    }
+    #[test]
+    fn eocd_bytes() -> ZipResult<()> {
+        use crate::write::ZipWriter;
+
+        let v = include_bytes!("../tests/data/mimetype.zip").to_vec();
+        let mut archive = ZipArchive::new(Cursor::new(v.clone()))?;
+        let eocd = archive.eocd_bytes()?;
+        assert_eq!(eocd.eocd.range(), v.len() as u64 - 22..v.len() as u64);
+        assert_eq!(*eocd.eocd.bytes, v[v.len() - 22..]);
+        assert_eq!(eocd.zip64_eocd, None);
+        assert_eq!(eocd.zip64_locator, None);
+
+        let v = include_bytes!("../tests/data/zip64_demo.zip").to_vec();
+        let mut archive = ZipArchive::new(Cursor::new(v.clone()))?;
+        let eocd = archive.eocd_bytes()?;
+        let locator = eocd.zip64_locator.unwrap();
+        let zip64_eocd = eocd.zip64_eocd.unwrap();
+        assert_eq!(eocd.eocd.range().end, v.len() as u64);
+        assert_eq!(locator.range().end, eocd.eocd.start);
+        assert_eq!(zip64_eocd.range().end, locator.start);
+        for record in [&eocd.eocd, &locator, &zip64_eocd] {
+            assert_eq!(
+                *record.bytes,
+                v[record.start as usize..][..record.bytes.len()]
+            );
+        }
+        assert_eq!(eocd.eocd.bytes[..4], *b"PK\x05\x06");
+        assert_eq!(locator.bytes[..4], *b"PK\x06\x07");
+        assert_eq!(zip64_eocd.bytes[..4], *b"PK\x06\x06");
+        assert_eq!(zip64_eocd.bytes.len(), 56);
 
-    /// Returns whether the file is actually a symbolic link
-    pub fn is_symlink(&self) -> bool {
-        self.unix_mode()
-            .is_some_and(|mode| mode & S_IFLNK == S_IFLNK)
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("comment");
+        let mut archive = writer.finish_into_readable()?;
+        let eocd = archive.eocd_bytes()?;
+        assert_eq!(eocd.eocd.range(), 0..29);
+        assert!(eocd.eocd.bytes.ends_with(b"comment"));
+        Ok(())
     }
 
-    /// Returns whether the file is a normal file (i.e. not a directory or symlink)
-    pub fn is_file(&self) -> bool {
-        !self.is_dir() && !self.is_symlink()
+    #[test]
+    #[cfg(feature = "deflate-flate2")]
+    fn recompression_estimate() -> ZipResult<()> {
+        use crate::write::{SimpleFileOptions, ZipWriter};
+        use crate::CompressionMethod;
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let text: String = (0..20_000)
+            .map(|i| format!("line {i} of some compressible text\n"))
+            .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir/", stored)?;
+        writer.start_file("dir/large.txt", stored)?;
+        writer.write_all(text.as_bytes())?;
+        writer.start_file("small.txt", stored)?;
+        writer.write_all(&text.as_bytes()[..1000])?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let estimate = archive.recompression_estimate(CompressionMethod::Deflated)?;
+        let [dir, large, small] = &estimate.entries[..] else {
+            panic!("expected 3 entries, got {:?}", estimate.entries);
+        };
+        assert_eq!(dir.predicted_compressed_size, 0);
+        assert!(!large.exact);
+        assert_eq!(large.current_compressed_size, text.len() as u64);
+        assert!(large.predicted_compressed_size < large.current_compressed_size / 2);
+        assert!(small.exact);
+        assert!(small.predicted_compressed_size < 1000);
+        assert_eq!(estimate.current_total(), text.len() as u64 + 1000);
+        assert!(estimate.predicted_total() < estimate.current_total() / 2);
+
+        // The extrapolation shouldn't be wildly off for uniformly compressible data
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "large.txt",
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        )?;
+        writer.write_all(text.as_bytes())?;
+        let actual = writer
+            .finish_into_readable()?
+            .by_index(0)?
+            .compressed_size();
+        assert!(large.predicted_compressed_size < actual * 2);
+        assert!(actual < large.predicted_compressed_size * 2);
+
+        // Each entry is sampled on its own, whatever else is in the archive, even if other
+        // entries are named after its index
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..10 {
+            writer.start_file(i.to_string(), stored)?;
+            writer.write_all(&text.as_bytes()[i * 100..])?;
+        }
+        writer.start_file("small.txt", stored)?;
+        writer.write_all(&text.as_bytes()[..1000])?;
+        let mut archive = writer.finish_into_readable()?;
+        let crowded = archive.recompression_estimate(CompressionMethod::Deflated)?;
+        let crowded_small = crowded.entries.last().unwrap();
+        assert_eq!(
+            crowded_small.predicted_compressed_size,
+            small.predicted_compressed_size
+        );
+        assert_eq!(crowded_small.exact, small.exact);
+        Ok(())
     }
 
-    /// Get unix mode for the file
-    pub fn unix_mode(&self) -> Option<u32> {
-        self.data.unix_mode()
+    #[test]
+    fn central_directory_first() -> ZipResult<()> {
+        use super::{ArchiveOffset, Config};
+        // The central directory is at offset 0, followed by the local headers and data
+        let v = include_bytes!("../tests/data/central_directory_first.zip").to_vec();
+        for archive_offset in [ArchiveOffset::Detect, ArchiveOffset::Known(0)] {
+            let config = Config {
+                archive_offset,
+                ..Default::default()
+            };
+            let mut archive = ZipArchive::with_config(config, Cursor::new(v.clone()))?;
+            assert_eq!(archive.len(), 2);
+            for (name, contents) in [
+                ("first.txt", "central directory first\n"),
+                ("second.txt", "local data after it\n"),
+            ] {
+                let mut file = archive.by_name(name)?;
+                assert!(file.header_start() > file.central_header_start());
+                let mut read_back = String::new();
+                file.read_to_string(&mut read_back)?;
+                assert_eq!(read_back, contents);
+            }
+        }
+        Ok(())
     }
 
-    /// Get the CRC32 hash of the original file
-    pub fn crc32(&self) -> u32 {
-        self.data.crc32
-    }
+    #[test]
+    fn extract_to_vfs() -> ZipResult<()> {
+        use super::{ExtractOptions, UnsafePathPolicy, VirtualFs};
+        use std::cell::RefCell;
+        use std::collections::BTreeMap;
+        use std::path::{Path, PathBuf};
+        use std::rc::Rc;
+
+        #[derive(Debug, PartialEq)]
+        enum Node {
+            Dir,
+            File(Rc<RefCell<Vec<u8>>>, Option<u32>),
+        }
 
-    /// Get the extra data of the zip header for this file
-    pub fn extra_data(&self) -> Option<&[u8]> {
-        self.data.extra_field.as_ref().map(|v| v.deref().deref())
-    }
+        struct MemoryFile(Rc<RefCell<Vec<u8>>>);
 
-    /// Get the starting offset of the data of the compressed file
-    pub fn data_start(&self) -> u64 {
-        *self.data.data_start.get().unwrap()
-    }
+        impl Write for MemoryFile {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
 
-    /// Get the starting offset of the zip header for this file
-    pub fn header_start(&self) -> u64 {
-        self.data.header_start
-    }
-    /// Get the starting offset of the zip header in the central directory for this file
-    pub fn central_header_start(&self) -> u64 {
-        self.data.central_header_start
-    }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
 
-    /// iterate through all extra fields
-    pub fn extra_data_fields(&self) -> impl Iterator<Item = &ExtraField> {
-        self.data.extra_fields.iter()
-    }
-}
+        #[derive(Default)]
+        struct MemoryFs(BTreeMap<PathBuf, Node>);
 
-impl<'a> Read for ZipFile<'a> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.get_reader()?.read(buf)
-    }
-}
+        impl VirtualFs for MemoryFs {
+            type File = MemoryFile;
 
-impl<'a> Drop for ZipFile<'a> {
-    fn drop(&mut self) {
-        // self.data is Owned, this reader is constructed by a streaming reader.
-        // In this case, we want to exhaust the reader so that the next file is accessible.
-        if let Cow::Owned(_) = self.data {
-            // Get the inner `Take` reader so all decryption, decompression and CRC calculation is skipped.
-            match &mut self.reader {
-                ZipFileReader::NoReader => {
-                    let innerreader = self.crypto_reader.take();
-                    let _ = copy(
-                        &mut innerreader.expect("Invalid reader state").into_inner(),
-                        &mut sink(),
-                    );
-                }
-                reader => {
-                    let innerreader = std::mem::replace(reader, ZipFileReader::NoReader);
-                    innerreader.drain();
+            fn create_dir(&mut self, path: &Path) -> ZipResult<()> {
+                self.0.insert(path.to_path_buf(), Node::Dir);
+                Ok(())
+            }
+
+            fn create_file(&mut self, path: &Path) -> ZipResult<MemoryFile> {
+                let contents = Rc::new(RefCell::new(Vec::new()));
+                self.0
+                    .insert(path.to_path_buf(), Node::File(contents.clone(), None));
+                Ok(MemoryFile(contents))
+            }
+
+            fn create_symlink(&mut self, _: &Path, _: &Path, _: bool) -> ZipResult<()> {
+                unreachable!("the archive has no symlinks")
+            }
+
+            fn set_permissions(&mut self, path: &Path, mode: u32) -> ZipResult<()> {
+                match self.0.get_mut(path) {
+                    Some(Node::File(_, permissions)) => *permissions = Some(mode),
+                    _ => panic!("{path:?} isn't a file"),
                 }
-            };
+                Ok(())
+            }
         }
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir/", SimpleFileOptions::default())?;
+        writer.start_file(
+            "dir/script.sh",
+            SimpleFileOptions::default().unix_permissions(0o4755),
+        )?;
+        writer.write_all(b"#!/bin/sh\n")?;
+        writer.start_file("../outside.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"escaped")?;
+        writer.start_file("top.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"top")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        let mut vfs = MemoryFs::default();
+        let options = ExtractOptions {
+            on_unsafe_path: UnsafePathPolicy::Skip,
+            ..Default::default()
+        };
+        archive.extract_to_vfs(&mut vfs, options)?;
+        let file = |contents: &[u8], mode| {
+            Node::File(Rc::new(RefCell::new(contents.to_vec())), Some(mode))
+        };
+        assert_eq!(
+            vfs.0,
+            BTreeMap::from([
+                (PathBuf::from("dir"), Node::Dir),
+                (
+                    PathBuf::from("dir/script.sh"),
+                    file(b"#!/bin/sh\n", 0o100755)
+                ),
+                (PathBuf::from("top.txt"), file(b"top", 0o100644)),
+            ])
+        );
+        Ok(())
     }
-}
 
-/// Read ZipFile structures from a non-seekable reader.
-///
-/// This is an alternative method to read a zip file. If possible, use the ZipArchive functions
-/// as some information will be missing when reading this manner.
-///
-/// Reads a file header from the start of the stream. Will return `Ok(Some(..))` if a file is
-/// present at the start of the stream. Returns `Ok(None)` if the start of the central directory
-/// is encountered. No more files should be read after this.
-///
-/// The Drop implementation of ZipFile ensures that the reader will be correctly positioned after
-/// the structure is done.
-///
-/// Missing fields are:
-/// * `comment`: set to an empty string
-/// * `data_start`: set to 0
-/// * `external_attributes`: `unix_mode()`: will return None
-pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'_>>> {
-    // We can't use the typical ::parse() method, as we follow separate code paths depending on the
-    // "magic" value (since the magic value will be from the central directory header if we've
-    // finished iterating over all the actual files).
-    /* TODO: smallvec? */
-    let mut block = [0u8; mem::size_of::<ZipLocalEntryBlock>()];
-    reader.read_exact(&mut block)?;
-    let block: Box<[u8]> = block.into();
+    #[test]
+    fn by_index_buffered() -> ZipResult<()> {
+        use super::Config;
+        use std::io::BufRead;
+        use std::num::NonZeroUsize;
 
-    let signature = spec::Magic::from_first_le_bytes(&block);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("lines.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"first line\nsecond, longer line\n\nlast line without newline")?;
+        let bytes = writer.finish()?.into_inner();
+        for io_buffer_size in [None, NonZeroUsize::new(4)] {
+            let config = Config {
+                io_buffer_size,
+                ..Default::default()
+            };
+            let mut archive = ZipArchive::with_config(config, Cursor::new(bytes.clone()))?;
+            let file = archive.by_index_buffered(0)?;
+            assert_eq!(
+                file.capacity(),
+                io_buffer_size.map_or(8192, NonZeroUsize::get)
+            );
+            assert_eq!(file.get_ref().name(), "lines.txt");
+            let lines = file.lines().collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(
+                lines,
+                [
+                    "first line",
+                    "second, longer line",
+                    "",
+                    "last line without newline"
+                ]
+            );
+        }
+        Ok(())
+    }
 
-    match signature {
-        spec::Magic::LOCAL_FILE_HEADER_SIGNATURE => (),
-        spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
-        _ => return Err(ZipLocalEntryBlock::WRONG_MAGIC_ERROR),
+    #[test]
+    fn entries_with_extra_field() -> ZipResult<()> {
+        let entries = |bytes: &[u8], tag| -> ZipResult<Vec<usize>> {
+            let archive = ZipArchive::new(Cursor::new(bytes.to_vec()))?;
+            Ok(archive.entries_with_extra_field(tag).collect())
+        };
+        let aes = include_bytes!("../tests/data/aes_archive.zip");
+        assert_eq!(entries(aes, 0x9901)?, [0, 1, 2, 3]);
+        assert_eq!(entries(aes, 0x000a)?, [0, 1, 2, 3]);
+        assert_eq!(entries(aes, 0x5455)?, []);
+
+        let extended_timestamp = include_bytes!("../tests/data/extended_timestamp.zip");
+        assert_eq!(entries(extended_timestamp, 0x5455)?, [1]);
+        assert_eq!(entries(extended_timestamp, 0x7875)?, [1]);
+        assert_eq!(entries(extended_timestamp, 0x9901)?, []);
+
+        let mimetype = include_bytes!("../tests/data/mimetype.zip");
+        for tag in [0x0001, 0x000a, 0x5455, 0x9901] {
+            assert_eq!(entries(mimetype, tag)?, []);
+        }
+        Ok(())
     }
 
-    let block = ZipLocalEntryBlock::interpret(&block)?;
+    #[test]
+    fn disk_info() -> ZipResult<()> {
+        use super::DiskInfo;
+        let single_disk = DiskInfo {
+            disk_number: 0,
+            disk_with_central_directory: 0,
+            number_of_disks: 1,
+        };
+        let v = include_bytes!("../tests/data/zip64_demo.zip").to_vec();
+        assert_eq!(ZipArchive::new(Cursor::new(v))?.disk_info(), single_disk);
 
-    let mut result = ZipFileData::from_local_block(block, reader)?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        let mut v = writer.finish()?.into_inner();
+        assert_eq!(
+            ZipArchive::new(Cursor::new(v.clone()))?.disk_info(),
+            single_disk
+        );
 
-    match parse_extra_field(&mut result) {
-        Ok(..) | Err(ZipError::Io(..)) => {}
-        Err(e) => return Err(e),
+        // Claim that this is the third disk and that the central directory is on it too, which
+        // can still be read as a single disk
+        let eocd_start = v.len() - 22;
+        v[eocd_start + 4..eocd_start + 8].copy_from_slice(&[2, 0, 2, 0]);
+        let mut archive = ZipArchive::new(Cursor::new(v))?;
+        assert_eq!(
+            archive.disk_info(),
+            DiskInfo {
+                disk_number: 2,
+                disk_with_central_directory: 2,
+                number_of_disks: 3,
+            }
+        );
+        let mut read_back = String::new();
+        archive
+            .by_name("file.txt")?
+            .read_to_string(&mut read_back)?;
+        assert_eq!(read_back, "contents");
+        Ok(())
     }
 
-    let limit_reader = (reader as &'a mut dyn Read).take(result.compressed_size);
+    #[test]
+    fn validate_entry_bounds() -> ZipResult<()> {
+        use super::Config;
+        use crate::result::ZipError;
+        // The second entry declares 1 MiB of stored data in a 261-byte archive
+        let v = include_bytes!("../tests/data/entry_size_past_end.zip").to_vec();
+        let mut archive = ZipArchive::new(Cursor::new(v.clone()))?;
+        let mut read_back = String::new();
+        archive.by_name("ok.txt")?.read_to_string(&mut read_back)?;
+        assert_eq!(read_back, "this entry is fine\n");
+
+        let config = Config {
+            validate_entry_bounds: true,
+            ..Default::default()
+        };
+        let Err(ZipError::InvalidArchive(message)) =
+            ZipArchive::with_config(config, Cursor::new(v))
+        else {
+            panic!("archive should have been rejected");
+        };
+        assert!(message.contains("past the end of the archive"), "{message}");
 
-    let result_crc32 = result.crc32;
-    let result_compression_method = result.compression_method;
-    let crypto_reader = make_crypto_reader(
-        result_compression_method,
-        result_crc32,
-        result.last_modified_time,
-        result.using_data_descriptor,
-        limit_reader,
-        None,
-        None,
-        #[cfg(feature = "aes-crypto")]
-        result.compressed_size,
-    )?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("valid.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        let archive = ZipArchive::with_config(config, writer.finish()?)?;
+        assert_eq!(archive.len(), 1);
+
+        // A ZIP64 extra field declares a compressed size so large that the end overflows
+        let v = include_bytes!("../tests/data/entry_size_overflow.zip").to_vec();
+        let Err(ZipError::InvalidArchive(message)) =
+            ZipArchive::with_config(config, Cursor::new(v))
+        else {
+            panic!("archive should have been rejected");
+        };
+        assert!(message.contains("past the end of the archive"), "{message}");
+        Ok(())
+    }
 
-    Ok(Some(ZipFile {
-        data: Cow::Owned(result),
-        crypto_reader: None,
-        reader: make_reader(result_compression_method, result_crc32, crypto_reader)?,
-    }))
-}
+    #[test]
+    fn read_data_descriptor_with_and_without_signature() -> ZipResult<()> {
+        use super::DataDescriptor;
+        const SIGNATURE: [u8; 4] = *b"PK\x07\x08";
+
+        let signed = include_bytes!("../tests/data/data_descriptor.zip").to_vec();
+        let find = |bytes: &[u8], pattern: &[u8]| {
+            bytes
+                .windows(pattern.len())
+                .position(|window| window == pattern)
+                .unwrap()
+        };
+        let descriptor_start = find(&signed, &SIGNATURE);
+        let expected = DataDescriptor {
+            crc32: 0xb095e5e3,
+            compressed_size: 14,
+            uncompressed_size: 12,
+        };
 
-#[cfg(test)]
-mod test {
-    use crate::result::ZipResult;
-    use crate::write::SimpleFileOptions;
-    use crate::CompressionMethod::Stored;
-    use crate::{ZipArchive, ZipWriter};
-    use std::io::{Cursor, Read, Write};
-    use tempdir::TempDir;
+        // Drop the signature, and move the central directory back to match
+        let mut unsigned = signed.clone();
+        unsigned.drain(descriptor_start..descriptor_start + 4);
+        let eocd_start = find(&unsigned, b"PK\x05\x06");
+        let cd_offset = u32::from_le_bytes(unsigned[eocd_start + 16..][..4].try_into().unwrap());
+        unsigned[eocd_start + 16..][..4].copy_from_slice(&(cd_offset - 4).to_le_bytes());
 
-    #[test]
-    fn invalid_offset() {
-        use super::ZipArchive;
+        for bytes in [&signed, &unsigned] {
+            let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+            assert_eq!(archive.read_data_descriptor(0)?, Some(expected));
+        }
 
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/invalid_offset.zip"));
-        let reader = ZipArchive::new(Cursor::new(v));
-        assert!(reader.is_err());
+        // An unsigned descriptor whose CRC-32 happens to equal the signature
+        let cd_start = find(&unsigned, b"PK\x01\x02");
+        unsigned[cd_start + 16..][..4].copy_from_slice(&SIGNATURE);
+        unsigned[descriptor_start..][..4].copy_from_slice(&SIGNATURE);
+        let mut archive = ZipArchive::new(Cursor::new(unsigned))?;
+        assert_eq!(
+            archive.read_data_descriptor(0)?,
+            Some(DataDescriptor {
+                crc32: u32::from_le_bytes(SIGNATURE),
+                ..expected
+            })
+        );
+        Ok(())
     }
 
     #[test]
-    fn invalid_offset2() {
-        use super::ZipArchive;
-
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/invalid_offset2.zip"));
-        let reader = ZipArchive::new(Cursor::new(v));
-        assert!(reader.is_err());
+    fn deflate_level_hint() -> ZipResult<()> {
+        use crate::DeflateLevelHint;
+        let hint = |bytes: &[u8]| -> ZipResult<Option<DeflateLevelHint>> {
+            let mut archive = ZipArchive::new(Cursor::new(bytes.to_vec()))?;
+            let hint = archive.by_index_raw(0)?.deflate_level_hint();
+            Ok(hint)
+        };
+        // Written by Info-ZIP with -1, -6 and -9
+        let fast = include_bytes!("../tests/data/deflate_level_1.zip");
+        assert_eq!(hint(fast)?, Some(DeflateLevelHint::Fast));
+        let normal = include_bytes!("../tests/data/deflate_level_6.zip");
+        assert_eq!(hint(normal)?, Some(DeflateLevelHint::Normal));
+        let maximum = include_bytes!("../tests/data/deflate_level_9.zip");
+        assert_eq!(hint(maximum)?, Some(DeflateLevelHint::Maximum));
+
+        let stored = include_bytes!("../tests/data/mimetype.zip");
+        assert_eq!(hint(stored)?, None);
+        Ok(())
     }
 
     #[test]
-    fn zip64_with_leading_junk() {
-        use super::ZipArchive;
+    fn write_toc_csv() -> ZipResult<()> {
+        /// Parse RFC 4180 CSV, as written by `write_toc_csv`.
+        fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+            let mut rows = Vec::new();
+            let mut row = Vec::new();
+            let mut field = String::new();
+            let mut chars = csv.chars().peekable();
+            let mut quoted = false;
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (_, '"') => quoted = !quoted,
+                    (false, ',') => row.push(std::mem::take(&mut field)),
+                    (false, '\r') => {}
+                    (false, '\n') => {
+                        row.push(std::mem::take(&mut field));
+                        rows.push(std::mem::take(&mut row));
+                    }
+                    (_, c) => field.push(c),
+                }
+            }
+            rows
+        }
 
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/zip64_demo.zip"));
-        let reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        assert_eq!(reader.len(), 1);
+        let awkward_name = "a, \"quoted\"\nname.txt";
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .unix_permissions(0o644);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(awkward_name, options)?;
+        writer.write_all(b"hello")?;
+        writer.add_directory("dir/", options.unix_permissions(0o755))?;
+        let archive = writer.finish_into_readable()?;
+
+        let mut csv = Vec::new();
+        archive.write_toc_csv(&mut csv)?;
+        let rows = parse_csv(std::str::from_utf8(&csv).unwrap());
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[0],
+            [
+                "name",
+                "size",
+                "compressed_size",
+                "method",
+                "crc32",
+                "modified",
+                "is_dir",
+                "unix_mode"
+            ]
+        );
+        assert_eq!(rows[1][0], awkward_name);
+        assert_eq!(rows[1][1], "5");
+        assert_eq!(rows[1][3], "Stored");
+        assert_eq!(rows[1][4], format!("{:08x}", crc32fast::hash(b"hello")));
+        assert_eq!(rows[1][6], "false");
+        assert_eq!(rows[1][7], "100644");
+        assert_eq!(rows[2][0], "dir/");
+        assert_eq!(rows[2][6], "true");
+        assert_eq!(rows[2][7], "40755");
+        Ok(())
     }
 
     #[test]
-    fn zip_contents() {
-        use super::ZipArchive;
+    fn korean_code_page() -> ZipResult<()> {
+        use crate::read::{Config, LegacyEncoding};
+        let bytes = include_bytes!("../tests/data/korean_code_page.zip");
+        let open = |legacy_encoding| {
+            let config = Config {
+                legacy_encoding,
+                ..Default::default()
+            };
+            ZipArchive::with_config(config, Cursor::new(bytes.to_vec()))
+        };
+
+        let mut archive = open(LegacyEncoding::Auto)?;
+        let korean = archive.by_index(0)?;
+        // "한글.txt" in code page 949
+        assert_eq!(korean.name_raw(), b"\xc7\xd1\xb1\xdb.txt");
+        assert_eq!(korean.code_page(), Some(949));
+        assert_eq!(korean.extra_data_fields().count(), 0);
+        drop(korean);
+        // Without the `encoding` feature, code page 949 falls back to CP437
+        let korean_name = if cfg!(feature = "encoding") {
+            "\u{d55c}\u{ae00}.txt"
+        } else {
+            "\u{255f}\u{2564}\u{2592}\u{2588}.txt"
+        };
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            [korean_name, "caf\u{e9} \u{20ac}.txt", "plain.txt"]
+        );
+        assert_eq!(
+            archive.entries_with_extra_field(0xe57a).collect::<Vec<_>>(),
+            [0, 1]
+        );
 
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
-        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        assert_eq!(reader.comment(), b"");
-        assert_eq!(reader.by_index(0).unwrap().central_header_start(), 77);
+        // The code page is only used when asked for
+        let archive = open(LegacyEncoding::default())?;
+        assert_eq!(archive.file_names().nth(1), Some("caf\u{398} \u{c7}.txt"));
+        Ok(())
     }
 
     #[test]
-    fn zip_read_streaming() {
-        use super::read_zipfile_from_stream;
-
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
-        let mut reader = Cursor::new(v);
-        loop {
-            if read_zipfile_from_stream(&mut reader).unwrap().is_none() {
-                break;
-            }
+    fn extract_entry_to_path() -> ZipResult<()> {
+        let options = SimpleFileOptions::default().unix_permissions(0o640);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("dir/file.txt", options)?;
+        writer.write_all(b"contents")?;
+        writer.add_directory("dir/sub/", options.unix_permissions(0o755))?;
+        writer.start_file("../escape.txt", options)?;
+        writer.write_all(b"escaped")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let dir = TempDir::new("extract_entry_to_path")?;
+        let dest = dir.path().join("a/b/renamed.txt");
+        archive.extract_entry_to_path(0, &dest)?;
+        assert_eq!(std::fs::read(&dest)?, b"contents");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&dest)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
         }
+        // Nothing else was extracted
+        assert_eq!(std::fs::read_dir(dir.path().join("a/b"))?.count(), 1);
+
+        let dest = dir.path().join("directory");
+        archive.extract_entry_to_path(1, &dest)?;
+        assert!(dest.is_dir());
+
+        let dest = dir.path().join("escape.txt");
+        assert!(archive.extract_entry_to_path(2, &dest).is_err());
+        assert!(!dest.exists());
+        assert!(archive
+            .extract_entry_to_path(0, dir.path().join(".."))
+            .is_err());
+        Ok(())
     }
 
     #[test]
-    fn zip_clone() {
-        use super::ZipArchive;
-        use std::io::Read;
-
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/mimetype.zip"));
-        let mut reader1 = ZipArchive::new(Cursor::new(v)).unwrap();
-        let mut reader2 = reader1.clone();
+    fn detect_anomalies() -> ZipResult<()> {
+        use super::ArchiveAnomaly;
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first", options)?;
+        writer.write_all(&[b'a'; 100])?;
+        writer.start_file("second", options)?;
+        writer.write_all(&[b'b'; 100])?;
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.detect_anomalies()?, []);
+        let bytes = archive.into_inner().into_inner();
+
+        // A copy of the first entry's central header, pointing at the same local header
+        let mut writer = ZipWriter::new_append(Cursor::new(bytes.clone()))?;
+        writer.shallow_copy_file("first", "copy")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(
+            archive.detect_anomalies()?,
+            [ArchiveAnomaly::OverlappingEntries {
+                first: 0,
+                second: 2
+            }]
+        );
 
-        let mut file1 = reader1.by_index(0).unwrap();
-        let mut file2 = reader2.by_index(0).unwrap();
+        let patch_central_header = |field: usize, value: u32| -> ZipResult<ZipArchive<_>> {
+            let mut bytes = bytes.clone();
+            let start = ZipArchive::new(Cursor::new(&bytes))?
+                .by_index(1)?
+                .central_header_start() as usize
+                + field;
+            bytes[start..start + 4].copy_from_slice(&value.to_le_bytes());
+            ZipArchive::new(Cursor::new(bytes))
+        };
+        // The second entry's compressed data extends over the central directory
+        let mut archive = patch_central_header(20, 200)?;
+        assert_eq!(
+            archive.detect_anomalies()?,
+            [ArchiveAnomaly::EntryContainsCentralDirectory { index: 1 }]
+        );
+        // The second entry claims to expand 100 bytes to 4 GB
+        let mut archive = patch_central_header(24, 4_000_000_000)?;
+        assert_eq!(
+            archive.detect_anomalies()?,
+            [ArchiveAnomaly::HighCompressionRatio {
+                uncompressed_size: 4_000_000_100,
+                compressed_size: 200
+            }]
+        );
 
-        let t = file1.last_modified().unwrap();
+        // The second entry's local header has lost its signature since the archive was opened, and
+        // where its data starts hasn't been found yet
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        let header_start = archive.by_index(1)?.header_start() as usize;
+        archive.reader.get_mut()[header_start] = 0;
+        std::sync::Arc::get_mut(&mut archive.shared).unwrap().files[1].data_start =
+            Default::default();
         assert_eq!(
-            (
-                t.year(),
-                t.month(),
-                t.day(),
-                t.hour(),
-                t.minute(),
-                t.second()
-            ),
-            (1980, 1, 1, 0, 0, 0)
+            archive.detect_anomalies()?,
+            [ArchiveAnomaly::InvalidLocalHeader { index: 1 }]
         );
+        Ok(())
+    }
 
-        let mut buf1 = [0; 5];
-        let mut buf2 = [0; 5];
-        let mut buf3 = [0; 5];
-        let mut buf4 = [0; 5];
+    #[test]
+    fn read_name() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"first")?;
+        writer.start_file("b.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"second")?;
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.read_name("b.txt")?, b"second");
+        assert_eq!(archive.read_name("a.txt")?, b"first");
+        assert!(matches!(
+            archive.read_name("c.txt"),
+            Err(crate::result::ZipError::FileNotFound)
+        ));
 
-        file1.read_exact(&mut buf1).unwrap();
-        file2.read_exact(&mut buf2).unwrap();
-        file1.read_exact(&mut buf3).unwrap();
-        file2.read_exact(&mut buf4).unwrap();
+        // A stored entry whose contents don't match its CRC-32
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("corrupt.txt", options)?;
+        writer.write_all(b"original")?;
+        let mut bytes = writer.finish()?.into_inner();
+        let data_start = bytes.windows(8).position(|w| w == b"original").unwrap();
+        bytes[data_start] = b'O';
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert!(archive.read_name("corrupt.txt").is_err());
+        Ok(())
+    }
 
-        assert_eq!(buf1, buf2);
-        assert_eq!(buf3, buf4);
-        assert_ne!(buf1, buf3);
+    #[test]
+    fn by_index_sized() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("normal.txt", SimpleFileOptions::default())?;
+        writer.write_all(&b"normal contents ".repeat(50))?;
+        writer.start_file(
+            "descriptor.txt",
+            SimpleFileOptions::default()
+                .local_header_sizes(crate::write::LocalHeaderSizes::DataDescriptorOnly),
+        )?;
+        writer.write_all(b"descriptor contents")?;
+        writer.add_directory("empty/", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut file = archive.by_index_sized(0)?;
+        assert_eq!(file.len(), Some(800));
+        assert!(!file.is_empty());
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(file.len(), Some(contents.len() as u64));
+        drop(file);
+        assert_eq!(archive.by_index_sized(1)?.len(), None);
+        assert!(archive.by_index_sized(2)?.is_empty());
+        Ok(())
     }
 
     #[test]
-    fn file_and_dir_predicates() {
-        use super::ZipArchive;
+    fn total_compressed_size() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "a.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&[b'a'; 300])?;
+        writer.start_file(
+            "b.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&[b'b'; 100])?;
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(archive.total_compressed_size(), Some(400));
+        assert_eq!(archive.compression_ratio(), Some(1.0));
 
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/files_and_dirs.zip"));
-        let mut zip = ZipArchive::new(Cursor::new(v)).unwrap();
+        let archive = ZipWriter::new(Cursor::new(Vec::new())).finish_into_readable()?;
+        assert_eq!(archive.total_compressed_size(), Some(0));
+        assert_eq!(archive.compression_ratio(), None);
 
-        for i in 0..zip.len() {
-            let zip_file = zip.by_index(i).unwrap();
-            let full_name = zip_file.enclosed_name().unwrap();
-            let file_name = full_name.file_name().unwrap().to_str().unwrap();
-            assert!(
-                (file_name.starts_with("dir") && zip_file.is_dir())
-                    || (file_name.starts_with("file") && zip_file.is_file())
-            );
-        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "descriptor.txt",
+            SimpleFileOptions::default()
+                .local_header_sizes(crate::write::LocalHeaderSizes::DataDescriptorOnly),
+        )?;
+        writer.write_all(b"descriptor contents")?;
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(archive.total_compressed_size(), None);
+        assert_eq!(archive.compression_ratio(), None);
+        Ok(())
     }
 
     #[test]
-    fn zip64_magic_in_filenames() {
-        let files = vec![
-            include_bytes!("../tests/data/zip64_magic_in_filename_1.zip").to_vec(),
-            include_bytes!("../tests/data/zip64_magic_in_filename_2.zip").to_vec(),
-            include_bytes!("../tests/data/zip64_magic_in_filename_3.zip").to_vec(),
-            include_bytes!("../tests/data/zip64_magic_in_filename_4.zip").to_vec(),
-            include_bytes!("../tests/data/zip64_magic_in_filename_5.zip").to_vec(),
-        ];
-        // Although we don't allow adding files whose names contain the ZIP64 CDB-end or
-        // CDB-end-locator signatures, we still read them when they aren't genuinely ambiguous.
-        for file in files {
-            ZipArchive::new(Cursor::new(file)).unwrap();
-        }
+    fn entries() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir/", SimpleFileOptions::default())?;
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .last_modified_time(crate::DateTime::from_date_and_time(2024, 5, 6, 7, 8, 10)?);
+        writer.start_file("dir/file.txt", options)?;
+        writer.write_all(b"entry contents")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        assert_eq!(archive.entries().len(), archive.len());
+        let entries: Vec<_> = archive.entries().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name(), "dir/");
+        assert!(entries[0].is_dir());
+        let file = entries[1];
+        assert_eq!(file.name(), "dir/file.txt");
+        assert!(!file.is_dir());
+        assert_eq!(file.compression(), Stored);
+        assert_eq!(file.size(), 14);
+        assert_eq!(file.compressed_size(), 14);
+        assert_eq!(
+            file.last_modified(),
+            Some(crate::DateTime::from_date_and_time(2024, 5, 6, 7, 8, 10)?)
+        );
+        let crc32 = file.crc32();
+        assert_eq!(crc32, archive.by_index(1)?.crc32());
+        Ok(())
     }
 
-    /// test case to ensure we don't preemptively over allocate based on the
-    /// declared number of files in the CDE of an invalid zip when the number of
-    /// files declared is more than the alleged offset in the CDE
     #[test]
-    fn invalid_cde_number_of_files_allocation_smaller_offset() {
-        use super::ZipArchive;
-
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!(
-            "../tests/data/invalid_cde_number_of_files_allocation_smaller_offset.zip"
-        ));
-        let reader = ZipArchive::new(Cursor::new(v));
-        assert!(reader.is_err() || reader.unwrap().is_empty());
+    fn max_extra_fields_per_entry() -> ZipResult<()> {
+        use super::Config;
+
+        // 2,000 empty extra fields in the central directory header
+        let bytes = include_bytes!("../tests/data/many_extra_fields.zip");
+        let open = |max_extra_fields_per_entry, truncate_extra_fields| {
+            let config = Config {
+                max_extra_fields_per_entry,
+                truncate_extra_fields,
+                ..Default::default()
+            };
+            ZipArchive::with_config(config, Cursor::new(bytes))
+        };
+        for result in [ZipArchive::new(Cursor::new(bytes)), open(100, false)] {
+            assert!(matches!(
+                result,
+                Err(crate::result::ZipError::InvalidArchive(
+                    "Entry has too many extra fields"
+                ))
+            ));
+        }
+        for (max, truncate) in [(100, true), (2000, false)] {
+            let mut archive = open(max, truncate)?;
+            let mut contents = String::new();
+            archive.by_index(0)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, "contents");
+        }
+        Ok(())
     }
 
-    /// test case to ensure we don't preemptively over allocate based on the
-    /// declared number of files in the CDE of an invalid zip when the number of
-    /// files declared is less than the alleged offset in the CDE
     #[test]
-    fn invalid_cde_number_of_files_allocation_greater_offset() {
-        use super::ZipArchive;
-
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!(
-            "../tests/data/invalid_cde_number_of_files_allocation_greater_offset.zip"
-        ));
-        let reader = ZipArchive::new(Cursor::new(v));
-        assert!(reader.is_err());
+    fn root_dir() -> ZipResult<()> {
+        let archive = |names: &[&str]| -> ZipResult<ZipArchive<Cursor<Vec<u8>>>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            for name in names {
+                match name.strip_suffix('/') {
+                    Some(_) => writer.add_directory(*name, SimpleFileOptions::default())?,
+                    None => writer.start_file(*name, SimpleFileOptions::default())?,
+                }
+            }
+            writer.finish_into_readable()
+        };
+        let names = [
+            "project-1.2.3/",
+            "project-1.2.3/src/",
+            "project-1.2.3/src/lib.rs",
+            "project-1.2.3\\README.md",
+        ];
+        assert_eq!(archive(&names)?.root_dir(), Some("project-1.2.3"));
+        assert_eq!(archive(&names[2..])?.root_dir(), Some("project-1.2.3"));
+        assert_eq!(archive(&[])?.root_dir(), None);
+        assert_eq!(archive(&["README.md"])?.root_dir(), None);
+        assert_eq!(archive(&["project/a.txt", "README.md"])?.root_dir(), None);
+        assert_eq!(archive(&["project/a.txt", "other/a.txt"])?.root_dir(), None);
+        assert_eq!(archive(&["./a.txt", "./b.txt"])?.root_dir(), None);
+        assert_eq!(archive(&["project", "project/a.txt"])?.root_dir(), None);
+        Ok(())
     }
 
-    #[cfg(feature = "deflate64")]
     #[test]
-    fn deflate64_index_out_of_bounds() -> std::io::Result<()> {
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!(
-            "../tests/data/raw_deflate64_index_out_of_bounds.zip"
+    fn extract_stripping_root() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("project-1.2.3/", SimpleFileOptions::default())?;
+        writer.add_directory("project-1.2.3/src/", SimpleFileOptions::default())?;
+        writer.start_file("project-1.2.3/src/lib.rs", SimpleFileOptions::default())?;
+        writer.write_all(b"// lib")?;
+        writer.start_file("project-1.2.3/README.md", SimpleFileOptions::default())?;
+        writer.write_all(b"# project")?;
+        let mut archive = writer.finish_into_readable()?;
+        let dir = TempDir::new("extract_stripping_root")?;
+        archive.extract_stripping_root(dir.path())?;
+        assert_eq!(std::fs::read(dir.path().join("src/lib.rs"))?, b"// lib");
+        assert_eq!(std::fs::read(dir.path().join("README.md"))?, b"# project");
+        assert!(!dir.path().join("project-1.2.3").exists());
+
+        // Enclosed with the root directory, but not without it
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("project/a.txt", SimpleFileOptions::default())?;
+        writer.start_file("project/../escaped.txt", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+        let dir = TempDir::new("extract_stripping_root")?;
+        assert!(matches!(
+            archive.extract_stripping_root(dir.path().join("inner")),
+            Err(crate::result::ZipError::InvalidArchive("Invalid file path"))
         ));
-        let mut reader = ZipArchive::new(Cursor::new(v))?;
-        std::io::copy(&mut reader.by_index(0)?, &mut std::io::sink()).expect_err("Invalid file");
+        assert!(!dir.path().join("escaped.txt").exists());
         Ok(())
     }
 
-    #[cfg(feature = "deflate64")]
     #[test]
-    fn deflate64_not_enough_space() {
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/deflate64_issue_25.zip"));
-        ZipArchive::new(Cursor::new(v)).expect_err("Invalid file");
+    fn seek_stored() -> ZipResult<()> {
+        use std::io::{Seek, SeekFrom};
+
+        let contents: Vec<u8> = (0..=255).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "stored",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&contents)?;
+        writer.start_file(
+            "after",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"after")?;
+        #[cfg(feature = "deflate-flate2")]
+        {
+            let options =
+                SimpleFileOptions::default().compression_method(crate::CompressionMethod::Deflated);
+            writer.start_file("deflated", options)?;
+            writer.write_all(&contents)?;
+        }
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut file = archive.by_name("stored")?;
+        let mut footer = [0u8; 4];
+        assert_eq!(file.seek(SeekFrom::End(-4))?, 252);
+        file.read_exact(&mut footer)?;
+        assert_eq!(footer, [252, 253, 254, 255]);
+        assert_eq!(file.read(&mut footer)?, 0);
+        assert_eq!(file.seek(SeekFrom::Current(-56))?, 200);
+        file.read_exact(&mut footer)?;
+        assert_eq!(footer, [200, 201, 202, 203]);
+        assert_eq!(file.seek(SeekFrom::Start(0))?, 0);
+        let mut read = Vec::new();
+        file.read_to_end(&mut read)?;
+        assert_eq!(read, contents);
+        assert!(file.seek(SeekFrom::Start(257)).is_err());
+        assert!(file.seek(SeekFrom::Current(-300)).is_err());
+        drop(file);
+
+        let mut file = archive.by_index_raw(0)?;
+        file.seek(SeekFrom::Start(100))?;
+        file.read_exact(&mut footer)?;
+        assert_eq!(footer, [100, 101, 102, 103]);
+        drop(file);
+
+        #[cfg(feature = "deflate-flate2")]
+        {
+            let error = archive
+                .by_name("deflated")?
+                .seek(SeekFrom::Start(1))
+                .unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+        }
+        Ok(())
     }
 
-    #[cfg(feature = "_deflate-any")]
     #[test]
-    fn test_read_with_data_descriptor() {
-        use std::io::Read;
+    fn read_zipfile_from_stream_with_data_descriptor() -> ZipResult<()> {
+        use super::{read_zipfile_from_stream, read_zipfile_from_stream_with_data_descriptor};
+        use crate::write::LocalHeaderSizes::DataDescriptorOnly;
 
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/data_descriptor.zip"));
-        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        let mut decompressed = [0u8; 16];
-        let mut file = reader.by_index(0).unwrap();
-        assert_eq!(file.read(&mut decompressed).unwrap(), 12);
-    }
+        let contents = b"streamed contents ".repeat(100);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().local_header_sizes(DataDescriptorOnly);
+        writer.start_file("stored.txt", options.compression_method(Stored))?;
+        writer.write_all(&contents)?;
+        writer.start_file("deflated.txt", options)?;
+        writer.write_all(&contents)?;
+        writer.start_file("zip64.txt", options.large_file(true))?;
+        writer.write_all(&contents)?;
+        writer.start_file("empty.txt", options)?;
+        writer.start_file("plain.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"plain")?;
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+
+        let mut reader = std::io::BufReader::new(Cursor::new(bytes.clone()));
+        assert!(read_zipfile_from_stream(&mut reader).is_err());
+
+        let mut reader = std::io::BufReader::new(Cursor::new(bytes.clone()));
+        for name in ["stored.txt", "deflated.txt", "zip64.txt", "empty.txt"] {
+            let expected = archive.read_data_descriptor(archive.index_for_name(name).unwrap())?;
+            let mut file = read_zipfile_from_stream_with_data_descriptor(&mut reader)?.unwrap();
+            assert_eq!(file.name(), name);
+            assert_eq!(file.data_descriptor(), None);
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read.len() as u64, expected.unwrap().uncompressed_size);
+            assert_eq!(file.data_descriptor(), expected);
+        }
+        let mut file = read_zipfile_from_stream_with_data_descriptor(&mut reader)?.unwrap();
+        assert_eq!(file.data_descriptor(), None);
+        let mut read = String::new();
+        file.read_to_string(&mut read)?;
+        assert_eq!(read, "plain");
+        drop(file);
+        assert!(read_zipfile_from_stream_with_data_descriptor(&mut reader)?.is_none());
+
+        // Entries that aren't read are skipped up to the end of their data descriptor
+        let mut reader = Cursor::new(bytes.clone());
+        for name in [
+            "stored.txt",
+            "deflated.txt",
+            "zip64.txt",
+            "empty.txt",
+            "plain.txt",
+        ] {
+            let file = read_zipfile_from_stream_with_data_descriptor(&mut reader)?.unwrap();
+            assert_eq!(file.name(), name);
+        }
 
-    #[test]
-    fn test_is_symlink() -> std::io::Result<()> {
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/symlink.zip"));
-        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        assert!(reader.by_index(0).unwrap().is_symlink());
-        let tempdir = TempDir::new("test_is_symlink")?;
-        reader.extract(&tempdir).unwrap();
-        assert!(tempdir.path().join("bar").is_symlink());
+        // A stored entry whose contents don't match the CRC-32 in its data descriptor
+        let mut bytes = bytes;
+        let data_start = bytes.windows(8).position(|w| w == b"streamed").unwrap();
+        bytes[data_start] = b'S';
+        let mut reader = Cursor::new(bytes);
+        let mut file = read_zipfile_from_stream_with_data_descriptor(&mut reader)?.unwrap();
+        assert!(file.read_to_end(&mut Vec::new()).is_err());
         Ok(())
     }
 
     #[test]
-    #[cfg(feature = "_deflate-any")]
-    fn test_utf8_extra_field() {
+    fn data_descriptor_sizes_only() -> ZipResult<()> {
+        use std::io::Seek;
+
         let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/chinese.zip"));
-        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        reader.by_name("七个房间.txt").unwrap();
+        v.extend_from_slice(include_bytes!(
+            "../tests/data/data_descriptor_sizes_only.zip"
+        ));
+        let mut archive = ZipArchive::new(Cursor::new(v))?;
+        // Dropping an entry whose sizes were recovered doesn't read past its local header
+        let file = archive.by_name("stored.txt")?;
+        let data_start = file.data_start();
+        drop(file);
+        assert_eq!(archive.reader.stream_position()?, data_start);
+        for (name, expected) in [
+            ("stored.txt", "stored contents\n".repeat(8)),
+            ("deflated.txt", "deflated contents\n".repeat(8)),
+            ("empty.txt", String::new()),
+        ] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.size(), expected.len() as u64);
+            assert_eq!(file.crc32(), crc32fast::hash(expected.as_bytes()));
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            assert_eq!(contents, expected);
+        }
+        // The central directory itself is unchanged
+        assert_eq!(archive.by_index_raw(0)?.size(), 0);
+
+        // The local header has a ZIP64 extra field, so the data descriptor has 8-byte sizes,
+        // although the central header has none
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!(
+            "../tests/data/data_descriptor_zip64_sizes_only.zip"
+        ));
+        let mut archive = ZipArchive::new(Cursor::new(v))?;
+        let expected = "zip64 descriptor contents\n".repeat(4);
+        let mut file = archive.by_name("zip64.txt")?;
+        assert_eq!(file.size(), expected.len() as u64);
+        assert_eq!(file.compressed_size(), expected.len() as u64);
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, expected);
+        Ok(())
     }
 
     #[test]
-    fn test_utf8() {
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/linux-7z.zip"));
-        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        reader.by_name("你好.txt").unwrap();
+    fn central_directory_limits() -> ZipResult<()> {
+        use super::Config;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..10 {
+            writer.start_file(format!("{i}.txt"), SimpleFileOptions::default())?;
+        }
+        let (bytes, offsets) = writer.finish_with_offsets()?;
+        let bytes = bytes.into_inner();
+        let central_directory_size = offsets.eocd_start - offsets.central_start;
+        let open = |max_file_count, max_central_directory_size| {
+            let config = Config {
+                max_file_count,
+                max_central_directory_size,
+                ..Default::default()
+            };
+            ZipArchive::with_config(config, Cursor::new(&bytes))
+        };
+        assert!(matches!(
+            open(Some(9), None),
+            Err(crate::result::ZipError::InvalidArchive(
+                "Too many files in the central directory"
+            ))
+        ));
+        assert!(matches!(
+            open(None, Some(central_directory_size - 1)),
+            Err(crate::result::ZipError::InvalidArchive(
+                "Central directory is too large"
+            ))
+        ));
+        assert_eq!(open(Some(10), Some(central_directory_size))?.len(), 10);
+        assert_eq!(open(None, None)?.len(), 10);
+        Ok(())
     }
 
     #[test]
-    fn test_utf8_2() {
-        let mut v = Vec::new();
-        v.extend_from_slice(include_bytes!("../tests/data/windows-7zip.zip"));
-        let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        reader.by_name("你好.txt").unwrap();
+    fn dead_space() -> ZipResult<()> {
+        // Alignment padding is part of the extra field
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().with_alignment(4096);
+        writer.start_file("first.txt", options)?;
+        writer.write_all(b"first")?;
+        writer.start_file("second.txt", options)?;
+        writer.write_all(b"second")?;
+        assert_eq!(writer.finish_into_readable()?.dead_space()?, 0);
+
+        // A streaming writer can't rewind over an aborted file, whose local header and data
+        // remain between the files before and after it
+        let mut writer = ZipWriter::new_streaming(Vec::new());
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("first.txt", options)?;
+        writer.write_all(b"first")?;
+        writer.start_file("aborted.txt", options)?;
+        writer.write_all(b"never listed")?;
+        writer.abort_file()?;
+        writer.start_file("last.txt", options)?;
+        writer.write_all(b"last")?;
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let aborted_len = 30 + "aborted.txt".len() as u64 + "never listed".len() as u64;
+        assert_eq!(archive.dead_space()?, aborted_len);
+        Ok(())
     }
 
     #[test]
-    fn test_64k_files() -> ZipResult<()> {
+    fn by_index_with_limit() -> ZipResult<()> {
+        use super::LimitedRead;
+
         let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
-        let options = SimpleFileOptions {
-            compression_method: Stored,
-            ..Default::default()
-        };
-        for i in 0..=u16::MAX {
-            let file_name = format!("{i}.txt");
-            writer.start_file(&*file_name, options)?;
-            writer.write_all(i.to_string().as_bytes())?;
-        }
+        writer.start_file("zeros", SimpleFileOptions::default())?;
+        writer.write_all(&[0u8; 10_000])?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut file = archive.by_index_with_limit(0, 10_000)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents.len(), 10_000);
+        assert_eq!(file.remaining(), 0);
+        drop(file);
+
+        let mut file = archive.by_index_with_limit(0, 9_999)?;
+        let error = file.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            *error.into_inner().unwrap().downcast().unwrap(),
+            crate::result::ZipError::InvalidArchive(LimitedRead::LIMIT_EXCEEDED)
+        ));
+        Ok(())
+    }
 
-        let mut reader = ZipArchive::new(writer.finish()?)?;
-        for i in 0..=u16::MAX {
-            let expected_name = format!("{i}.txt");
-            let expected_contents = i.to_string();
-            let expected_contents = expected_contents.as_bytes();
-            let mut file = reader.by_name(&expected_name)?;
-            let mut contents = Vec::with_capacity(expected_contents.len());
-            file.read_to_end(&mut contents)?;
-            assert_eq!(contents, expected_contents);
-            drop(file);
-            contents.clear();
-            let mut file = reader.by_index(i as usize)?;
-            file.read_to_end(&mut contents)?;
-            assert_eq!(contents, expected_contents);
-        }
+    #[test]
+    fn stream_zip64_data_descriptor() -> ZipResult<()> {
+        use super::{list_from_local_headers, read_zipfile_from_stream_with_data_descriptor};
+
+        // The first entry's local header has zero sizes and a ZIP64 extra field, so its data
+        // descriptor has 8-byte sizes
+        let bytes = include_bytes!("../tests/data/zip64_data_descriptor_streamed.zip");
+        let mut reader = std::io::BufReader::new(Cursor::new(bytes));
+        let mut file = read_zipfile_from_stream_with_data_descriptor(&mut reader)?.unwrap();
+        assert_eq!(file.name(), "zip64.txt");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "zip64 contents\n".repeat(8));
+        let descriptor = file.data_descriptor().unwrap();
+        assert_eq!(descriptor.uncompressed_size, contents.len() as u64);
+        assert_eq!(descriptor.crc32, crc32fast::hash(contents.as_bytes()));
+        drop(file);
+        let mut file = read_zipfile_from_stream_with_data_descriptor(&mut reader)?.unwrap();
+        assert_eq!(file.name(), "small.txt");
+        contents.clear();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "small contents\n");
+        drop(file);
+        assert!(read_zipfile_from_stream_with_data_descriptor(&mut reader)?.is_none());
+
+        let entries = list_from_local_headers(&mut Cursor::new(bytes))?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].uncompressed_size, 120);
+        assert_eq!(entries[1].name.as_ref(), "small.txt");
         Ok(())
     }
 }
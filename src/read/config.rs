@@ -1,9 +1,255 @@
+use crate::cp437::FromCp437;
+use std::num::NonZeroUsize;
+
 /// Configuration for reading ZIP archives.
-#[repr(transparent)]
-#[derive(Debug, Default, Clone, Copy)]
+///
+/// New options may be added in minor releases, so this can't be built with a struct literal.
+/// Start from [`Config::default`] and use the setters instead:
+///
+/// ```
+/// use zip::read::Config;
+///
+/// let config = Config::default()
+///     .strict_utf8_names(true)
+///     .max_file_count(Some(10_000));
+/// assert!(config.strict_utf8_names);
+/// ```
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub struct Config {
     /// An offset into the reader to use to find the start of the archive.
     pub archive_offset: ArchiveOffset,
+
+    /// Whether to reject an archive if the name of an entry flagged as UTF-8 isn't valid UTF-8.
+    ///
+    /// If `false` (the default), invalid sequences are replaced with `U+FFFD`.
+    pub strict_utf8_names: bool,
+
+    /// The character set used to decode names and comments of entries that aren't flagged as
+    /// UTF-8. The raw bytes remain available through [`ZipFile::name_raw`](crate::read::ZipFile::name_raw).
+    pub legacy_encoding: LegacyEncoding,
+
+    /// Whether to check entries marked as stored (uncompressed) for deflated data written by
+    /// broken tools. If a stored entry's data doesn't match its CRC-32 but inflating it produces
+    /// data that does, it's read as a deflated entry instead. The check reads the data of each
+    /// stored entry once more when it's opened, and is skipped for encrypted entries and when no
+    /// deflate feature is enabled. The entry's metadata still describes it as stored.
+    pub sniff_compression: bool,
+
+    /// Size of the heap buffer that [`ZipArchive`](crate::read::ZipArchive) uses to copy entry
+    /// data when extracting or merging archives. If `None` (the default), data is copied through
+    /// [`std::io::copy`], which uses an 8 KiB buffer on the stack. It's also the capacity of the
+    /// buffer returned by [`ZipArchive::by_index_buffered`](crate::read::ZipArchive::by_index_buffered).
+    ///
+    /// On top of this buffer, reading an entry holds the decoder state of its compression method.
+    /// Peak heap usage per entry is approximately:
+    ///
+    /// * Stored: nothing beyond the archive's reader.
+    /// * Deflated: 32 KiB of input buffer and about 43 KiB of inflate state, including the
+    ///   32 KiB window.
+    /// * Deflate64: 8 KiB of input buffer and a 128 KiB window.
+    /// * Bzip2: 32 KiB of input buffer and up to about 3.6 MiB for the largest (900 KiB) block size.
+    /// * Zstd: about 128 KiB of input buffer and the window size declared by the frame, which may
+    ///   be up to 128 MiB.
+    /// * LZMA: the dictionary size declared by the header, which may be up to 4 GiB, and the
+    ///   output decoded from each 4 KiB of input until it's read.
+    ///
+    /// So memory use is bounded independently of entry size, but not independently of archive
+    /// contents for Zstd and LZMA. Services with a hard memory budget should reject those methods
+    /// or entries whose declared sizes they can't afford.
+    pub io_buffer_size: Option<NonZeroUsize>,
+
+    /// Whether to reject an archive if the compressed data of any entry, as declared by its
+    /// central-directory header, extends past the end of the reader. This detects truncated
+    /// archives and overlong size declarations when the archive is opened, rather than when the
+    /// affected entry is read.
+    pub validate_entry_bounds: bool,
+
+    /// The most extra fields that are parsed from the header of a single entry. Each one is at
+    /// least 4 bytes, so an entry's 65,535 bytes of extra data can hold up to 16,383 of them,
+    /// whereas real archives seldom have more than a handful. The default is 1,024.
+    pub max_extra_fields_per_entry: usize,
+
+    /// What to do when an entry has more extra fields than
+    /// [`max_extra_fields_per_entry`](Config::max_extra_fields_per_entry). If `false` (the
+    /// default), the archive is rejected. If `true`, the rest are skipped; their bytes remain
+    /// available through [`ZipFile::extra_data`](crate::read::ZipFile::extra_data), but they
+    /// don't affect how the entry is read.
+    pub truncate_extra_fields: bool,
+
+    /// The most entries an archive's central directory may declare. If `Some`, an archive
+    /// declaring more is rejected before any of its entries are read, however plausible the
+    /// count is given the size of the reader. The default is `None`.
+    pub max_file_count: Option<usize>,
+
+    /// The largest central directory, in bytes, that is read when opening an archive. If `Some`,
+    /// an archive is rejected as soon as reading its central directory goes past this size,
+    /// whatever size it declares. The default is `None`.
+    pub max_central_directory_size: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            archive_offset: ArchiveOffset::default(),
+            strict_utf8_names: false,
+            legacy_encoding: LegacyEncoding::default(),
+            sniff_compression: false,
+            io_buffer_size: None,
+            validate_entry_bounds: false,
+            max_extra_fields_per_entry: 1024,
+            truncate_extra_fields: false,
+            max_file_count: None,
+            max_central_directory_size: None,
+        }
+    }
+}
+
+impl Config {
+    /// Sets [`archive_offset`](Config::archive_offset).
+    #[must_use]
+    pub const fn archive_offset(mut self, archive_offset: ArchiveOffset) -> Self {
+        self.archive_offset = archive_offset;
+        self
+    }
+
+    /// Sets [`strict_utf8_names`](Config::strict_utf8_names).
+    #[must_use]
+    pub const fn strict_utf8_names(mut self, strict_utf8_names: bool) -> Self {
+        self.strict_utf8_names = strict_utf8_names;
+        self
+    }
+
+    /// Sets [`legacy_encoding`](Config::legacy_encoding).
+    #[must_use]
+    pub const fn legacy_encoding(mut self, legacy_encoding: LegacyEncoding) -> Self {
+        self.legacy_encoding = legacy_encoding;
+        self
+    }
+
+    /// Sets [`sniff_compression`](Config::sniff_compression).
+    #[must_use]
+    pub const fn sniff_compression(mut self, sniff_compression: bool) -> Self {
+        self.sniff_compression = sniff_compression;
+        self
+    }
+
+    /// Sets [`io_buffer_size`](Config::io_buffer_size).
+    #[must_use]
+    pub const fn io_buffer_size(mut self, io_buffer_size: Option<NonZeroUsize>) -> Self {
+        self.io_buffer_size = io_buffer_size;
+        self
+    }
+
+    /// Sets [`validate_entry_bounds`](Config::validate_entry_bounds).
+    #[must_use]
+    pub const fn validate_entry_bounds(mut self, validate_entry_bounds: bool) -> Self {
+        self.validate_entry_bounds = validate_entry_bounds;
+        self
+    }
+
+    /// Sets [`max_extra_fields_per_entry`](Config::max_extra_fields_per_entry).
+    #[must_use]
+    pub const fn max_extra_fields_per_entry(mut self, max_extra_fields_per_entry: usize) -> Self {
+        self.max_extra_fields_per_entry = max_extra_fields_per_entry;
+        self
+    }
+
+    /// Sets [`truncate_extra_fields`](Config::truncate_extra_fields).
+    #[must_use]
+    pub const fn truncate_extra_fields(mut self, truncate_extra_fields: bool) -> Self {
+        self.truncate_extra_fields = truncate_extra_fields;
+        self
+    }
+
+    /// Sets [`max_file_count`](Config::max_file_count).
+    #[must_use]
+    pub const fn max_file_count(mut self, max_file_count: Option<usize>) -> Self {
+        self.max_file_count = max_file_count;
+        self
+    }
+
+    /// Sets [`max_central_directory_size`](Config::max_central_directory_size).
+    #[must_use]
+    pub const fn max_central_directory_size(
+        mut self,
+        max_central_directory_size: Option<u64>,
+    ) -> Self {
+        self.max_central_directory_size = max_central_directory_size;
+        self
+    }
+}
+
+/// A single-byte character set for names and comments that aren't flagged as UTF-8.
+///
+/// The ZIP specification says these are IBM code page 437, but some tools write them in the
+/// system code page instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LegacyEncoding {
+    /// IBM code page 437, as required by the specification
+    #[default]
+    Cp437,
+    /// ISO 8859-1, in which each byte is the Unicode code point of the same value
+    Latin1,
+    /// Windows code page 1252, which differs from ISO 8859-1 in the range 0x80..=0x9F. Bytes in
+    /// that range which it leaves undefined are decoded as in ISO 8859-1.
+    Windows1252,
+    /// The code page named by an entry's Korean code page extra field (0xe57a), if it's one of
+    /// the encodings above (437, 1252 or 28591 for ISO 8859-1) or, with the `encoding` feature,
+    /// 949 for Korean. Other entries are decoded as IBM code page 437, and the code page they name
+    /// is still available from [`ZipFile::code_page`](crate::read::ZipFile::code_page), for
+    /// decoding [`ZipFile::name_raw`](crate::read::ZipFile::name_raw) with another library.
+    Auto,
+}
+
+impl LegacyEncoding {
+    pub(crate) fn decode(self, bytes: Box<[u8]>) -> Box<str> {
+        match self {
+            LegacyEncoding::Cp437 | LegacyEncoding::Auto => bytes.from_cp437(),
+            LegacyEncoding::Latin1 => bytes.iter().copied().map(char::from).collect(),
+            LegacyEncoding::Windows1252 => {
+                bytes.iter().copied().map(windows_1252_to_char).collect()
+            }
+        }
+    }
+
+    /// The encoding for a Windows code page identifier, if it's supported.
+    pub(crate) const fn from_code_page(code_page: u16) -> Option<Self> {
+        match code_page {
+            437 => Some(LegacyEncoding::Cp437),
+            1252 => Some(LegacyEncoding::Windows1252),
+            28591 => Some(LegacyEncoding::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Decodes text in a Windows code page, if it's supported and the text is valid in it.
+    pub(crate) fn decode_code_page(code_page: u16, bytes: &[u8]) -> Option<Box<str>> {
+        if let Some(encoding) = Self::from_code_page(code_page) {
+            return Some(encoding.decode(bytes.into()));
+        }
+        #[cfg(feature = "encoding")]
+        if code_page == 949 {
+            // The WHATWG EUC-KR encoding is Windows code page 949
+            return encoding_rs::EUC_KR
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(Into::into);
+        }
+        None
+    }
+}
+
+fn windows_1252_to_char(input: u8) -> char {
+    const HIGH_CONTROL_RANGE: [u16; 32] = [
+        0x20ac, 0x0081, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160,
+        0x2039, 0x0152, 0x008d, 0x017d, 0x008f, 0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022,
+        0x2013, 0x2014, 0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x009d, 0x017e, 0x0178,
+    ];
+    match input {
+        0x80..=0x9f => char::from_u32(HIGH_CONTROL_RANGE[(input - 0x80) as usize].into()).unwrap(),
+        _ => char::from(input),
+    }
 }
 
 /// The offset of the start of the archive from the beginning of the reader.
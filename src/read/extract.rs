@@ -0,0 +1,960 @@
+//! Options for extracting an archive to the filesystem
+
+use crate::result::{ZipError, ZipResult};
+use std::ffi::OsStr;
+use std::fs::{self, create_dir_all};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Options for [`ZipArchive::extract_with_options`](super::ZipArchive::extract_with_options).
+///
+/// New options may be added in minor releases, so start from [`ExtractOptions::default`] and
+/// set the fields you need.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct ExtractOptions {
+    /// How to handle the AppleDouble entries (`__MACOSX/` and `._` files) that macOS adds to
+    /// archives to carry resource forks and extended attributes.
+    pub apple_double: AppleDoublePolicy,
+    /// What to do with an entry whose path would escape the extraction directory, as determined
+    /// by [`ZipFile::enclosed_name`](crate::read::ZipFile::enclosed_name).
+    pub on_unsafe_path: UnsafePathPolicy,
+    /// On Unix, a mask that is AND-ed with the permission bits of each stored mode before it's
+    /// applied to an extracted file, like a umask in reverse: `Some(0o755)` strips group and
+    /// world write access. Has no effect on entries whose mode is overridden by `force_mode`.
+    pub permission_mask: Option<u32>,
+    /// On Unix, a mode to apply to every extracted file instead of the one stored in the
+    /// archive.
+    pub force_mode: Option<u32>,
+    /// On Unix, whether to clear the set-user-ID, set-group-ID and sticky bits of each stored
+    /// mode before it's applied. Defaults to `true`: an untrusted archive could otherwise plant
+    /// a set-user-ID executable, which runs with the privileges of whoever extracted it when it's
+    /// run by someone else. Before this option was added, the bits were always kept. It doesn't
+    /// apply to `force_mode`.
+    pub strip_special_bits: bool,
+    /// On Unix, whether to give extracted files, directories and symbolic links the user and
+    /// group IDs stored in an Info-ZIP Unix (0x7875) or PKWARE Unix (0x000d) extra field. This
+    /// needs root privileges or `CAP_CHOWN`: without them, the IDs are ignored unless
+    /// `strict_ownership` is set.
+    pub restore_ownership: bool,
+    /// Whether to fail extraction when `restore_ownership` is set but changing the owner of a
+    /// file isn't permitted.
+    pub strict_ownership: bool,
+    /// What to do when a regular file is about to be extracted over an existing one.
+    pub overwrite: Overwrite,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            apple_double: AppleDoublePolicy::default(),
+            on_unsafe_path: UnsafePathPolicy::default(),
+            permission_mask: None,
+            force_mode: None,
+            strip_special_bits: true,
+            restore_ownership: false,
+            strict_ownership: false,
+            overwrite: Overwrite::default(),
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// The mode to give an extracted file, if any, given the mode stored in the archive.
+    pub(crate) fn effective_mode(&self, stored: Option<u32>) -> Option<u32> {
+        if let Some(mode) = self.force_mode {
+            return Some(mode);
+        }
+        let mut mode = stored?;
+        if self.strip_special_bits {
+            mode &= !0o7000;
+        }
+        Some(match self.permission_mask {
+            Some(mask) => mode & (mask | !0o7777),
+            None => mode,
+        })
+    }
+}
+
+/// A filesystem that [`ZipArchive::extract_to_vfs`](super::ZipArchive::extract_to_vfs) can
+/// extract into, such as one kept in memory on platforms without `std::fs`.
+///
+/// Paths are relative to the root of the extraction, and have been checked or sanitized according
+/// to [`ExtractOptions::on_unsafe_path`], so they have no root or `..` components. They're given
+/// to `create_file` and `create_symlink` without first creating their parent directories, which
+/// implementations have to create if their storage needs them.
+pub trait VirtualFs {
+    /// Writer for the contents of a regular file
+    type File: Write;
+
+    /// Create a directory and any missing parents, unless it already exists.
+    fn create_dir(&mut self, path: &Path) -> ZipResult<()>;
+
+    /// Create a regular file, replacing any existing one, and return a writer for its contents.
+    fn create_file(&mut self, path: &Path) -> ZipResult<Self::File>;
+
+    /// Create a symbolic link to `target`, which is stored in the archive relative to the root of
+    /// the extraction. `target_is_dir` is whether the archive has a directory entry with the
+    /// target's name, for filesystems that distinguish links to directories.
+    fn create_symlink(&mut self, path: &Path, target: &Path, target_is_dir: bool) -> ZipResult<()>;
+
+    /// Set the Unix mode of a regular file, once every entry has been extracted. The mode is the
+    /// one stored in the archive, adjusted as described by [`ExtractOptions`].
+    fn set_permissions(&mut self, path: &Path, mode: u32) -> ZipResult<()>;
+
+    /// Set the user and group IDs of an extracted entry, without following it if it's a symbolic
+    /// link, when [`ExtractOptions::restore_ownership`] is set. Does nothing by default, for
+    /// filesystems without owners. A [`std::io::ErrorKind::PermissionDenied`] error is ignored
+    /// unless [`ExtractOptions::strict_ownership`] is set.
+    #[allow(unused_variables)]
+    fn set_owner(&mut self, path: &Path, uid: u32, gid: u32) -> ZipResult<()> {
+        Ok(())
+    }
+
+    /// Return the modification time of the file at `path`, or `None` if there's no such file,
+    /// so that [`ExtractOptions::overwrite`] can decide whether to replace it. By default, every
+    /// file is treated as missing, so it's always written.
+    #[allow(unused_variables)]
+    fn modified(&mut self, path: &Path) -> ZipResult<Option<SystemTime>> {
+        Ok(None)
+    }
+}
+
+/// The real filesystem, below a root directory
+pub(crate) struct DirectoryFs<'a> {
+    pub(crate) root: &'a Path,
+}
+
+impl VirtualFs for DirectoryFs<'_> {
+    type File = fs::File;
+
+    fn create_dir(&mut self, path: &Path) -> ZipResult<()> {
+        make_writable_dir_all(self.root.join(path))
+    }
+
+    fn create_file(&mut self, path: &Path) -> ZipResult<fs::File> {
+        let outpath = self.root.join(path);
+        if let Some(p) = outpath.parent() {
+            make_writable_dir_all(p)?;
+        }
+        Ok(fs::File::create(outpath)?)
+    }
+
+    #[allow(unused_variables)]
+    fn create_symlink(&mut self, path: &Path, target: &Path, target_is_dir: bool) -> ZipResult<()> {
+        let outpath = self.root.join(path);
+        if let Some(p) = outpath.parent() {
+            make_writable_dir_all(p)?;
+        }
+        let target_path = self.root.join(target);
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(target_path, outpath)?;
+        #[cfg(windows)]
+        {
+            let target_is_dir =
+                target_is_dir || fs::metadata(&target_path).is_ok_and(|metadata| metadata.is_dir());
+            if target_is_dir {
+                std::os::windows::fs::symlink_dir(target_path, outpath)?;
+            } else {
+                std::os::windows::fs::symlink_file(target_path, outpath)?;
+            }
+        }
+        // Other platforms such as WebAssembly don't support symbolic links, so a file containing
+        // the target path takes their place
+        #[cfg(not(any(unix, windows)))]
+        fs::File::create(outpath)?.write_all(target.to_string_lossy().as_bytes())?;
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn set_permissions(&mut self, path: &Path, mode: u32) -> ZipResult<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(self.root.join(path), fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    #[allow(unused_variables)]
+    fn set_owner(&mut self, path: &Path, uid: u32, gid: u32) -> ZipResult<()> {
+        #[cfg(unix)]
+        std::os::unix::fs::lchown(self.root.join(path), Some(uid), Some(gid))?;
+        Ok(())
+    }
+
+    fn modified(&mut self, path: &Path) -> ZipResult<Option<SystemTime>> {
+        match fs::symlink_metadata(self.root.join(path)) {
+            Ok(metadata) => Ok(Some(metadata.modified()?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Finds the user and group IDs stored in an Info-ZIP Unix (0x7875) or PKWARE Unix (0x000d)
+/// extra field.
+pub(crate) fn unix_owner(mut extra: &[u8]) -> Option<(u32, u32)> {
+    fn read_id(bytes: &[u8]) -> Option<u32> {
+        // IDs are little-endian and may be wider than 32 bits, as long as they fit
+        let (low, high) = bytes.split_at(bytes.len().min(4));
+        if high.iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let mut id = [0; 4];
+        id[..low.len()].copy_from_slice(low);
+        Some(u32::from_le_bytes(id))
+    }
+
+    while let [kind_lo, kind_hi, len_lo, len_hi, rest @ ..] = extra {
+        let len = u16::from_le_bytes([*len_lo, *len_hi]) as usize;
+        let data = rest.get(..len)?;
+        match u16::from_le_bytes([*kind_lo, *kind_hi]) {
+            // Version 1, then the size and value of the UID, then the size and value of the GID
+            0x7875 => {
+                if let [1, uid_size, rest @ ..] = data {
+                    let uid = rest.get(..*uid_size as usize)?;
+                    let (gid_size, gid) = rest[uid.len()..].split_first()?;
+                    return Some((read_id(uid)?, read_id(gid.get(..*gid_size as usize)?)?));
+                }
+            }
+            // Access time, modification time, then 16-bit UID and GID
+            0x000d if len >= 12 => {
+                let uid = u16::from_le_bytes([data[8], data[9]]);
+                let gid = u16::from_le_bytes([data[10], data[11]]);
+                return Some((uid.into(), gid.into()));
+            }
+            _ => {}
+        }
+        extra = &rest[len..];
+    }
+    None
+}
+
+fn make_writable_dir_all<T: AsRef<Path>>(outpath: T) -> ZipResult<()> {
+    create_dir_all(outpath.as_ref())?;
+    #[cfg(unix)]
+    {
+        // Dirs must be writable until all normal files are extracted
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(
+            outpath.as_ref(),
+            fs::Permissions::from_mode(
+                0o700 | fs::metadata(outpath.as_ref())?.permissions().mode(),
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// How [`ZipArchive::extract_with_options`](super::ZipArchive::extract_with_options) handles
+/// AppleDouble entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppleDoublePolicy {
+    /// Extract them as ordinary files.
+    #[default]
+    Keep,
+    /// Don't extract them.
+    Skip,
+    /// Don't extract them, but set the extended attributes and resource forks they contain on
+    /// the files they describe.
+    #[cfg(target_os = "macos")]
+    Apply,
+}
+
+/// How [`ZipArchive::extract_with_options`](super::ZipArchive::extract_with_options) handles
+/// entries whose paths are absolute or lead outside the extraction directory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnsafePathPolicy {
+    /// Stop extracting and return an error.
+    #[default]
+    Abort,
+    /// Don't extract them.
+    Skip,
+    /// Extract them to the path returned by
+    /// [`ZipFile::mangled_name`](crate::read::ZipFile::mangled_name), which drops any root and
+    /// `..` components. Entries whose path is empty once sanitized are skipped.
+    Sanitize,
+}
+
+/// How [`ZipArchive::extract_with_options`](super::ZipArchive::extract_with_options) handles
+/// regular files that already exist at the path an entry is extracted to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overwrite {
+    /// Replace them.
+    #[default]
+    Always,
+    /// Keep them, and don't extract the entry.
+    Never,
+    /// Replace them only if the entry's modification time is later than theirs. The entry's time
+    /// is taken from its NTFS or extended timestamp extra field if it has one, and otherwise from
+    /// [`ZipFile::last_modified`](crate::read::ZipFile::last_modified), which is assumed to be
+    /// UTC since its time zone isn't recorded. Entries without a modification time are skipped.
+    IfArchiveNewer,
+}
+
+/// Converts an MS-DOS date and time, assumed to be UTC, to a [`SystemTime`].
+pub(crate) fn msdos_to_system_time(datetime: crate::DateTime) -> Option<SystemTime> {
+    // Days since the Unix epoch, from the proleptic Gregorian calendar with years starting in
+    // March so that the leap day comes last
+    let (month, day) = (datetime.month() as u64, datetime.day() as u64);
+    let year = datetime.year() as u64 - u64::from(month <= 2);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let days = year * 365 + year / 4 - year / 100 + year / 400 + day_of_year - 719_468;
+    let seconds = days * 86_400
+        + datetime.hour() as u64 * 3_600
+        + datetime.minute() as u64 * 60
+        + datetime.second() as u64;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+}
+
+/// Converts an NTFS time, in 100-nanosecond intervals since 1601-01-01 UTC, to a [`SystemTime`].
+pub(crate) fn ntfs_to_system_time(ticks: u64) -> Option<SystemTime> {
+    const TICKS_PER_SECOND: u64 = 10_000_000;
+    let since_1601 = Duration::new(
+        ticks / TICKS_PER_SECOND,
+        (ticks % TICKS_PER_SECOND) as u32 * 100,
+    );
+    let epoch_since_1601 = Duration::from_secs(11_644_473_600);
+    match since_1601.checked_sub(epoch_since_1601) {
+        Some(after) => SystemTime::UNIX_EPOCH.checked_add(after),
+        None => SystemTime::UNIX_EPOCH.checked_sub(epoch_since_1601 - since_1601),
+    }
+}
+
+/// What an entry is, as far as AppleDouble handling is concerned.
+pub(crate) enum AppleDoubleEntry {
+    /// An ordinary entry
+    None,
+    /// AppleDouble data for the file at the given path, relative to the extraction directory
+    Metadata(#[cfg_attr(not(target_os = "macos"), allow(dead_code))] PathBuf),
+    /// Some other entry under `__MACOSX/`, such as a directory
+    Other,
+}
+
+impl AppleDoubleEntry {
+    pub(crate) fn classify(path: &Path) -> Self {
+        let mut components = path.components();
+        let in_macosx = components
+            .next()
+            .is_some_and(|first| first == Component::Normal(OsStr::new("__MACOSX")));
+        let described = if in_macosx {
+            components.as_path()
+        } else {
+            path
+        };
+        let metadata_target = described.file_name().and_then(|name| {
+            let name = name.to_str()?.strip_prefix("._")?;
+            if name.is_empty() {
+                return None;
+            }
+            Some(described.with_file_name(name))
+        });
+        match metadata_target {
+            Some(target) => Self::Metadata(target),
+            None if in_macosx => Self::Other,
+            None => Self::None,
+        }
+    }
+}
+
+/// The largest AppleDouble entry that's read into memory to apply it to the file it describes.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub(crate) const MAX_APPLE_DOUBLE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Read an AppleDouble entry into memory, failing if it's longer than `limit` bytes rather than
+/// trusting the archive to keep it small.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub(crate) fn read_apple_double(reader: impl Read, limit: u64) -> ZipResult<Vec<u8>> {
+    let mut data = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(ZipError::InvalidArchive("AppleDouble entry is too large"));
+    }
+    Ok(data)
+}
+
+const APPLE_DOUBLE_MAGIC: u32 = 0x00051607;
+const ENTRY_ID_RESOURCE_FORK: u32 = 2;
+const ENTRY_ID_FINDER_INFO: u32 = 9;
+const FINDER_INFO_LEN: usize = 32;
+const ATTR_MAGIC: u32 = u32::from_be_bytes(*b"ATTR");
+const ATTR_HEADER_LEN: usize = 36;
+
+fn read_be_u32(data: &[u8], offset: usize) -> ZipResult<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or(ZipError::InvalidArchive("AppleDouble data is truncated"))
+}
+
+fn read_be_u16(data: &[u8], offset: usize) -> ZipResult<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or(ZipError::InvalidArchive("AppleDouble data is truncated"))
+}
+
+fn slice(data: &[u8], offset: u32, len: u32) -> ZipResult<&[u8]> {
+    let start = offset as usize;
+    start
+        .checked_add(len as usize)
+        .and_then(|end| data.get(start..end))
+        .ok_or(ZipError::InvalidArchive("AppleDouble data is truncated"))
+}
+
+/// Parse an AppleDouble file as written by macOS, returning the extended attributes it contains
+/// as `(name, value)` pairs. The Finder info and resource fork are returned as the
+/// `com.apple.FinderInfo` and `com.apple.ResourceFork` attributes respectively.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub(crate) fn parse_apple_double(data: &[u8]) -> ZipResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    if read_be_u32(data, 0)? != APPLE_DOUBLE_MAGIC {
+        return Err(ZipError::InvalidArchive("Invalid AppleDouble magic"));
+    }
+    let entry_count = read_be_u16(data, 24)?;
+    let mut attributes = Vec::new();
+    for index in 0..entry_count as usize {
+        let entry = 26 + 12 * index;
+        let id = read_be_u32(data, entry)?;
+        let offset = read_be_u32(data, entry + 4)?;
+        let len = read_be_u32(data, entry + 8)?;
+        let contents = slice(data, offset, len)?;
+        match id {
+            ENTRY_ID_RESOURCE_FORK if !contents.is_empty() => {
+                attributes.push((b"com.apple.ResourceFork".to_vec(), contents.to_vec()));
+            }
+            ENTRY_ID_FINDER_INFO => {
+                let finder_info = &contents[..contents.len().min(FINDER_INFO_LEN)];
+                if finder_info.iter().any(|&b| b != 0) {
+                    attributes.push((b"com.apple.FinderInfo".to_vec(), finder_info.to_vec()));
+                }
+                // Extended attributes follow the Finder info, after 2 bytes of padding
+                let attr_header = offset as usize + FINDER_INFO_LEN + 2;
+                if contents.len() >= FINDER_INFO_LEN + 2 + ATTR_HEADER_LEN
+                    && read_be_u32(data, attr_header)? == ATTR_MAGIC
+                {
+                    parse_attributes(data, attr_header, &mut attributes)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(attributes)
+}
+
+fn parse_attributes(
+    data: &[u8],
+    attr_header: usize,
+    attributes: &mut Vec<(Vec<u8>, Vec<u8>)>,
+) -> ZipResult<()> {
+    let attr_count = read_be_u16(data, attr_header + 34)?;
+    let mut entry = attr_header + ATTR_HEADER_LEN;
+    for _ in 0..attr_count {
+        let offset = read_be_u32(data, entry)?;
+        let len = read_be_u32(data, entry + 4)?;
+        let name_len = *data
+            .get(entry + 10)
+            .ok_or(ZipError::InvalidArchive("AppleDouble data is truncated"))?;
+        let name = slice(data, (entry + 11) as u32, name_len as u32)?;
+        let name = name.strip_suffix(&[0]).unwrap_or(name);
+        attributes.push((name.to_vec(), slice(data, offset, len)?.to_vec()));
+        // Entries are aligned to 4 bytes
+        entry = (entry + 11 + name_len as usize + 3) & !3;
+    }
+    Ok(())
+}
+
+/// Set the extended attributes from an AppleDouble file on `path`.
+#[cfg(target_os = "macos")]
+pub(crate) fn apply_apple_double(path: &Path, data: &[u8]) -> ZipResult<()> {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+
+    extern "C" {
+        fn setxattr(
+            path: *const c_char,
+            name: *const c_char,
+            value: *const c_void,
+            size: usize,
+            position: u32,
+            options: c_int,
+        ) -> c_int;
+    }
+    const XATTR_NOFOLLOW: c_int = 0x0001;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| ZipError::InvalidArchive("Invalid file path"))?;
+    for (name, value) in parse_apple_double(data)? {
+        let name = CString::new(name)
+            .map_err(|_| ZipError::InvalidArchive("Invalid extended attribute name"))?;
+        // SAFETY: all pointers are valid for the given lengths, and the strings are
+        // NUL-terminated.
+        let result = unsafe {
+            setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+                XATTR_NOFOLLOW,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_apple_double, read_apple_double, AppleDoubleEntry, AppleDoublePolicy, ExtractOptions,
+        UnsafePathPolicy,
+    };
+    use crate::result::ZipResult;
+    use crate::write::SimpleFileOptions;
+    use crate::{ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    /// Build an AppleDouble file holding a single extended attribute, as macOS would.
+    fn apple_double_with_attribute(name: &str, value: &[u8]) -> Vec<u8> {
+        let mut attr_entry = Vec::new();
+        let attr_entries_start = 0x54 + 36;
+        let name_len = name.len() + 1;
+        let attr_entries_len = (11 + name_len + 3) & !3;
+        let value_start = attr_entries_start + attr_entries_len;
+        attr_entry.extend_from_slice(&(value_start as u32).to_be_bytes());
+        attr_entry.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        attr_entry.extend_from_slice(&0u16.to_be_bytes());
+        attr_entry.push(name_len as u8);
+        attr_entry.extend_from_slice(name.as_bytes());
+        attr_entry.push(0);
+        attr_entry.resize(attr_entries_len, 0);
+        let end = value_start + value.len();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x00051607u32.to_be_bytes());
+        data.extend_from_slice(&0x00020000u32.to_be_bytes());
+        data.extend_from_slice(b"Mac OS X        ");
+        data.extend_from_slice(&2u16.to_be_bytes());
+        // Finder info, followed by the extended attributes
+        data.extend_from_slice(&9u32.to_be_bytes());
+        data.extend_from_slice(&0x32u32.to_be_bytes());
+        data.extend_from_slice(&(end as u32 - 0x32).to_be_bytes());
+        // Empty resource fork
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&(end as u32).to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.resize(0x54, 0);
+        data.extend_from_slice(b"ATTR");
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(&(end as u32).to_be_bytes());
+        data.extend_from_slice(&(value_start as u32).to_be_bytes());
+        data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&attr_entry);
+        data.extend_from_slice(value);
+        data
+    }
+
+    fn archive_with_apple_double() -> ZipResult<ZipArchive<Cursor<Vec<u8>>>> {
+        let options = SimpleFileOptions::default();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir", options)?;
+        writer.start_file("dir/file.txt", options)?;
+        writer.write_all(b"contents")?;
+        writer.add_directory("__MACOSX", options)?;
+        writer.add_directory("__MACOSX/dir", options)?;
+        writer.start_file("__MACOSX/dir/._file.txt", options)?;
+        writer.write_all(&apple_double_with_attribute("com.example.test", b"value"))?;
+        writer.finish_into_readable()
+    }
+
+    #[test]
+    fn classify() {
+        assert!(matches!(
+            AppleDoubleEntry::classify(Path::new("__MACOSX/dir/._file.txt")),
+            AppleDoubleEntry::Metadata(target) if target == Path::new("dir/file.txt")
+        ));
+        assert!(matches!(
+            AppleDoubleEntry::classify(Path::new("._file.txt")),
+            AppleDoubleEntry::Metadata(target) if target == Path::new("file.txt")
+        ));
+        assert!(matches!(
+            AppleDoubleEntry::classify(Path::new("__MACOSX/dir")),
+            AppleDoubleEntry::Other
+        ));
+        assert!(matches!(
+            AppleDoubleEntry::classify(Path::new("dir/file.txt")),
+            AppleDoubleEntry::None
+        ));
+    }
+
+    #[test]
+    fn parse() -> ZipResult<()> {
+        let data = apple_double_with_attribute("com.example.test", b"value");
+        assert_eq!(
+            parse_apple_double(&data)?,
+            [(b"com.example.test".to_vec(), b"value".to_vec())]
+        );
+        assert!(parse_apple_double(&data[..0x60]).is_err());
+
+        let len = data.len() as u64;
+        assert_eq!(read_apple_double(&data[..], len)?, data);
+        assert!(read_apple_double(&data[..], len - 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn keep_and_skip() -> ZipResult<()> {
+        let mut archive = archive_with_apple_double()?;
+
+        let kept = TempDir::new("apple_double_keep")?;
+        archive.extract(&kept)?;
+        assert!(kept.path().join("__MACOSX/dir/._file.txt").is_file());
+
+        let skipped = TempDir::new("apple_double_skip")?;
+        let options = ExtractOptions {
+            apple_double: AppleDoublePolicy::Skip,
+            ..Default::default()
+        };
+        archive.extract_with_options(&skipped, options)?;
+        assert!(skipped.path().join("dir/file.txt").is_file());
+        assert!(!skipped.path().join("__MACOSX").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn unsafe_path_policies() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("safe.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"safe")?;
+        writer.start_file("../escape", SimpleFileOptions::default())?;
+        writer.write_all(b"escape")?;
+        let mut archive = writer.finish_into_readable()?;
+        let extract = |archive: &mut ZipArchive<_>, on_unsafe_path| -> ZipResult<TempDir> {
+            let parent = TempDir::new("unsafe_path_policies")?;
+            let dir = parent.path().join("out");
+            let options = ExtractOptions {
+                on_unsafe_path,
+                ..Default::default()
+            };
+            let result = archive.extract_with_options(&dir, options);
+            assert!(!parent.path().join("escape").exists());
+            result.map(|_| parent)
+        };
+
+        assert!(extract(&mut archive, UnsafePathPolicy::Abort).is_err());
+
+        let skipped = extract(&mut archive, UnsafePathPolicy::Skip)?;
+        let out = skipped.path().join("out");
+        assert_eq!(std::fs::read(out.join("safe.txt"))?, b"safe");
+        assert!(!out.join("escape").exists());
+
+        let sanitized = extract(&mut archive, UnsafePathPolicy::Sanitize)?;
+        let out = sanitized.path().join("out");
+        assert_eq!(std::fs::read(out.join("safe.txt"))?, b"safe");
+        assert_eq!(std::fs::read(out.join("escape"))?, b"escape");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn permission_mask_and_force_mode() -> ZipResult<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "open.sh",
+            SimpleFileOptions::default().unix_permissions(0o777),
+        )?;
+        writer.write_all(b"#!/bin/sh\n")?;
+        let mut archive = writer.finish_into_readable()?;
+        let mode_of = |dir: &TempDir| -> ZipResult<u32> {
+            Ok(std::fs::metadata(dir.path().join("open.sh"))?
+                .permissions()
+                .mode()
+                & 0o7777)
+        };
+
+        let masked = TempDir::new("permission_mask")?;
+        let options = ExtractOptions {
+            permission_mask: Some(0o755),
+            ..Default::default()
+        };
+        archive.extract_with_options(&masked, options)?;
+        assert_eq!(mode_of(&masked)?, 0o755);
+
+        let forced = TempDir::new("force_mode")?;
+        let options = ExtractOptions {
+            permission_mask: Some(0o755),
+            force_mode: Some(0o600),
+            ..Default::default()
+        };
+        archive.extract_with_options(&forced, options)?;
+        assert_eq!(mode_of(&forced)?, 0o600);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn strip_special_bits() -> ZipResult<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "setuid",
+            SimpleFileOptions::default().unix_permissions(0o755),
+        )?;
+        writer.write_all(b"#!/bin/sh\n")?;
+        let mut bytes = writer.finish()?.into_inner();
+        // The writer doesn't store special bits, so add them to the external attributes
+        let central_header_start = ZipArchive::new(Cursor::new(&bytes))?
+            .by_index(0)?
+            .central_header_start() as usize;
+        let mode_bytes = central_header_start + 40..central_header_start + 42;
+        bytes[mode_bytes].copy_from_slice(&0o104755u16.to_le_bytes());
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mode_of = |dir: &TempDir| -> ZipResult<u32> {
+            Ok(std::fs::metadata(dir.path().join("setuid"))?
+                .permissions()
+                .mode()
+                & 0o7777)
+        };
+
+        let stripped = TempDir::new("strip_special_bits")?;
+        archive.extract(&stripped)?;
+        assert_eq!(mode_of(&stripped)?, 0o755);
+
+        let kept = TempDir::new("keep_special_bits")?;
+        let options = ExtractOptions {
+            strip_special_bits: false,
+            ..Default::default()
+        };
+        archive.extract_with_options(&kept, options)?;
+        assert_eq!(mode_of(&kept)?, 0o4755);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn apply() -> ZipResult<()> {
+        use std::ffi::CString;
+        use std::os::raw::{c_char, c_int, c_void};
+        use std::os::unix::ffi::OsStrExt;
+
+        extern "C" {
+            fn getxattr(
+                path: *const c_char,
+                name: *const c_char,
+                value: *mut c_void,
+                size: usize,
+                position: u32,
+                options: c_int,
+            ) -> isize;
+        }
+
+        let mut archive = archive_with_apple_double()?;
+        let applied = TempDir::new("apple_double_apply")?;
+        let options = ExtractOptions {
+            apple_double: AppleDoublePolicy::Apply,
+            ..Default::default()
+        };
+        archive.extract_with_options(&applied, options)?;
+        assert!(!applied.path().join("__MACOSX").exists());
+
+        let path =
+            CString::new(applied.path().join("dir/file.txt").as_os_str().as_bytes()).unwrap();
+        let name = CString::new("com.example.test").unwrap();
+        let mut value = [0u8; 16];
+        // SAFETY: the buffer is valid for its length, and the strings are NUL-terminated.
+        let len = unsafe {
+            getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+                0,
+                0,
+            )
+        };
+        assert_eq!(len, 5);
+        assert_eq!(&value[..5], b"value");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn restore_ownership() -> ZipResult<()> {
+        use std::os::unix::fs::MetadataExt;
+        // test.txt has an Info-ZIP Unix extra field with UID and GID 1000
+        let bytes = include_bytes!("../../tests/data/extended_timestamp.zip");
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let dir = TempDir::new("restore_ownership")?;
+        let options = ExtractOptions {
+            restore_ownership: true,
+            ..Default::default()
+        };
+        // Without privileges, the IDs are ignored and the files belong to whoever extracted them
+        archive.extract_with_options(&dir, options)?;
+        let extracted = std::fs::metadata(dir.path().join("test.txt"))?;
+        let untouched = std::fs::metadata(dir.path().join("mimetype"))?;
+        if untouched.uid() == 0 {
+            assert_eq!((extracted.uid(), extracted.gid()), (1000, 1000));
+        } else {
+            assert_eq!(extracted.uid(), untouched.uid());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn restore_ownership_denied() -> ZipResult<()> {
+        use super::{DirectoryFs, VirtualFs};
+        use std::fs::File;
+
+        struct DenyingFs<'a>(DirectoryFs<'a>);
+
+        impl VirtualFs for DenyingFs<'_> {
+            type File = File;
+
+            fn create_dir(&mut self, path: &Path) -> ZipResult<()> {
+                self.0.create_dir(path)
+            }
+
+            fn create_file(&mut self, path: &Path) -> ZipResult<File> {
+                self.0.create_file(path)
+            }
+
+            fn create_symlink(
+                &mut self,
+                path: &Path,
+                target: &Path,
+                is_dir: bool,
+            ) -> ZipResult<()> {
+                self.0.create_symlink(path, target, is_dir)
+            }
+
+            fn set_permissions(&mut self, path: &Path, mode: u32) -> ZipResult<()> {
+                self.0.set_permissions(path, mode)
+            }
+
+            fn set_owner(&mut self, _: &Path, _: u32, _: u32) -> ZipResult<()> {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied).into())
+            }
+        }
+
+        let bytes = include_bytes!("../../tests/data/extended_timestamp.zip");
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let dir = TempDir::new("restore_ownership_denied")?;
+        let mut vfs = DenyingFs(DirectoryFs { root: dir.path() });
+        let mut options = ExtractOptions {
+            restore_ownership: true,
+            ..Default::default()
+        };
+        archive.extract_to_vfs(&mut vfs, options)?;
+        assert!(dir.path().join("test.txt").is_file());
+
+        options.strict_ownership = true;
+        assert!(archive.extract_to_vfs(&mut vfs, options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlink_target() -> ZipResult<()> {
+        let target = format!("{}/target.txt", ["directory"; 40].join("/"));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("link", target.as_str(), SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let dir = TempDir::new("symlink_target")?;
+        archive.extract(&dir)?;
+        let link = std::fs::read_link(dir.path().join("link"))?;
+        assert_eq!(link, dir.path().join(&target));
+        Ok(())
+    }
+
+    #[test]
+    fn system_times() {
+        use super::{msdos_to_system_time, ntfs_to_system_time};
+        use crate::DateTime;
+        use std::time::{Duration, SystemTime};
+
+        let at = |seconds| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        let msdos = |year, month, day, hour, minute, second| {
+            DateTime::from_date_and_time(year, month, day, hour, minute, second).unwrap()
+        };
+        assert_eq!(
+            msdos_to_system_time(msdos(1980, 1, 1, 0, 0, 0)),
+            at(315_532_800)
+        );
+        assert_eq!(
+            msdos_to_system_time(msdos(2000, 3, 1, 0, 0, 0)),
+            at(951_868_800)
+        );
+        assert_eq!(
+            msdos_to_system_time(msdos(2024, 2, 29, 12, 34, 56)),
+            at(1_709_210_096)
+        );
+        assert_eq!(ntfs_to_system_time(116_444_736_000_000_000), at(0));
+        assert_eq!(
+            ntfs_to_system_time(116_444_736_010_000_001),
+            Some(SystemTime::UNIX_EPOCH + Duration::new(1, 100))
+        );
+    }
+
+    #[test]
+    fn overwrite() -> ZipResult<()> {
+        use super::Overwrite;
+        use crate::DateTime;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let old = DateTime::from_date_and_time(2000, 1, 1, 0, 0, 0).unwrap();
+        let future = DateTime::from_date_and_time(2107, 12, 31, 0, 0, 0).unwrap();
+        writer.start_file(
+            "old.txt",
+            SimpleFileOptions::default().last_modified_time(old),
+        )?;
+        writer.write_all(b"archived")?;
+        writer.start_file(
+            "future.txt",
+            SimpleFileOptions::default().last_modified_time(future),
+        )?;
+        writer.write_all(b"archived")?;
+        writer.start_file("new.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"archived")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        for (overwrite, expected) in [
+            (Overwrite::Always, ["archived", "archived"]),
+            (Overwrite::Never, ["local", "local"]),
+            (Overwrite::IfArchiveNewer, ["local", "archived"]),
+        ] {
+            let dir = TempDir::new("overwrite")?;
+            std::fs::write(dir.path().join("old.txt"), "local")?;
+            std::fs::write(dir.path().join("future.txt"), "local")?;
+            let options = ExtractOptions {
+                overwrite,
+                ..Default::default()
+            };
+            archive.extract_with_options(&dir, options)?;
+            let contents = ["old.txt", "future.txt"]
+                .map(|name| std::fs::read_to_string(dir.path().join(name)).unwrap());
+            assert_eq!(contents, expected, "{overwrite:?}");
+            // Files that don't exist yet are always extracted
+            assert_eq!(
+                std::fs::read_to_string(dir.path().join("new.txt"))?,
+                "archived"
+            );
+        }
+        Ok(())
+    }
+}
@@ -3,8 +3,8 @@ use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
 use super::{
-    central_header_to_zip_file_inner, read_zipfile_from_stream, ZipCentralEntryBlock, ZipError,
-    ZipFile, ZipFileData, ZipResult,
+    central_header_to_zip_file_inner, read_zipfile_from_stream, Config, ZipCentralEntryBlock,
+    ZipError, ZipFile, ZipFileData, ZipResult,
 };
 use crate::spec::FixedSizeBlock;
 
@@ -33,6 +33,7 @@ impl<R: Read> ZipStreamReader<R> {
             archive_offset,
             central_header_start,
             block,
+            &Config::default(),
         )?;
         Ok(ZipStreamFileMetadata(file))
     }
@@ -182,7 +183,7 @@ impl ZipStreamFileMetadata {
         self.name()
             .chars()
             .next_back()
-            .map_or(false, |c| c == '/' || c == '\\')
+            .is_some_and(|c| c == '/' || c == '\\')
     }
 
     /// Returns whether the file is a regular file
@@ -220,6 +221,7 @@ mod test {
         }
     }
 
+    #[allow(dead_code)]
     #[derive(Default, Debug, Eq, PartialEq)]
     struct CounterVisitor(u64, u64);
     impl ZipStreamVisitor for CounterVisitor {
@@ -54,6 +54,7 @@ impl Magic {
     pub const CENTRAL_DIRECTORY_END_SIGNATURE: Self = Self::literal(0x06054b50);
     pub const ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE: Self = Self::literal(0x06064b50);
     pub const ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE: Self = Self::literal(0x07064b50);
+    pub const DATA_DESCRIPTOR_SIGNATURE: Self = Self::literal(0x08074b50);
 }
 
 /// Similar to [`Magic`], but used for extra field tags as per section 4.5.3 of APPNOTE.TXT.
@@ -241,14 +242,22 @@ impl FixedSizeBlock for Zip32CDEBlock {
     ];
 }
 
-#[derive(Debug)]
-pub(crate) struct Zip32CentralDirectoryEnd {
+/// The ZIP32 end-of-central-directory record, as specified in APPNOTE 4.3.16
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Zip32CentralDirectoryEnd {
+    /// Number of this disk
     pub disk_number: u16,
+    /// Number of the disk with the start of the central directory
     pub disk_with_central_directory: u16,
+    /// Number of central directory entries on this disk
     pub number_of_files_on_this_disk: u16,
+    /// Total number of central directory entries, or 0xFFFF if it's in the ZIP64 record
     pub number_of_files: u16,
+    /// Size of the central directory, or 0xFFFFFFFF if it's in the ZIP64 record
     pub central_directory_size: u32,
+    /// Offset of the start of the central directory, or 0xFFFFFFFF if it's in the ZIP64 record
     pub central_directory_offset: u32,
+    /// Archive comment, which must be shorter than 64 KiB
     pub zip_file_comment: Box<[u8]>,
 }
 
@@ -279,6 +288,8 @@ impl Zip32CentralDirectoryEnd {
         Ok((block, zip_file_comment))
     }
 
+    /// Parses the record, starting with its signature, along with the comment that follows it.
+    /// If the reader ends before the declared length of the comment, the comment is truncated.
     pub fn parse<T: Read>(reader: &mut T) -> ZipResult<Zip32CentralDirectoryEnd> {
         let Zip32CDEBlock {
             // magic,
@@ -292,8 +303,13 @@ impl Zip32CentralDirectoryEnd {
             ..
         } = Zip32CDEBlock::parse(reader)?;
 
-        let mut zip_file_comment = vec![0u8; zip_file_comment_length as usize].into_boxed_slice();
-        reader.read_exact(&mut zip_file_comment)?;
+        // Some writers declare a longer comment than they wrote, so whatever bytes are left are
+        // taken as the comment
+        let mut zip_file_comment = Vec::with_capacity(zip_file_comment_length as usize);
+        reader
+            .take(zip_file_comment_length as u64)
+            .read_to_end(&mut zip_file_comment)?;
+        let zip_file_comment = zip_file_comment.into_boxed_slice();
 
         Ok(Zip32CentralDirectoryEnd {
             disk_number,
@@ -307,7 +323,7 @@ impl Zip32CentralDirectoryEnd {
     }
 
     #[allow(clippy::type_complexity)]
-    pub fn find_and_parse<T: Read + Seek>(
+    pub(crate) fn find_and_parse<T: Read + Seek>(
         reader: &mut T,
     ) -> ZipResult<Box<[(Rc<Zip32CentralDirectoryEnd>, u64)]>> {
         let mut results = vec![];
@@ -386,6 +402,7 @@ impl Zip32CentralDirectoryEnd {
         }
     }
 
+    /// Writes the record, followed by the comment. Fails if the comment is too long.
     pub fn write<T: Write>(self, writer: &mut T) -> ZipResult<()> {
         let (block, comment) = self.block_and_comment()?;
         block.write(writer)?;
@@ -422,13 +439,19 @@ impl FixedSizeBlock for Zip64CDELocatorBlock {
     ];
 }
 
-pub(crate) struct Zip64CentralDirectoryEndLocator {
+/// The ZIP64 end-of-central-directory locator, as specified in APPNOTE 4.3.15
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Zip64CentralDirectoryEndLocator {
+    /// Number of the disk with the start of the ZIP64 end-of-central-directory record
     pub disk_with_central_directory: u32,
+    /// Offset of the ZIP64 end-of-central-directory record
     pub end_of_central_directory_offset: u64,
+    /// Total number of disks
     pub number_of_disks: u32,
 }
 
 impl Zip64CentralDirectoryEndLocator {
+    /// Parses the locator, starting with its signature.
     pub fn parse<T: Read>(reader: &mut T) -> ZipResult<Zip64CentralDirectoryEndLocator> {
         let Zip64CDELocatorBlock {
             // magic,
@@ -445,7 +468,7 @@ impl Zip64CentralDirectoryEndLocator {
         })
     }
 
-    pub fn block(self) -> Zip64CDELocatorBlock {
+    pub(crate) fn block(self) -> Zip64CDELocatorBlock {
         let Self {
             disk_with_central_directory,
             end_of_central_directory_offset,
@@ -459,6 +482,7 @@ impl Zip64CentralDirectoryEndLocator {
         }
     }
 
+    /// Writes the locator.
     pub fn write<T: Write>(self, writer: &mut T) -> ZipResult<()> {
         self.block().write(writer)
     }
@@ -503,19 +527,31 @@ impl FixedSizeBlock for Zip64CDEBlock {
     ];
 }
 
-pub(crate) struct Zip64CentralDirectoryEnd {
+/// The ZIP64 end-of-central-directory record, as specified in APPNOTE 4.3.14. The extensible
+/// data sector isn't supported: it's skipped when parsing and never written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Zip64CentralDirectoryEnd {
+    /// Version made by
     pub version_made_by: u16,
+    /// Version needed to extract
     pub version_needed_to_extract: u16,
+    /// Number of this disk
     pub disk_number: u32,
+    /// Number of the disk with the start of the central directory
     pub disk_with_central_directory: u32,
+    /// Number of central directory entries on this disk
     pub number_of_files_on_this_disk: u64,
+    /// Total number of central directory entries
     pub number_of_files: u64,
+    /// Size of the central directory
     pub central_directory_size: u64,
+    /// Offset of the start of the central directory
     pub central_directory_offset: u64,
     //pub extensible_data_sector: Vec<u8>, <-- We don't do anything with this at the moment.
 }
 
 impl Zip64CentralDirectoryEnd {
+    /// Parses the record, starting with its signature.
     pub fn parse<T: Read>(reader: &mut T) -> ZipResult<Zip64CentralDirectoryEnd> {
         let Zip64CDEBlock {
             // record_size,
@@ -541,7 +577,7 @@ impl Zip64CentralDirectoryEnd {
         })
     }
 
-    pub fn find_and_parse<T: Read + Seek>(
+    pub(crate) fn find_and_parse<T: Read + Seek>(
         reader: &mut T,
         search_lower_bound: u64,
         search_upper_bound: u64,
@@ -624,7 +660,7 @@ impl Zip64CentralDirectoryEnd {
         }
     }
 
-    pub fn block(self) -> Zip64CDEBlock {
+    pub(crate) fn block(self) -> Zip64CDEBlock {
         let Self {
             version_made_by,
             version_needed_to_extract,
@@ -650,6 +686,7 @@ impl Zip64CentralDirectoryEnd {
         }
     }
 
+    /// Writes the record.
     pub fn write<T: Write>(self, writer: &mut T) -> ZipResult<()> {
         self.block().write(writer)
     }
@@ -659,7 +696,7 @@ pub(crate) fn is_dir(filename: &str) -> bool {
     filename
         .chars()
         .next_back()
-        .map_or(false, |c| c == '/' || c == '\\')
+        .is_some_and(|c| c == '/' || c == '\\')
 }
 
 #[cfg(test)]
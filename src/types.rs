@@ -20,7 +20,7 @@ pub(crate) mod ffi {
     pub const S_IFLNK: u32 = 0o0120000;
 }
 
-use crate::extra_fields::ExtraField;
+use crate::extra_fields::{ExtraField, UnlistedExtraField};
 use crate::result::DateTimeRangeError;
 use crate::spec::is_dir;
 use crate::types::ffi::S_IFDIR;
@@ -34,11 +34,16 @@ pub(crate) struct ZipRawValues {
     pub(crate) uncompressed_size: u64,
 }
 
+/// The host system an entry was made on, stored in the upper byte of "version made by". It
+/// determines how the entry's external attributes are interpreted.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 #[repr(u8)]
 pub enum System {
+    /// MS-DOS and OS/2 (FAT/VFAT/FAT32); the external attributes are MS-DOS attributes
     Dos = 0,
+    /// Unix; the upper 16 bits of the external attributes are the file mode
     Unix = 3,
+    /// Any other host system
     #[default]
     Unknown,
 }
@@ -244,6 +249,12 @@ impl DateTime {
         )
     }
 
+    /// Converts an msdos date and time packed into a u32, with the date in the high 16 bits and
+    /// the time in the low 16 bits, to a DateTime object if it represents a valid date and time.
+    pub fn from_msdos_u32(packed: u32) -> Result<DateTime, DateTimeRangeError> {
+        Self::try_from_msdos((packed >> 16) as u16, packed as u16)
+    }
+
     /// Constructs a DateTime from a specific date and time
     ///
     /// The bounds are:
@@ -261,10 +272,6 @@ impl DateTime {
         minute: u8,
         second: u8,
     ) -> Result<DateTime, DateTimeRangeError> {
-        fn is_leap_year(year: u16) -> bool {
-            (year % 4 == 0) && ((year % 25 != 0) || (year % 16 == 0))
-        }
-
         if (1980..=2107).contains(&year)
             && (1..=12).contains(&month)
             && (1..=31).contains(&day)
@@ -273,14 +280,7 @@ impl DateTime {
             && second <= 60
         {
             let second = second.min(58); // exFAT can't store leap seconds
-            let max_day = match month {
-                1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-                4 | 6 | 9 | 11 => 30,
-                2 if is_leap_year(year) => 29,
-                2 => 28,
-                _ => unreachable!(),
-            };
-            if day > max_day {
+            if day > Self::days_in_month(year, month) {
                 return Err(DateTimeRangeError);
             }
             Ok(DateTime {
@@ -296,6 +296,43 @@ impl DateTime {
         }
     }
 
+    /// Constructs a DateTime from a specific date and time, saturating each field into its valid
+    /// range instead of failing.
+    ///
+    /// Each field is clamped independently:
+    /// * year: below 1980 becomes 1980, above 2107 becomes 2107
+    /// * month: 0 becomes 1, above 12 becomes 12
+    /// * day: 0 becomes 1, past the end of the (clamped) month becomes its last day
+    /// * hour: above 23 becomes 23
+    /// * minute: above 59 becomes 59
+    /// * second: above 58 becomes 58
+    ///
+    /// The other fields aren't adjusted to compensate, so for example 1970-06-15 becomes
+    /// 1980-06-15 rather than the earliest representable time.
+    pub fn clamped(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        let year = year.clamp(1980, 2107);
+        let month = month.clamp(1, 12);
+        DateTime {
+            year,
+            month,
+            day: day.clamp(1, Self::days_in_month(year, month)),
+            hour: hour.min(23),
+            minute: minute.min(59),
+            second: second.min(58),
+        }
+    }
+
+    fn days_in_month(year: u16, month: u8) -> u8 {
+        let is_leap_year = (year % 4 == 0) && ((year % 25 != 0) || (year % 16 == 0));
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap_year => 29,
+            2 => 28,
+            _ => unreachable!(),
+        }
+    }
+
     /// Indicates whether this date and time can be written to a zip archive.
     pub fn is_valid(&self) -> bool {
         DateTime::from_date_and_time(
@@ -328,6 +365,12 @@ impl DateTime {
         (self.day as u16) | ((self.month as u16) << 5) | ((self.year - 1980) << 9)
     }
 
+    /// Gets this datetime in the msdos representation packed into a u32, with the date in the
+    /// high 16 bits and the time in the low 16 bits
+    pub const fn to_msdos_u32(&self) -> u32 {
+        ((self.datepart() as u32) << 16) | self.timepart() as u32
+    }
+
     #[cfg(feature = "time")]
     /// Converts the DateTime to a OffsetDateTime structure
     #[deprecated(since = "1.3.1", note = "use `OffsetDateTime::try_from()` instead")]
@@ -437,6 +480,9 @@ pub struct ZipFileData {
     pub compression_method: crate::compression::CompressionMethod,
     /// Compression level to store the file
     pub compression_level: Option<i64>,
+    /// Compression level hint from general purpose flag bits 1 and 2. Only set for Deflated and
+    /// Deflate64 entries.
+    pub deflate_level_hint: Option<DeflateLevelHint>,
     /// Last modified time. This will only have a 2 second precision.
     pub last_modified_time: Option<DateTime>,
     /// CRC32 checksum
@@ -453,11 +499,15 @@ pub struct ZipFileData {
     pub extra_field: Option<Arc<Vec<u8>>>,
     /// Extra field only written to central directory
     pub central_extra_field: Option<Arc<Vec<u8>>>,
+    /// Extra field only written to the local header, after `extra_field`
+    pub local_extra_field: Option<Arc<Vec<u8>>>,
     /// File comment
     pub file_comment: Box<str>,
     /// Specifies where the local header of the file starts
     pub header_start: u64,
-    /// Specifies where the extra data of the file starts
+    /// Specifies where the extra field in the local header starts, i.e. the offset just past the
+    /// file name and any ZIP64 extra field. Only known for entries written by `ZipWriter`, which
+    /// uses it to update the AES extra field in place.
     pub extra_data_start: Option<u64>,
     /// Specifies where the central header of the file starts
     ///
@@ -473,18 +523,31 @@ pub struct ZipFileData {
     pub aes_mode: Option<(AesMode, AesVendorVersion, CompressionMethod)>,
     /// Specifies where in the extra data the AES metadata starts
     pub aes_extra_data_start: u64,
+    /// Version needed to extract to write instead of the computed one
+    pub version_needed_override: Option<u16>,
 
     /// extra fields, see <https://libzip.org/specifications/extrafld.txt>
     pub extra_fields: Vec<ExtraField>,
+    /// extra fields that aren't exposed through [`ExtraField`]
+    pub(crate) unlisted_extra_fields: Vec<UnlistedExtraField>,
 }
 
 impl ZipFileData {
+    /// The Windows code page named by the Korean code page extra field, if there is one
+    pub(crate) fn code_page(&self) -> Option<u16> {
+        self.unlisted_extra_fields
+            .iter()
+            .find_map(|field| match field {
+                UnlistedExtraField::KoreanCodePage(code_page) => Some(*code_page),
+                _ => None,
+            })
+    }
+
     /// Get the starting offset of the data of the compressed file
     pub fn data_start(&self) -> u64 {
         *self.data_start.get().unwrap()
     }
 
-    #[allow(dead_code)]
     pub fn is_dir(&self) -> bool {
         is_dir(&self.file_name)
     }
@@ -560,6 +623,9 @@ impl ZipFileData {
 
     /// PKZIP version needed to open this file (from APPNOTE 4.4.3.2).
     pub fn version_needed(&self) -> u16 {
+        if let Some(version_needed) = self.version_needed_override {
+            return version_needed;
+        }
         let compression_version: u16 = match self.compression_method {
             CompressionMethod::Stored => MIN_VERSION.into(),
             #[cfg(feature = "_deflate-any")]
@@ -603,6 +669,13 @@ impl ZipFileData {
             .unwrap_or_default()
     }
     #[inline(always)]
+    pub(crate) fn local_extra_field_len(&self) -> usize {
+        self.local_extra_field
+            .as_ref()
+            .map(|v| v.len())
+            .unwrap_or_default()
+    }
+    #[inline(always)]
     pub(crate) fn central_extra_field_len(&self) -> usize {
         self.central_extra_field
             .as_ref()
@@ -628,14 +701,39 @@ impl ZipFileData {
         let permissions = options.permissions.unwrap_or(0o100644);
         let file_name: Box<str> = name.into();
         let file_name_raw: Box<[u8]> = file_name.bytes().collect();
+        let external_attributes = match options.system {
+            System::Dos => {
+                // MS-DOS attributes can only record the directory and read-only bits; files get
+                // the archive bit, as DOS itself would set
+                let mut attributes = if permissions & 0o170000 == S_IFDIR {
+                    0x10
+                } else {
+                    0x20
+                };
+                if permissions & 0o222 == 0 {
+                    attributes |= 0x01;
+                }
+                attributes
+            }
+            _ => permissions << 16,
+        };
         let mut local_block = ZipFileData {
-            system: System::Unix,
+            system: options.system,
             version_made_by: DEFAULT_VERSION,
             encrypted: options.encrypt_with.is_some(),
-            using_data_descriptor: false,
-            is_utf8: !file_name.is_ascii(),
+            using_data_descriptor: options.local_header_sizes
+                == crate::write::LocalHeaderSizes::DataDescriptorOnly,
+            is_utf8: options.force_utf8 || !file_name.is_ascii(),
             compression_method,
             compression_level: options.compression_level,
+            deflate_level_hint: if DeflateLevelHint::applies_to(compression_method) {
+                Some(match options.deflate_level_hint {
+                    Some(hint) => hint,
+                    None => DeflateLevelHint::from_level(options.compression_level),
+                })
+            } else {
+                None
+            },
             last_modified_time: Some(options.last_modified_time),
             crc32: raw_values.crc32,
             compressed_size: raw_values.compressed_size,
@@ -644,16 +742,19 @@ impl ZipFileData {
             file_name_raw,
             extra_field: Some(extra_field.to_vec().into()),
             central_extra_field: options.extended_options.central_extra_data().cloned(),
+            local_extra_field: options.extended_options.local_extra_data().cloned(),
             file_comment: String::with_capacity(0).into_boxed_str(),
             header_start,
             data_start: OnceLock::new(),
             central_header_start: 0,
-            external_attributes: permissions << 16,
+            external_attributes,
             large_file: options.large_file,
             aes_mode,
             extra_fields: Vec::new(),
+            unlisted_extra_fields: Vec::new(),
             extra_data_start,
             aes_extra_data_start,
+            version_needed_override: options.version_needed,
         };
         local_block.version_made_by = local_block.version_needed() as u8;
         local_block
@@ -679,20 +780,10 @@ impl ZipFileData {
         } = block;
 
         let encrypted: bool = flags & 1 == 1;
-        if encrypted {
-            return Err(ZipError::UnsupportedArchive(
-                "Encrypted files are not supported",
-            ));
-        }
 
         /* FIXME: these were previously incorrect: add testing! */
         /* flags & (1 << 3) != 0 */
         let using_data_descriptor: bool = flags & (1 << 3) == 1 << 3;
-        if using_data_descriptor {
-            return Err(ZipError::UnsupportedArchive(
-                "The file length is not available in the local header",
-            ));
-        }
 
         /* flags & (1 << 1) != 0 */
         let is_utf8: bool = flags & (1 << 11) != 0;
@@ -720,6 +811,7 @@ impl ZipFileData {
             is_utf8,
             compression_method,
             compression_level: None,
+            deflate_level_hint: DeflateLevelHint::from_flags(compression_method, flags),
             last_modified_time: DateTime::try_from_msdos(last_mod_date, last_mod_time).ok(),
             crc32,
             compressed_size: compressed_size.into(),
@@ -728,6 +820,7 @@ impl ZipFileData {
             file_name_raw: file_name_raw.into(),
             extra_field: Some(Arc::new(extra_field)),
             central_extra_field: None,
+            local_extra_field: None,
             file_comment: String::with_capacity(0).into_boxed_str(), // file comment is only available in the central directory
             // header_start and data start are not available, but also don't matter, since seeking is
             // not available.
@@ -741,8 +834,10 @@ impl ZipFileData {
             large_file: false,
             aes_mode: None,
             extra_fields: Vec::new(),
+            unlisted_extra_fields: Vec::new(),
             extra_data_start: None,
             aes_extra_data_start: 0,
+            version_needed_override: None,
         })
     }
 
@@ -751,18 +846,30 @@ impl ZipFileData {
     }
 
     fn is_ascii(&self) -> bool {
-        self.file_name_raw.is_ascii()
+        self.file_name_raw.is_ascii() && self.file_comment.is_ascii()
     }
 
     fn flags(&self) -> u16 {
-        let utf8_bit: u16 = if self.is_utf8() && !self.is_ascii() {
+        // The name and comment are written as UTF-8, so the flag is only needed when they aren't
+        // ASCII (which CP437 readers decode the same way), unless it was explicitly requested.
+        let utf8_bit: u16 = if self.is_utf8() && (self.is_utf8 || !self.is_ascii()) {
             1u16 << 11
         } else {
             0
         };
         let encrypted_bit: u16 = if self.encrypted { 1u16 << 0 } else { 0 };
+        let data_descriptor_bit: u16 = if self.using_data_descriptor {
+            1u16 << 3
+        } else {
+            0
+        };
+
+        let level_hint_bits = match self.deflate_level_hint {
+            Some(hint) => hint.to_flags(),
+            None => 0,
+        };
 
-        utf8_bit | encrypted_bit
+        utf8_bit | encrypted_bit | data_descriptor_bit | level_hint_bits
     }
 
     fn clamp_size_field(&self, field: u64) -> u32 {
@@ -774,16 +881,23 @@ impl ZipFileData {
     }
 
     pub(crate) fn local_block(&self) -> ZipResult<ZipLocalEntryBlock> {
-        let compressed_size: u32 = self.clamp_size_field(self.compressed_size);
-        let uncompressed_size: u32 = self.clamp_size_field(self.uncompressed_size);
+        // With a data descriptor, the CRC-32 and sizes in the local header are zero
+        let (crc32, compressed_size, uncompressed_size) = if self.using_data_descriptor {
+            (0, 0, 0)
+        } else {
+            (self.crc32, self.compressed_size, self.uncompressed_size)
+        };
+        let compressed_size: u32 = self.clamp_size_field(compressed_size);
+        let uncompressed_size: u32 = self.clamp_size_field(uncompressed_size);
 
         let extra_block_len: usize = self
-            .zip64_extra_field_block()
+            .local_zip64_extra_field_block()
             .map(|block| block.full_size())
             .unwrap_or(0);
-        let extra_field_length: u16 = (self.extra_field_len() + extra_block_len)
-            .try_into()
-            .map_err(|_| ZipError::InvalidArchive("Extra data field is too large"))?;
+        let extra_field_length: u16 =
+            (self.extra_field_len() + self.local_extra_field_len() + extra_block_len)
+                .try_into()
+                .map_err(|_| ZipError::InvalidArchive("Extra data field is too large"))?;
 
         let last_modified_time = self
             .last_modified_time
@@ -795,7 +909,7 @@ impl ZipFileData {
             compression_method: self.compression_method.serialize_to_u16(),
             last_mod_time: last_modified_time.timepart(),
             last_mod_date: last_modified_time.datepart(),
-            crc32: self.crc32,
+            crc32,
             compressed_size,
             uncompressed_size,
             file_name_length: self.file_name_raw.len().try_into().unwrap(),
@@ -803,7 +917,11 @@ impl ZipFileData {
         })
     }
 
-    pub(crate) fn block(&self, zip64_extra_field_length: u16) -> ZipResult<ZipCentralEntryBlock> {
+    pub(crate) fn block(
+        &self,
+        zip64_extra_field_length: u16,
+        header_start: u64,
+    ) -> ZipResult<ZipCentralEntryBlock> {
         let extra_field_len: u16 = self.extra_field_len().try_into().unwrap();
         let central_extra_field_len: u16 = self.central_extra_field_len().try_into().unwrap();
         let last_modified_time = self
@@ -819,49 +937,55 @@ impl ZipFileData {
             last_mod_time: last_modified_time.timepart(),
             last_mod_date: last_modified_time.datepart(),
             crc32: self.crc32,
-            compressed_size: self
-                .compressed_size
-                .min(spec::ZIP64_BYTES_THR)
-                .try_into()
-                .unwrap(),
-            uncompressed_size: self
-                .uncompressed_size
-                .min(spec::ZIP64_BYTES_THR)
-                .try_into()
-                .unwrap(),
+            compressed_size: self.clamp_size_field(self.compressed_size),
+            uncompressed_size: self.clamp_size_field(self.uncompressed_size),
             file_name_length: self.file_name_raw.len().try_into().unwrap(),
             extra_field_length: zip64_extra_field_length
                 .checked_add(extra_field_len + central_extra_field_len)
                 .ok_or(ZipError::InvalidArchive(
                     "Extra field length in central directory exceeds 64KiB",
                 ))?,
-            file_comment_length: self.file_comment.as_bytes().len().try_into().unwrap(),
+            file_comment_length: self.file_comment.len().try_into().unwrap(),
             disk_number: 0,
             internal_file_attributes: 0,
             external_file_attributes: self.external_attributes,
-            offset: self
-                .header_start
-                .min(spec::ZIP64_BYTES_THR)
-                .try_into()
-                .unwrap(),
+            offset: header_start.min(spec::ZIP64_BYTES_THR).try_into().unwrap(),
+        })
+    }
+
+    /// ZIP64 extra field for the local header. This is present exactly when `large_file` is set,
+    /// and always holds both sizes, so that the space is reserved before the sizes are known.
+    pub(crate) fn local_zip64_extra_field_block(&self) -> Option<Zip64ExtraFieldBlock> {
+        if !self.large_file {
+            return None;
+        }
+        Some(Zip64ExtraFieldBlock {
+            magic: spec::ExtraFieldMagic::ZIP64_EXTRA_FIELD_TAG,
+            size: 2 * mem::size_of::<u64>() as u16,
+            uncompressed_size: Some(self.uncompressed_size),
+            compressed_size: Some(self.compressed_size),
+            header_start: None,
         })
     }
 
-    pub(crate) fn zip64_extra_field_block(&self) -> Option<Zip64ExtraFieldBlock> {
+    pub(crate) fn zip64_extra_field_block(
+        &self,
+        header_start: u64,
+    ) -> Option<Zip64ExtraFieldBlock> {
         let uncompressed_size: Option<u64> =
             if self.uncompressed_size >= spec::ZIP64_BYTES_THR || self.large_file {
-                Some(spec::ZIP64_BYTES_THR)
+                Some(self.uncompressed_size)
             } else {
                 None
             };
         let compressed_size: Option<u64> =
             if self.compressed_size >= spec::ZIP64_BYTES_THR || self.large_file {
-                Some(spec::ZIP64_BYTES_THR)
+                Some(self.compressed_size)
             } else {
                 None
             };
-        let header_start: Option<u64> = if self.header_start >= spec::ZIP64_BYTES_THR {
-            Some(spec::ZIP64_BYTES_THR)
+        let header_start: Option<u64> = if header_start >= spec::ZIP64_BYTES_THR {
+            Some(header_start)
         } else {
             None
         };
@@ -1036,13 +1160,75 @@ impl Zip64ExtraFieldBlock {
 ///
 /// According to the [specification](https://www.winzip.com/win/en/aes_info.html#winzip11) AE-2
 /// does not make use of the CRC check.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u16)]
 pub enum AesVendorVersion {
+    /// AE-1, which keeps the CRC-32 of the unencrypted data
     Ae1 = 0x0001,
+    /// AE-2, which stores a CRC-32 of zero, so only the authentication code protects the data
     Ae2 = 0x0002,
 }
 
+/// Compression level hint stored in bits 1 and 2 of the general purpose flags of Deflated and
+/// Deflate64 entries.
+///
+/// The hint is informational only: it isn't needed to decompress the entry, and writers aren't
+/// required to set it accurately.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
+pub enum DeflateLevelHint {
+    /// Normal compression (both bits clear).
+    Normal,
+    /// Maximum compression.
+    Maximum,
+    /// Fast compression.
+    Fast,
+    /// Super fast compression.
+    SuperFast,
+}
+
+impl DeflateLevelHint {
+    /// Decodes the hint from the general purpose flags of an entry using `compression_method`.
+    pub(crate) const fn from_flags(
+        compression_method: CompressionMethod,
+        flags: u16,
+    ) -> Option<Self> {
+        if !Self::applies_to(compression_method) {
+            return None;
+        }
+        Some(match (flags >> 1) & 0b11 {
+            0 => Self::Normal,
+            1 => Self::Maximum,
+            2 => Self::Fast,
+            _ => Self::SuperFast,
+        })
+    }
+
+    /// Chooses the hint for a compression level, following Info-ZIP.
+    pub(crate) const fn from_level(level: Option<i64>) -> Self {
+        match level {
+            Some(..=2) => Self::Fast,
+            Some(8..) => Self::Maximum,
+            _ => Self::Normal,
+        }
+    }
+
+    /// Whether entries using `compression_method` record the hint.
+    pub(crate) const fn applies_to(compression_method: CompressionMethod) -> bool {
+        matches!(compression_method.serialize_to_u16(), 8 | 9)
+    }
+
+    /// Encodes the hint as general purpose flag bits.
+    pub(crate) const fn to_flags(self) -> u16 {
+        (match self {
+            Self::Normal => 0,
+            Self::Maximum => 1,
+            Self::Fast => 2,
+            Self::SuperFast => 3,
+        }) << 1
+    }
+}
+
 /// AES variant used.
 #[derive(Copy, Clone, Debug)]
 #[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
@@ -1100,6 +1286,7 @@ mod test {
             is_utf8: true,
             compression_method: crate::compression::CompressionMethod::Stored,
             compression_level: None,
+            deflate_level_hint: None,
             last_modified_time: None,
             crc32: 0,
             compressed_size: 0,
@@ -1108,6 +1295,7 @@ mod test {
             file_name_raw: file_name.into_bytes().into_boxed_slice(),
             extra_field: None,
             central_extra_field: None,
+            local_extra_field: None,
             file_comment: String::with_capacity(0).into_boxed_str(),
             header_start: 0,
             extra_data_start: None,
@@ -1117,7 +1305,9 @@ mod test {
             large_file: false,
             aes_mode: None,
             aes_extra_data_start: 0,
+            version_needed_override: None,
             extra_fields: Vec::new(),
+            unlisted_extra_fields: Vec::new(),
         };
         assert_eq!(data.file_name_sanitized(), PathBuf::from("path/etc/passwd"));
     }
@@ -1140,6 +1330,24 @@ mod test {
         assert_eq!(dt.datepart(), 0b1111111_1100_11111);
     }
 
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    fn datetime_msdos_u32() {
+        use super::DateTime;
+        let dt = DateTime::from_date_and_time(2107, 12, 31, 23, 59, 58).unwrap();
+        let packed = dt.to_msdos_u32();
+        assert_eq!(packed, 0b1111111_1100_11111_10111_111011_11101);
+        assert_eq!(packed >> 16, dt.datepart() as u32);
+        assert_eq!(packed & 0xffff, dt.timepart() as u32);
+        assert_eq!(DateTime::from_msdos_u32(packed).unwrap(), dt);
+
+        let dt = DateTime::from_date_and_time(2018, 11, 17, 10, 38, 30).unwrap();
+        assert_eq!(DateTime::from_msdos_u32(dt.to_msdos_u32()).unwrap(), dt);
+        assert_eq!(DateTime::default().to_msdos_u32(), 0x0021_0000);
+        // Month 0 is invalid
+        assert!(DateTime::from_msdos_u32(0).is_err());
+    }
+
     #[test]
     fn datetime_equality() {
         use super::DateTime;
@@ -1374,6 +1582,42 @@ mod test {
         assert!(dt.to_time().is_err());
     }
 
+    #[test]
+    fn clamped() {
+        use super::DateTime;
+
+        // Below range
+        let dt = DateTime::clamped(1970, 0, 0, 0, 0, 0);
+        assert_eq!(
+            dt,
+            DateTime::from_date_and_time(1980, 1, 1, 0, 0, 0).unwrap()
+        );
+
+        // Above range
+        let dt = DateTime::clamped(2200, 13, 40, 24, 60, 61);
+        assert_eq!(
+            dt,
+            DateTime::from_date_and_time(2107, 12, 31, 23, 59, 58).unwrap()
+        );
+        let dt = DateTime::clamped(2023, 2, 31, 12, 0, 0);
+        assert_eq!(
+            dt,
+            DateTime::from_date_and_time(2023, 2, 28, 12, 0, 0).unwrap()
+        );
+        let dt = DateTime::clamped(2024, 2, 31, 12, 0, 0);
+        assert_eq!(
+            dt,
+            DateTime::from_date_and_time(2024, 2, 29, 12, 0, 0).unwrap()
+        );
+
+        // Valid
+        let dt = DateTime::clamped(2018, 11, 17, 10, 38, 30);
+        assert_eq!(
+            dt,
+            DateTime::from_date_and_time(2018, 11, 17, 10, 38, 30).unwrap()
+        );
+    }
+
     #[cfg(feature = "time")]
     #[test]
     fn time_at_january() {
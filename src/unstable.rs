@@ -9,6 +9,33 @@ use std::path::{Component, Path, MAIN_SEPARATOR};
 pub mod stream {
     pub use crate::read::stream::*;
 }
+/// End-of-central-directory records, for tools that build or repair archives at the byte level.
+///
+/// This API is explicitly unstable: the types may gain fields or change in any release.
+///
+/// ```
+/// use zip::unstable::spec::Zip32CentralDirectoryEnd;
+///
+/// let eocd = Zip32CentralDirectoryEnd {
+///     disk_number: 0,
+///     disk_with_central_directory: 0,
+///     number_of_files_on_this_disk: 1,
+///     number_of_files: 1,
+///     central_directory_size: 55,
+///     central_directory_offset: 39,
+///     zip_file_comment: b"comment".to_vec().into_boxed_slice(),
+/// };
+/// let mut bytes = Vec::new();
+/// eocd.clone().write(&mut bytes)?;
+/// assert_eq!(bytes.len(), 22 + 7);
+/// assert_eq!(Zip32CentralDirectoryEnd::parse(&mut bytes.as_slice())?, eocd);
+/// # Ok::<(), zip::result::ZipError>(())
+/// ```
+pub mod spec {
+    pub use crate::spec::{
+        Zip32CentralDirectoryEnd, Zip64CentralDirectoryEnd, Zip64CentralDirectoryEndLocator,
+    };
+}
 /// Types for creating ZIP archives.
 pub mod write {
     use crate::write::{FileOptionExtension, FileOptions};
@@ -26,6 +53,27 @@ pub mod write {
     }
 }
 
+/// Returns whether CRC-32 checksums, which are computed for every file read or written, use the
+/// CPU's dedicated instructions (SSE 4.1 and PCLMULQDQ on x86 and x86_64, or the CRC extension on
+/// aarch64) rather than a portable table-driven implementation. This is detected at runtime, with
+/// the same checks as `crc32fast`.
+pub fn crc32_is_hardware_accelerated() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::arch::is_x86_feature_detected!("pclmulqdq")
+            && std::arch::is_x86_feature_detected!("sse2")
+            && std::arch::is_x86_feature_detected!("sse4.1")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("crc")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
 /// Helper methods for writing unsigned integers in little-endian form.
 pub trait LittleEndianWriteExt: Write {
     fn write_u16_le(&mut self, input: u16) -> io::Result<()> {
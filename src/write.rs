@@ -4,14 +4,16 @@
 use crate::aes::AesWriter;
 use crate::compression::CompressionMethod;
 use crate::read::{
-    find_content, parse_single_extra_field, Config, ZipArchive, ZipFile, ZipFileReader,
+    detached_central_directory_to_files, find_content, parse_single_extra_field, Config,
+    ZipArchive, ZipFile, ZipFileReader,
 };
 use crate::result::{ZipError, ZipResult};
 use crate::spec::{self, FixedSizeBlock, Zip32CDEBlock};
 #[cfg(feature = "aes-crypto")]
 use crate::types::AesMode;
 use crate::types::{
-    ffi, AesVendorVersion, DateTime, ZipFileData, ZipLocalEntryBlock, ZipRawValues, MIN_VERSION,
+    ffi, AesVendorVersion, DateTime, DeflateLevelHint, System, ZipFileData, ZipLocalEntryBlock,
+    ZipRawValues, MIN_VERSION,
 };
 use crate::write::ffi::S_IFLNK;
 #[cfg(any(feature = "_deflate-any", feature = "bzip2", feature = "zstd",))]
@@ -19,8 +21,10 @@ use core::num::NonZeroU64;
 use crc32fast::Hasher;
 use indexmap::IndexMap;
 use std::borrow::ToOwned;
+use std::collections::HashMap;
 use std::default::Default;
 use std::fmt::{Debug, Formatter};
+use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::io::Cursor;
@@ -66,6 +70,18 @@ impl<W> Debug for MaybeEncrypted<W> {
     }
 }
 
+impl<W: Write> MaybeEncrypted<W> {
+    /// The writer that encrypted data goes to.
+    fn get_mut(&mut self) -> &mut W {
+        match self {
+            MaybeEncrypted::Unencrypted(w) => w,
+            #[cfg(feature = "aes-crypto")]
+            MaybeEncrypted::Aes(w) => w.get_mut(),
+            MaybeEncrypted::ZipCrypto(w) => &mut w.writer,
+        }
+    }
+}
+
 impl<W: Write> Write for MaybeEncrypted<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         match self {
@@ -124,6 +140,13 @@ impl<W: Write + Seek> Debug for GenericZipWriter<W> {
 // Put the struct declaration in a private module to convince rustdoc to display ZipWriter nicely
 pub(crate) mod zip_writer {
     use super::*;
+
+    /// Rearranges the files of a finished archive; see [`ZipWriter::collect_then_write_sorted`]
+    pub(super) type SortOnFinish<W> = fn(&mut ZipWriter<W>) -> ZipResult<()>;
+
+    /// Reports progress; see [`ZipWriter::set_progress_callback`]
+    pub(super) type ProgressCallback = dyn FnMut(WriteProgress<'_>) + Send + Sync;
+
     /// ZIP archive generator
     ///
     /// Handles the bookkeeping involved in building an archive, and provides an
@@ -160,14 +183,25 @@ pub(crate) mod zip_writer {
         pub(super) writing_raw: bool,
         pub(super) comment: Box<[u8]>,
         pub(super) flush_on_finish_file: bool,
+        pub(super) sync_on_finish_file: Option<fn(&W) -> io::Result<()>>,
+        pub(super) truncate_on_finish: Option<fn(&mut W, u64) -> io::Result<()>>,
+        pub(super) sort_on_finish: Option<SortOnFinish<W>>,
+        pub(super) known_crc32: Option<u32>,
+        pub(super) minimal_mode: bool,
+        pub(super) uncompressed_copy: Option<Vec<u8>>,
+        pub(super) pre_central_directory_block: Box<[u8]>,
+        pub(super) archive_offset: u64,
+        pub(super) streaming: bool,
+        pub(super) aes_vendor_version: Option<AesVendorVersion>,
+        pub(super) progress_callback: Option<Box<ProgressCallback>>,
     }
 
     impl<W: Write + Seek> Debug for ZipWriter<W> {
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
             f.write_fmt(format_args!(
-                "ZipWriter {{files: {:?}, stats: {:?}, writing_to_file: {}, writing_raw: {}, comment: {:?}, flush_on_finish_file: {}}}",
+                "ZipWriter {{files: {:?}, stats: {:?}, writing_to_file: {}, writing_raw: {}, comment: {:?}, flush_on_finish_file: {}, fsync_on_finish_file: {}}}",
                 self.files, self.stats, self.writing_to_file, self.writing_raw,
-                self.comment, self.flush_on_finish_file))
+                self.comment, self.flush_on_finish_file, self.sync_on_finish_file.is_some()))
         }
     }
 }
@@ -203,6 +237,8 @@ mod sealed {
         fn extra_data(&self) -> Option<&Arc<Vec<u8>>>;
         /// Central Extra Data
         fn central_extra_data(&self) -> Option<&Arc<Vec<u8>>>;
+        /// Local Extra Data
+        fn local_extra_data(&self) -> Option<&Arc<Vec<u8>>>;
     }
     impl Sealed for () {}
     impl FileOptionExtension for () {
@@ -212,6 +248,9 @@ mod sealed {
         fn central_extra_data(&self) -> Option<&Arc<Vec<u8>>> {
             None
         }
+        fn local_extra_data(&self) -> Option<&Arc<Vec<u8>>> {
+            None
+        }
     }
     impl Sealed for ExtendedFileOptions {}
 
@@ -222,6 +261,9 @@ mod sealed {
         fn central_extra_data(&self) -> Option<&Arc<Vec<u8>>> {
             Some(&self.central_extra_data)
         }
+        fn local_extra_data(&self) -> Option<&Arc<Vec<u8>>> {
+            Some(&self.local_extra_data)
+        }
     }
 }
 
@@ -253,6 +295,20 @@ impl<'a> arbitrary::Arbitrary<'a> for EncryptWith<'a> {
     }
 }
 
+/// Where the CRC-32 and sizes of a file are recorded besides the central directory, which always
+/// holds them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LocalHeaderSizes {
+    /// The local header is written with placeholder values, and the real ones are written over
+    /// them once the file is finished.
+    #[default]
+    BackPatch,
+    /// The local header's CRC-32 and sizes are left as zero, and bit 3 of its general purpose
+    /// flags is set to say that they're in a data descriptor following the file's data instead,
+    /// as a writer that can't seek would do. The local header is never rewritten.
+    DataDescriptorOnly,
+}
+
 /// Metadata for a file to be written
 #[derive(Clone, Debug, Copy)]
 pub struct FileOptions<'k, T: FileOptionExtension> {
@@ -264,8 +320,17 @@ pub struct FileOptions<'k, T: FileOptionExtension> {
     pub(crate) encrypt_with: Option<EncryptWith<'k>>,
     pub(crate) extended_options: T,
     pub(crate) alignment: u16,
+    pub(crate) force_utf8: bool,
+    pub(crate) local_header_sizes: LocalHeaderSizes,
+    pub(crate) deflate_level_hint: Option<DeflateLevelHint>,
+    pub(crate) version_needed: Option<u16>,
+    pub(crate) system: System,
     #[cfg(feature = "deflate-zopfli")]
     pub(super) zopfli_buffer_size: Option<usize>,
+    #[cfg(feature = "zstd")]
+    pub(super) zstd_window_log: Option<u32>,
+    #[cfg(feature = "aes-crypto")]
+    pub(super) aes_vendor_version: Option<AesVendorVersion>,
 }
 /// Simple File Options. Can be copied and good for simple writing zip files
 pub type SimpleFileOptions = FileOptions<'static, ()>;
@@ -276,26 +341,80 @@ pub type FullFileOptions<'k> = FileOptions<'k, ExtendedFileOptions>;
 pub struct ExtendedFileOptions {
     extra_data: Arc<Vec<u8>>,
     central_extra_data: Arc<Vec<u8>>,
+    local_extra_data: Arc<Vec<u8>>,
+}
+
+/// Which headers of an entry an extra field added with [`ExtendedFileOptions::add_extra_field`]
+/// is written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExtraFieldLocation {
+    /// Only the local header, which precedes the entry's data
+    LocalOnly,
+    /// Only the entry's header in the central directory
+    CentralOnly,
+    /// Both headers
+    Both,
 }
 
 impl ExtendedFileOptions {
     /// Adds an extra data field, unless we detect that it's invalid.
+    ///
+    /// Unless `central_only` is set, the field is written to the local header as well, where it
+    /// sits between the file name and the file's data. This is the place for opaque per-entry
+    /// metadata: bytes written after the local header without an extra field to contain them
+    /// would be read as the start of the file's data by every ZIP reader.
     pub fn add_extra_data(
         &mut self,
         header_id: u16,
         data: Box<[u8]>,
         central_only: bool,
+    ) -> ZipResult<()> {
+        let location = if central_only {
+            ExtraFieldLocation::CentralOnly
+        } else {
+            ExtraFieldLocation::Both
+        };
+        self.add_extra_data_at(header_id, data, location)
+    }
+
+    /// Adds an extra field with the given header ID to the headers chosen by `location`, unless
+    /// we detect that it's invalid.
+    ///
+    /// The IDs of the fields that this crate writes itself are rejected, whatever the enabled
+    /// features: ZIP64 (`0x0001`), AES (`0x9901`) and the Unicode path and comment fields
+    /// (`0x7075` and `0x6375`).
+    pub fn add_extra_field(
+        &mut self,
+        header_id: u16,
+        data: &[u8],
+        location: ExtraFieldLocation,
+    ) -> ZipResult<()> {
+        if matches!(header_id, 0x0001 | 0x6375 | 0x7075 | 0x9901) {
+            return Err(InvalidArchive(
+                "Extra field ID is reserved for fields written by the zip crate",
+            ));
+        }
+        self.add_extra_data_at(header_id, data.into(), location)
+    }
+
+    fn add_extra_data_at(
+        &mut self,
+        header_id: u16,
+        data: Box<[u8]>,
+        location: ExtraFieldLocation,
     ) -> ZipResult<()> {
         let len = data.len() + 4;
-        if self.extra_data.len() + self.central_extra_data.len() + len > u16::MAX as usize {
+        if self.extra_data.len() + self.central_extra_data.len() + self.local_extra_data.len() + len
+            > u16::MAX as usize
+        {
             Err(InvalidArchive(
                 "Extra data field would be longer than allowed",
             ))
         } else {
-            let field = if central_only {
-                &mut self.central_extra_data
-            } else {
-                &mut self.extra_data
+            let field = match location {
+                ExtraFieldLocation::LocalOnly => &mut self.local_extra_data,
+                ExtraFieldLocation::CentralOnly => &mut self.central_extra_data,
+                ExtraFieldLocation::Both => &mut self.extra_data,
             };
             let vec = Arc::get_mut(field);
             let vec = match vec {
@@ -347,10 +466,7 @@ impl ExtendedFileOptions {
             {
                 use crate::unstable::LittleEndianReadExt;
                 let header_id = data.read_u16_le()?;
-                if EXTRA_FIELD_MAPPING
-                    .iter()
-                    .any(|&mapped| mapped == header_id)
-                {
+                if EXTRA_FIELD_MAPPING.contains(&header_id) {
                     return Err(ZipError::Io(io::Error::new(
                         io::ErrorKind::Other,
                         format!(
@@ -369,8 +485,8 @@ impl ExtendedFileOptions {
 
 impl Debug for ExtendedFileOptions {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        f.write_fmt(format_args!("ExtendedFileOptions {{extra_data: vec!{:?}.into(), central_extra_data: vec!{:?}.into()}}",
-        self.extra_data, self.central_extra_data))
+        f.write_fmt(format_args!("ExtendedFileOptions {{extra_data: vec!{:?}.into(), central_extra_data: vec!{:?}.into(), local_extra_data: vec!{:?}.into()}}",
+        self.extra_data, self.central_extra_data, self.local_extra_data))
     }
 }
 
@@ -389,6 +505,13 @@ impl<'a> arbitrary::Arbitrary<'a> for FileOptions<'a, ExtendedFileOptions> {
             large_file: bool::arbitrary(u)?,
             encrypt_with: Option::<EncryptWith>::arbitrary(u)?,
             alignment: u16::arbitrary(u)?,
+            force_utf8: bool::arbitrary(u)?,
+            local_header_sizes: if bool::arbitrary(u)? {
+                LocalHeaderSizes::DataDescriptorOnly
+            } else {
+                LocalHeaderSizes::BackPatch
+            },
+            deflate_level_hint: Option::<DeflateLevelHint>::arbitrary(u)?,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
             ..Default::default()
@@ -429,12 +552,15 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
     ///
     /// `None` value specifies default compression level.
     ///
-    /// Range of values depends on compression method:
-    /// * `Deflated`: 10 - 264 for Zopfli, 0 - 9 for other encoders. Default is 24 if Zopfli is the
-    ///   only encoder, or 6 otherwise.
-    /// * `Bzip2`: 0 - 9. Default is 6
-    /// * `Zstd`: -7 - 22, with zero being mapped to default level. Default is 3
-    /// * others: only `None` is allowed
+    /// The supported levels depend on the compression method, and are listed at
+    /// [`CompressionMethod::clamp_level`], which finds the closest supported level to any other;
+    /// [`ZipWriter::start_file`] fails for a level the method doesn't support. Methods that don't
+    /// take a level only allow `None`.
+    ///
+    /// The defaults are:
+    /// * `Deflated`: 24 if Zopfli is the only encoder, or 6 otherwise
+    /// * `Bzip2`: 6
+    /// * `Zstd`: 3, which zero is also mapped to
     #[must_use]
     pub const fn compression_level(mut self, level: Option<i64>) -> Self {
         self.compression_level = level;
@@ -466,17 +592,81 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
-    /// Set whether the new file's compressed and uncompressed size is less than 4 GiB.
+    /// Set whether the new file's compressed or uncompressed size may reach 4 GiB.
     ///
     /// If set to `false` and the file exceeds the limit, an I/O error is thrown and the file is
-    /// aborted. If set to `true`, readers will require ZIP64 support and if the file does not
-    /// exceed the limit, 20 B are wasted. The default is `false`.
+    /// aborted. If set to `true`, a ZIP64 extra field is always written to the local and central
+    /// headers, whatever the file's actual size turns out to be, and the 32-bit size fields are
+    /// set to `0xFFFFFFFF`. Readers will then require ZIP64 support, and if the file does not
+    /// exceed the limit, 20 B are wasted in each header. Use this when the size isn't known in
+    /// advance, since the local header can't be grown after the data is written. The default is
+    /// `false`.
     #[must_use]
     pub const fn large_file(mut self, large: bool) -> Self {
         self.large_file = large;
         self
     }
 
+    /// Set whether to always mark the new file's name as UTF-8.
+    ///
+    /// By default, the language encoding flag (bit 11 of the general purpose flags) is only set
+    /// when the name or comment contains non-ASCII characters, since pure ASCII reads the same
+    /// as CP437. Setting this to `true` sets the flag unconditionally, for consumers that
+    /// require it. The default is `false`.
+    #[must_use]
+    pub const fn force_utf8(mut self, force_utf8: bool) -> Self {
+        self.force_utf8 = force_utf8;
+        self
+    }
+
+    /// Set where the new file's CRC-32 and sizes are recorded besides the central directory.
+    ///
+    /// Even when the output is seekable, some consumers expect the local header to either hold
+    /// the real values or follow the streaming layout with a data descriptor, so this lets them
+    /// be chosen. The default is [`LocalHeaderSizes::BackPatch`].
+    #[must_use]
+    pub const fn local_header_sizes(mut self, local_header_sizes: LocalHeaderSizes) -> Self {
+        self.local_header_sizes = local_header_sizes;
+        self
+    }
+
+    /// Set the compression level hint recorded in the general purpose flags of a Deflated file.
+    ///
+    /// When `None`, the hint is derived from the compression level: levels up to 2 are recorded
+    /// as [`DeflateLevelHint::Fast`], levels from 8 up as [`DeflateLevelHint::Maximum`] and the
+    /// rest as [`DeflateLevelHint::Normal`]. The hint isn't recorded for other compression methods.
+    #[must_use]
+    pub const fn deflate_level_hint(
+        mut self,
+        deflate_level_hint: Option<DeflateLevelHint>,
+    ) -> Self {
+        self.deflate_level_hint = deflate_level_hint;
+        self
+    }
+
+    /// Set the "version needed to extract" written in the new file's local and central headers,
+    /// such as 20 for version 2.0, instead of the one computed from the features it uses.
+    ///
+    /// This is for consumers that expect a particular value. A version lower than the file's
+    /// features need is written as given, but readers that check it may then misread the file or
+    /// refuse to extract it. The "version made by" is set to match, as it is for computed values.
+    #[must_use]
+    pub const fn version_needed(mut self, version_needed: u16) -> Self {
+        self.version_needed = Some(version_needed);
+        self
+    }
+
+    /// Set the host system recorded for the new file.
+    ///
+    /// This determines how readers interpret its permissions: with [`System::Unix`] they're
+    /// stored as a Unix mode, and with [`System::Dos`] only the directory and read-only MS-DOS
+    /// attributes are stored. The default is [`System::Unix`].
+    #[must_use]
+    pub const fn system(mut self, system: System) -> Self {
+        self.system = system;
+        self
+    }
+
     pub(crate) fn with_deprecated_encryption(self, password: &[u8]) -> FileOptions<'static, T> {
         FileOptions {
             encrypt_with: Some(EncryptWith::ZipCrypto(
@@ -496,6 +686,19 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         }
     }
 
+    /// Set the AES vendor version to encrypt with, when encrypting with
+    /// [`FileOptions::with_aes_encryption`]. AE-1 keeps the file's CRC-32, whereas AE-2 stores
+    /// zero in its place.
+    ///
+    /// By default, AE-2 is used for files shorter than 20 bytes, whose CRC-32 could be used to
+    /// reconstruct their contents, and AE-1 otherwise, as WinZip does.
+    #[must_use]
+    #[cfg(feature = "aes-crypto")]
+    pub const fn aes_vendor_version(mut self, vendor_version: AesVendorVersion) -> Self {
+        self.aes_vendor_version = Some(vendor_version);
+        self
+    }
+
     /// Sets the size of the buffer used to hold the next block that Zopfli will compress. The
     /// larger the buffer, the more effective the compression, but the more memory is required.
     /// A value of `None` indicates no buffer, which is recommended only when all non-empty writes
@@ -507,6 +710,27 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Compresses the file with Zstandard at the given level, and optionally with a window of
+    /// `2^window_log` bytes, which is how far back it can find matches. A larger window improves
+    /// the ratio of large files, but the decompressor needs as much memory: zstd's default
+    /// decompression settings, which this crate's reader uses, reject windows larger than
+    /// `2^27` bytes. Without `window_log`, zstd picks the window from the level.
+    ///
+    /// Fails if the level or window log is outside the range zstd supports.
+    #[cfg(feature = "zstd")]
+    pub fn zstd_parameters(mut self, level: i32, window_log: Option<u32>) -> ZipResult<Self> {
+        if !zstd::compression_level_range().contains(&level) {
+            return Err(InvalidArchive("Unsupported Zstandard compression level"));
+        }
+        if window_log.is_some_and(|log| !ZSTD_WINDOW_LOG_RANGE.contains(&log)) {
+            return Err(InvalidArchive("Unsupported Zstandard window log"));
+        }
+        self.compression_method = CompressionMethod::Zstd;
+        self.compression_level = Some(level.into());
+        self.zstd_window_log = window_log;
+        Ok(self)
+    }
+
     /// Returns the compression level currently set.
     pub const fn get_compression_level(&self) -> Option<i64> {
         self.compression_level
@@ -530,15 +754,30 @@ impl<'k> FileOptions<'k, ExtendedFileOptions> {
             .add_extra_data(header_id, data, central_only)
     }
 
+    /// Adds an extra field to the headers chosen by `location`; see
+    /// [`ExtendedFileOptions::add_extra_field`].
+    pub fn add_extra_field(
+        &mut self,
+        header_id: u16,
+        data: &[u8],
+        location: ExtraFieldLocation,
+    ) -> ZipResult<()> {
+        self.extended_options
+            .add_extra_field(header_id, data, location)
+    }
+
     /// Removes the extra data fields.
     #[must_use]
     pub fn clear_extra_data(mut self) -> Self {
-        if self.extended_options.extra_data.len() > 0 {
+        if !self.extended_options.extra_data.is_empty() {
             self.extended_options.extra_data = Arc::new(vec![]);
         }
-        if self.extended_options.central_extra_data.len() > 0 {
+        if !self.extended_options.central_extra_data.is_empty() {
             self.extended_options.central_extra_data = Arc::new(vec![]);
         }
+        if !self.extended_options.local_extra_data.is_empty() {
+            self.extended_options.local_extra_data = Arc::new(vec![]);
+        }
         self
     }
 }
@@ -554,8 +793,17 @@ impl<'k, T: FileOptionExtension> Default for FileOptions<'k, T> {
             encrypt_with: None,
             extended_options: T::default(),
             alignment: 1,
+            force_utf8: false,
+            local_header_sizes: LocalHeaderSizes::BackPatch,
+            deflate_level_hint: None,
+            version_needed: None,
+            system: System::Unix,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: Some(1 << 15),
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
+            #[cfg(feature = "aes-crypto")]
+            aes_vendor_version: None,
         }
     }
 }
@@ -575,7 +823,14 @@ impl<W: Write + Seek> Write for ZipWriter<W> {
             Some(ref mut w) => {
                 let write_result = w.write(buf);
                 if let Ok(count) = write_result {
-                    self.stats.update(&buf[0..count]);
+                    if self.known_crc32.is_none() || cfg!(debug_assertions) {
+                        self.stats.update(&buf[0..count]);
+                    } else {
+                        self.stats.bytes_written += count as u64;
+                    }
+                    if let Some(copy) = &mut self.uncompressed_copy {
+                        copy.extend_from_slice(&buf[0..count]);
+                    }
                     if self.stats.bytes_written > spec::ZIP64_BYTES_THR
                         && !self.files.last_mut().unwrap().1.large_file
                     {
@@ -585,6 +840,7 @@ impl<W: Write + Seek> Write for ZipWriter<W> {
                             "Large file option has not been set",
                         ));
                     }
+                    self.report_progress();
                 }
                 write_result
             }
@@ -617,6 +873,14 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
     /// Initializes the archive from an existing ZIP archive, making it ready for append.
     ///
     /// This uses a default configuration to initially read the archive.
+    ///
+    /// Any bytes after the archive's end-of-central-directory record, such as padding added by
+    /// some tools, are discarded: new files and the new central directory are written over the
+    /// old central directory and everything after it. If the finished archive is shorter than the
+    /// original stream, the leftover bytes are removed if truncation was enabled with
+    /// `set_truncate_on_finish`, which is available for [`File`] and [`Cursor<Vec<u8>>`].
+    /// Otherwise the central directory is moved to the end of the stream, leaving them as unused
+    /// space before it.
     pub fn new_append(readwriter: A) -> ZipResult<ZipWriter<A>> {
         Self::new_append_with_config(Default::default(), readwriter)
     }
@@ -635,12 +899,143 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 comment: footer.zip_file_comment,
                 writing_raw: true, // avoid recomputing the last file's header
                 flush_on_finish_file: false,
+                sync_on_finish_file: None,
+                truncate_on_finish: None,
+                sort_on_finish: None,
+                known_crc32: None,
+                minimal_mode: false,
+                uncompressed_copy: None,
+                pre_central_directory_block: Box::new([]),
+                archive_offset: 0,
+                streaming: false,
+                aes_vendor_version: None,
+                progress_callback: None,
             })
         } else {
             Err(InvalidArchive("No central-directory end header found"))
         }
     }
 
+    /// Resumes writing an archive that was interrupted before it was finished, from a snapshot of
+    /// the files it contained that was taken with [`ZipWriter::export_index`].
+    ///
+    /// The snapshot is trusted to describe the stream, so the files it lists aren't read, except
+    /// for their local headers. The stream must be at least as long as it was when the snapshot
+    /// was taken. Anything written after that, such as a file that was being written when the
+    /// build was interrupted, is overwritten by the files added next and the central directory.
+    pub fn new_append_with_index(
+        mut readwriter: A,
+        snapshot: IndexSnapshot,
+    ) -> ZipResult<ZipWriter<A>> {
+        let len = readwriter.seek(SeekFrom::End(0))?;
+        if len < snapshot.end {
+            return Err(InvalidArchive(
+                "Archive is shorter than when its index snapshot was taken",
+            ));
+        }
+        let files = detached_central_directory_to_files(
+            &snapshot.central_directory,
+            snapshot.number_of_files,
+            snapshot.archive_offset,
+            &mut readwriter,
+        )?;
+        readwriter.seek(SeekFrom::Start(snapshot.end))?;
+        Ok(ZipWriter {
+            inner: Storer(MaybeEncrypted::Unencrypted(readwriter)),
+            files,
+            stats: Default::default(),
+            writing_to_file: false,
+            comment: snapshot.comment,
+            writing_raw: true,
+            flush_on_finish_file: false,
+            sync_on_finish_file: None,
+            truncate_on_finish: None,
+            sort_on_finish: None,
+            known_crc32: None,
+            minimal_mode: false,
+            uncompressed_copy: None,
+            pre_central_directory_block: snapshot.pre_central_directory_block,
+            archive_offset: snapshot.archive_offset,
+            streaming: false,
+            aes_vendor_version: None,
+            progress_callback: None,
+        })
+    }
+
+    /// Makes the physical order of the files, as well as their order in the central directory,
+    /// sorted by name when the archive is finished, regardless of the order in which they were
+    /// added. Together with fixed modification times, this makes the archive reproducible.
+    ///
+    /// Files are still written as they're added, and rearranged when the archive is finished. To
+    /// do that, everything from the first file's local header to the end of the last file is read
+    /// back: into memory if it's at most 64 MiB long, and otherwise into a temporary file in
+    /// [`std::env::temp_dir`], which is deleted afterwards. It's then written again in sorted
+    /// order, so finishing takes about as much I/O as writing all the files once more. Reading
+    /// the files back is why this needs a writer that also implements [`Read`].
+    #[must_use]
+    pub fn collect_then_write_sorted(mut self) -> Self {
+        self.sort_on_finish = Some(|writer| writer.write_sorted(SORT_IN_MEMORY_LIMIT));
+        self
+    }
+
+    fn write_sorted(&mut self, in_memory_limit: u64) -> ZipResult<()> {
+        self.files.sort_keys();
+        // Shallow copies share a local header, which is placed by the first name that uses it
+        let mut extents: Vec<(u64, u64)> = Vec::new();
+        let mut extent_indices: HashMap<u64, usize> = HashMap::new();
+        for (_, file) in self.files.iter() {
+            extent_indices.entry(file.header_start).or_insert_with(|| {
+                extents.push((file.header_start, 0));
+                extents.len() - 1
+            });
+        }
+        let Some(region_start) = extents.iter().map(|(start, _)| *start).min() else {
+            return Ok(());
+        };
+        let writer = self.inner.get_plain();
+        let region_end = writer.stream_position()?;
+        let mut starts: Vec<u64> = extents.iter().map(|(start, _)| *start).collect();
+        starts.sort_unstable();
+        for (start, end) in extents.iter_mut() {
+            let next = starts.partition_point(|other| other <= start);
+            *end = starts.get(next).copied().unwrap_or(region_end);
+        }
+
+        writer.seek(SeekFrom::Start(region_start))?;
+        let mut region = writer.take(region_end - region_start);
+        let mut new_starts = Vec::with_capacity(extents.len());
+        if region_end - region_start <= in_memory_limit {
+            let mut buffer = Vec::new();
+            region.read_to_end(&mut buffer)?;
+            let mut buffer = Cursor::new(buffer);
+            copy_extents(&mut buffer, region_start, &extents, writer, &mut new_starts)?;
+        } else {
+            let mut buffer = TempFile::new()?;
+            io::copy(&mut region, &mut buffer.file)?;
+            copy_extents(
+                &mut buffer.file,
+                region_start,
+                &extents,
+                writer,
+                &mut new_starts,
+            )?;
+        }
+        debug_assert_eq!(writer.stream_position()?, region_end);
+
+        for (_, file) in self.files.iter_mut() {
+            let index = extent_indices[&file.header_start];
+            let old_start = file.header_start;
+            let new_start = new_starts[index];
+            let moved = |offset: u64| offset - old_start + new_start;
+            file.header_start = new_start;
+            file.extra_data_start = file.extra_data_start.map(moved);
+            if let Some(data_start) = file.data_start.take() {
+                file.data_start.get_or_init(|| moved(data_start));
+            }
+        }
+        Ok(())
+    }
+
     /// `flush_on_finish_file` is designed to support a streaming `inner` that may unload flushed
     /// bytes. It flushes a file's header and body once it starts writing another file. A ZipWriter
     /// will not try to seek back into where a previous file was written unless
@@ -693,7 +1088,10 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
         self.inner
             .get_plain()
             .seek(SeekFrom::Start(write_position))?;
-        if src_data.extra_field.is_some() || src_data.central_extra_field.is_some() {
+        if src_data.extra_field.is_some()
+            || src_data.central_extra_field.is_some()
+            || src_data.local_extra_field.is_some()
+        {
             let mut options = FileOptions::<ExtendedFileOptions> {
                 compression_method: src_data.compression_method,
                 compression_level: src_data.compression_level,
@@ -706,10 +1104,20 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 extended_options: ExtendedFileOptions {
                     extra_data: src_data.extra_field.clone().unwrap_or_default(),
                     central_extra_data: src_data.central_extra_field.clone().unwrap_or_default(),
+                    local_extra_data: src_data.local_extra_field.clone().unwrap_or_default(),
                 },
                 alignment: 1,
+                force_utf8: src_data.is_utf8,
+                local_header_sizes: LocalHeaderSizes::BackPatch,
+                deflate_level_hint: src_data.deflate_level_hint,
+                version_needed: None,
+                system: src_data.system,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                #[cfg(feature = "zstd")]
+                zstd_window_log: None,
+                #[cfg(feature = "aes-crypto")]
+                aes_vendor_version: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
@@ -728,8 +1136,17 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 encrypt_with: None,
                 extended_options: (),
                 alignment: 1,
+                force_utf8: src_data.is_utf8,
+                local_header_sizes: LocalHeaderSizes::BackPatch,
+                deflate_level_hint: src_data.deflate_level_hint,
+                version_needed: None,
+                system: src_data.system,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                #[cfg(feature = "zstd")]
+                zstd_window_log: None,
+                #[cfg(feature = "aes-crypto")]
+                aes_vendor_version: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
@@ -786,10 +1203,13 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
     ///```
     pub fn finish_into_readable(mut self) -> ZipResult<ZipArchive<A>> {
         let central_start = self.finalize()?;
-        let inner = mem::replace(&mut self.inner, Closed).unwrap();
+        let mut inner = mem::replace(&mut self.inner, Closed).unwrap();
         let comment = mem::take(&mut self.comment);
+        let eocd_start = inner.seek(SeekFrom::End(0))?
+            - (mem::size_of::<Zip32CDEBlock>() + comment.len()) as u64;
         let files = mem::take(&mut self.files);
-        let archive = ZipArchive::from_finalized_writer(files, comment, inner, central_start)?;
+        let archive =
+            ZipArchive::from_finalized_writer(files, comment, inner, central_start, eocd_start)?;
         Ok(archive)
     }
 }
@@ -809,14 +1229,132 @@ impl<W: Write + Seek> ZipWriter<W> {
             writing_raw: false,
             comment: Box::new([]),
             flush_on_finish_file: false,
+            sync_on_finish_file: None,
+            truncate_on_finish: None,
+            sort_on_finish: None,
+            known_crc32: None,
+            minimal_mode: false,
+            uncompressed_copy: None,
+            pre_central_directory_block: Box::new([]),
+            archive_offset: 0,
+            streaming: false,
+            aes_vendor_version: None,
+            progress_callback: None,
         }
     }
 
+    /// Makes the archive as small as possible, for tiny archives such as configuration bundles
+    /// where every byte counts.
+    ///
+    /// In minimal mode:
+    ///
+    /// * A file whose compressed data would be at least as long as its contents is stored instead.
+    ///   To allow this, the uncompressed contents of each compressed, unencrypted file are kept in
+    ///   memory until the file is finished, so this mode is meant for small files.
+    /// * Alignment requested with [`FileOptions::with_alignment`] is ignored, since it's
+    ///   implemented by padding the local header's extra field.
+    ///
+    /// Everything else is already minimal by default: the writer adds no extended timestamps or
+    /// other optional extra fields of its own, and each entry's "version made by" is the minimum
+    /// version needed to extract it. Extra data passed in [`FullFileOptions`] is still written,
+    /// since it was asked for explicitly, as is the ZIP64 extra field of files started with
+    /// [`FileOptions::large_file`].
+    #[must_use]
+    pub fn minimal_mode(mut self) -> Self {
+        self.minimal_mode = true;
+        self
+    }
+
+    /// Initializes the archive, after writing `prefix` (e.g. the stub of a self-extracting
+    /// executable) to `inner`.
+    ///
+    /// The offsets recorded in the archive are relative to the end of the prefix, so the archive
+    /// can also be read on its own, and [`ZipArchive::offset`] will report the prefix's length
+    /// when it's read with [`ArchiveOffset::Detect`].
+    ///
+    /// [`ArchiveOffset::Detect`]: crate::read::ArchiveOffset::Detect
+    pub fn new_with_prefix(mut inner: W, prefix: &[u8]) -> ZipResult<ZipWriter<W>> {
+        inner.write_all(prefix)?;
+        let archive_offset = inner.stream_position()?;
+        let mut writer = ZipWriter::new(inner);
+        writer.archive_offset = archive_offset;
+        Ok(writer)
+    }
+
     /// Returns true if a file is currently open for writing.
     pub const fn is_writing_file(&self) -> bool {
         self.writing_to_file && !self.inner.is_closed()
     }
 
+    /// Takes a snapshot of the files finished so far, along with the archive comment, from which
+    /// an interrupted build can be resumed using [`ZipWriter::new_append_with_index`]. A file
+    /// that is still being written isn't included.
+    ///
+    /// This fails if a file's central directory header can't be written, in which case finishing
+    /// the archive would fail too.
+    pub fn export_index(&self) -> ZipResult<IndexSnapshot> {
+        let finished = if self.writing_to_file {
+            self.files.len().saturating_sub(1)
+        } else {
+            self.files.len()
+        };
+        let mut end = self.archive_offset;
+        let mut central_directory = Vec::new();
+        for file in self.files.values().take(finished) {
+            let data_end = file.data_start() + file.compressed_size;
+            let data_descriptor_len = match file.using_data_descriptor {
+                false => 0,
+                true if file.large_file => 24,
+                true => 16,
+            };
+            end = end.max(data_end + data_descriptor_len);
+            write_central_directory_header(
+                &mut central_directory,
+                file,
+                file.header_start - self.archive_offset,
+            )?;
+        }
+        Ok(IndexSnapshot {
+            end,
+            archive_offset: self.archive_offset,
+            number_of_files: finished as u64,
+            central_directory,
+            comment: self.comment.clone(),
+            pre_central_directory_block: self.pre_central_directory_block.clone(),
+        })
+    }
+
+    /// Set a callback to be called after each write to the file being written, with the number
+    /// of bytes written to it so far and the number of bytes its compressed (and possibly
+    /// encrypted) data takes up in the output so far. It's called as often as the
+    /// [`Write`] implementation is, e.g. for each buffer when data is copied in with
+    /// [`io::copy`]. Compressors hold back some of their output until the file is finished, so
+    /// the output count may lag behind and doesn't include the final flush. The callback replaces
+    /// any set previously.
+    pub fn set_progress_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(WriteProgress<'_>) + Send + Sync + 'static,
+    {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    fn report_progress(&mut self) {
+        let Some(callback) = &mut self.progress_callback else {
+            return;
+        };
+        let Some(Ok(position)) = self.inner.bare_mut().map(|w| w.stream_position()) else {
+            return;
+        };
+        let Some((name, _)) = self.files.last() else {
+            return;
+        };
+        callback(WriteProgress {
+            name,
+            input_bytes: self.stats.bytes_written,
+            output_bytes: position.saturating_sub(self.stats.start),
+        });
+    }
+
     /// Set ZIP archive comment.
     pub fn set_comment<S>(&mut self, comment: S)
     where
@@ -833,6 +1371,16 @@ impl<W: Write + Seek> ZipWriter<W> {
         self.comment = comment;
     }
 
+    /// Set an opaque block of bytes to write between the last entry's data and the central
+    /// directory when the archive is finished, replacing any block set previously.
+    ///
+    /// Readers skip over this space, since the central directory records its own offset and
+    /// those of the entries. This is how e.g. the APK Signature Scheme v2 embeds its signing
+    /// block.
+    pub fn insert_block_before_central_directory(&mut self, bytes: &[u8]) {
+        self.pre_central_directory_block = bytes.into();
+    }
+
     /// Get ZIP archive comment.
     pub fn get_comment(&mut self) -> Result<&str, Utf8Error> {
         from_utf8(self.get_raw_comment())
@@ -860,7 +1408,7 @@ impl<W: Write + Seek> ZipWriter<W> {
     fn start_entry<S, SToOwned, T: FileOptionExtension>(
         &mut self,
         name: S,
-        options: FileOptions<T>,
+        mut options: FileOptions<T>,
         raw_values: Option<ZipRawValues>,
     ) -> ZipResult<()>
     where
@@ -868,6 +1416,19 @@ impl<W: Write + Seek> ZipWriter<W> {
         SToOwned: Into<Box<str>>,
     {
         self.finish_file()?;
+        if self.streaming {
+            #[cfg(feature = "aes-crypto")]
+            if let Some(EncryptWith::Aes { .. }) = options.encrypt_with {
+                return Err(ZipError::Io(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "AES encryption needs a seekable writer",
+                )));
+            }
+            // The sizes of a raw copy are known up front, so its local header has them already
+            if raw_values.is_none() {
+                options.local_header_sizes = LocalHeaderSizes::DataDescriptorOnly;
+            }
+        }
 
         let raw_values = raw_values.unwrap_or(ZipRawValues {
             crc32: 0,
@@ -897,6 +1458,10 @@ impl<W: Write + Seek> ZipWriter<W> {
         }
         {
             let header_start = self.inner.get_plain().stream_position()?;
+            #[cfg(feature = "aes-crypto")]
+            {
+                self.aes_vendor_version = options.aes_vendor_version;
+            }
 
             let (compression_method, aes_mode) = match options.encrypt_with {
                 #[cfg(feature = "aes-crypto")]
@@ -918,23 +1483,19 @@ impl<W: Write + Seek> ZipWriter<W> {
                 &extra_data,
             );
             file.version_made_by = file.version_made_by.max(file.version_needed() as u8);
-            let block = file.local_block();
-            let index = self.insert_file_data(file)?;
-            let writer = self.inner.get_plain();
-            let result = block?.write(writer);
-            self.ok_or_abort_file(result)?;
-            let writer = self.inner.get_plain();
-            let file = &mut self.files[index];
-            // file name
-            writer.write_all(&file.file_name_raw)?;
-            let zip64_start = writer.stream_position()?;
-            if file.large_file {
-                write_local_zip64_extra_field(writer, file)?;
-            }
-            let header_end = writer.stream_position()?;
-            file.extra_data_start = Some(header_end);
-            let mut extra_data_end = header_end + extra_data.len() as u64;
             if options.alignment > 1 {
+                // The padding has to be known before the local header is written, since the
+                // header records the length of the extra field it's in
+                let zip64_extra_field_len = file
+                    .local_zip64_extra_field_block()
+                    .map(|block| block.full_size())
+                    .unwrap_or(0);
+                let extra_data_end = header_start
+                    + size_of::<ZipLocalEntryBlock>() as u64
+                    + file.file_name_raw.len() as u64
+                    + zip64_extra_field_len as u64
+                    + extra_data.len() as u64
+                    + file.local_extra_field_len() as u64;
                 let align = options.alignment as u64;
                 let unaligned_header_bytes = extra_data_end % align;
                 if unaligned_header_bytes != 0 {
@@ -951,8 +1512,25 @@ impl<W: Write + Seek> ZipWriter<W> {
                         0xa11e,
                         pad_body.into_boxed_slice(),
                     )?;
+                    file.extra_field = Some(Arc::new(extra_data.clone()));
                 }
             }
+            let block = file.local_block();
+            let index = self.insert_file_data(file)?;
+            let writer = self.inner.get_plain();
+            let result = block?.write(writer);
+            self.ok_or_abort_file(result)?;
+            let writer = self.inner.get_plain();
+            let file = &mut self.files[index];
+            // file name
+            writer.write_all(&file.file_name_raw)?;
+            let zip64_start = writer.stream_position()?;
+            if file.large_file {
+                write_local_zip64_extra_field(writer, file)?;
+            }
+            let header_end = writer.stream_position()?;
+            file.extra_data_start = Some(header_end);
+            let mut extra_data_end = header_end + extra_data.len() as u64;
             let extra_data_len = extra_data.len();
             if extra_data_len > 0 {
                 let result = (|| {
@@ -968,15 +1546,31 @@ impl<W: Write + Seek> ZipWriter<W> {
                     let _ = self.abort_file();
                     return Err(e);
                 }
-                debug_assert_eq!(extra_data_end % (options.alignment.max(1) as u64), 0);
-                self.stats.start = extra_data_end;
                 file.extra_field = Some(extra_data.into());
-            } else {
-                self.stats.start = extra_data_end;
             }
+            // The central directory has the same fields up to here, but not the local-only ones
+            let central_reserved = extra_data_end - zip64_start;
+            if let Some(data) = file
+                .local_extra_field
+                .clone()
+                .filter(|data| !data.is_empty())
+            {
+                let result = (|| {
+                    ExtendedFileOptions::validate_extra_data(&data, extra_data_end - zip64_start)?;
+                    writer.write_all(&data)?;
+                    extra_data_end = writer.stream_position()?;
+                    Ok(())
+                })();
+                if let Err(e) = result {
+                    let _ = self.abort_file();
+                    return Err(e);
+                }
+            }
+            debug_assert_eq!(extra_data_end % (options.alignment.max(1) as u64), 0);
+            self.stats.start = extra_data_end;
             if let Some(data) = central_extra_data {
                 let validation_result =
-                    ExtendedFileOptions::validate_extra_data(data, extra_data_end - zip64_start);
+                    ExtendedFileOptions::validate_extra_data(data, central_reserved);
                 if let Err(e) = validation_result {
                     let _ = self.abort_file();
                     return Err(e);
@@ -1036,10 +1630,13 @@ impl<W: Write + Seek> ZipWriter<W> {
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "zstd")]
+            None,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
         let writer = self.inner.get_plain();
+        let uncompressed_copy = self.uncompressed_copy.take();
 
         if !self.writing_raw {
             let file = match self.files.last_mut() {
@@ -1048,7 +1645,7 @@ impl<W: Write + Seek> ZipWriter<W> {
             };
             file.uncompressed_size = self.stats.bytes_written;
 
-            let file_end = writer.stream_position()?;
+            let mut file_end = writer.stream_position()?;
             debug_assert!(file_end >= self.stats.start);
             file.compressed_size = file_end - self.stats.start;
             let mut crc = true;
@@ -1058,26 +1655,62 @@ impl<W: Write + Seek> ZipWriter<W> {
                 // unencrypted contents.
                 //
                 // C.f. https://www.winzip.com/en/support/aes-encryption/#crc-faq
-                aes_mode.1 = if self.stats.bytes_written < 20 {
-                    crc = false;
-                    AesVendorVersion::Ae2
-                } else {
-                    AesVendorVersion::Ae1
+                //
+                // An explicit choice in the FileOptions takes precedence.
+                aes_mode.1 = match self.aes_vendor_version.take() {
+                    Some(vendor_version) => vendor_version,
+                    None if self.stats.bytes_written < 20 => AesVendorVersion::Ae2,
+                    None => AesVendorVersion::Ae1,
                 };
+                crc = aes_mode.1 == AesVendorVersion::Ae1;
             }
-            file.crc32 = if crc {
-                self.stats.hasher.clone().finalize()
-            } else {
-                0
+            file.crc32 = match (crc, self.known_crc32.take()) {
+                (false, _) => 0,
+                (true, None) => self.stats.hasher.clone().finalize(),
+                (true, Some(known_crc32)) => {
+                    if cfg!(debug_assertions) && known_crc32 != self.stats.hasher.clone().finalize()
+                    {
+                        let _ = self.abort_file();
+                        return Err(InvalidArchive(
+                            "CRC-32 given for the file doesn't match the data written",
+                        ));
+                    }
+                    known_crc32
+                }
             };
+            if let Some(contents) =
+                uncompressed_copy.filter(|_| file_end - self.stats.start >= file.uncompressed_size)
+            {
+                // Compression didn't help, so store the contents instead. The local header
+                // keeps its length, so the data still starts in the same place.
+                file.compression_method = Stored;
+                file.compression_level = None;
+                file.deflate_level_hint = None;
+                file.compressed_size = file.uncompressed_size;
+                writer.seek(SeekFrom::Start(file.header_start))?;
+                file.local_block()?.write(writer)?;
+                writer.seek(SeekFrom::Start(self.stats.start))?;
+                writer.write_all(&contents)?;
+                file_end = writer.stream_position()?;
+            }
             update_aes_extra_data(writer, file)?;
-            update_local_file_header(writer, file)?;
-            writer.seek(SeekFrom::Start(file_end))?;
+            if file.using_data_descriptor {
+                writer.seek(SeekFrom::Start(file_end))?;
+                write_data_descriptor(writer, file)?;
+            } else {
+                update_local_file_header(writer, file)?;
+                writer.seek(SeekFrom::Start(file_end))?;
+            }
         }
         if self.flush_on_finish_file {
             let result = writer.flush();
             self.ok_or_abort_file(result)?;
         }
+        if let Some(sync) = self.sync_on_finish_file {
+            let writer = self.inner.get_plain();
+            let result = writer.flush().and_then(|()| sync(writer));
+            self.ok_or_abort_file(result)?;
+        }
 
         self.writing_to_file = false;
         Ok(())
@@ -1104,12 +1737,17 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// Removes the file currently being written from the archive if there is one, or else removes
     /// the file most recently written.
     pub fn abort_file(&mut self) -> ZipResult<()> {
+        self.known_crc32 = None;
+        self.aes_vendor_version = None;
+        self.uncompressed_copy = None;
         let (_, last_file) = self.files.pop().ok_or(ZipError::FileNotFound)?;
         let make_plain_writer = self.inner.prepare_next_writer(
             Stored,
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "zstd")]
+            None,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
@@ -1123,7 +1761,7 @@ impl<W: Write + Seek> ZipWriter<W> {
                     .is_some_and(|start| start < last_file_start)
             }),
         };
-        if rewind_safe {
+        if rewind_safe && !self.streaming {
             self.inner
                 .get_plain()
                 .seek(SeekFrom::Start(last_file.header_start))?;
@@ -1136,6 +1774,9 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// same name as a file already in the archive.
     ///
     /// The data should be written using the [`Write`] implementation on this [`ZipWriter`]
+    ///
+    /// A name ending in `/` denotes a directory, so it's rejected; use [`ZipWriter::add_directory`]
+    /// for those instead.
     pub fn start_file<S, T: FileOptionExtension, SToOwned>(
         &mut self,
         name: S,
@@ -1145,17 +1786,59 @@ impl<W: Write + Seek> ZipWriter<W> {
         S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
         SToOwned: Into<Box<str>>,
     {
+        let name: Box<str> = name.into();
+        if name.ends_with('/') {
+            return Err(InvalidArchive(
+                "A file name can't end with '/'; use add_directory to add a directory",
+            ));
+        }
         Self::normalize_options(&mut options);
+        let keep_uncompressed_copy = self.minimal_mode
+            && !self.streaming
+            && options.compression_method != Stored
+            && options.encrypt_with.is_none();
+        if self.minimal_mode {
+            options.alignment = 1;
+        }
         let make_new_self = self.inner.prepare_next_writer(
             options.compression_method,
             options.compression_level,
             #[cfg(feature = "deflate-zopfli")]
             options.zopfli_buffer_size,
+            #[cfg(feature = "zstd")]
+            options.zstd_window_log,
         )?;
         self.start_entry(name, options, None)?;
         let result = self.inner.switch_to(make_new_self);
         self.ok_or_abort_file(result)?;
         self.writing_raw = false;
+        if keep_uncompressed_copy {
+            self.uncompressed_copy = Some(Vec::new());
+        }
+        Ok(())
+    }
+
+    /// Like [`ZipWriter::start_file`], but the CRC-32 of the file's contents is given rather than
+    /// computed from the data written. This saves hashing huge contents whose checksum is already
+    /// trusted, such as those from a content-addressed store. The contents are still compressed
+    /// and encrypted as the options specify.
+    ///
+    /// In debug builds, the data is hashed anyway, and finishing the file fails with
+    /// [`ZipError::InvalidArchive`] if its CRC-32 doesn't match `crc32`. Release builds write
+    /// `crc32` to the headers unchecked, so a wrong value produces an archive whose file fails
+    /// verification when it's read.
+    pub fn start_file_with_crc32<S, T: FileOptionExtension, SToOwned>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+        crc32: u32,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        self.start_file(name, options)?;
+        self.known_crc32 = Some(crc32);
         Ok(())
     }
 
@@ -1247,7 +1930,7 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// Add a new file using the already compressed data from a ZIP file being read and renames it, this
     /// allows faster copies of the `ZipFile` since there is no need to decompress and compress it again.
     /// Any `ZipFile` metadata is copied and not checked, for example the file CRC.
-
+    ///
     /// ```no_run
     /// use std::fs::File;
     /// use std::io::{Read, Seek, Write};
@@ -1281,7 +1964,8 @@ impl<W: Write + Seek> ZipWriter<W> {
                 file.last_modified()
                     .unwrap_or_else(DateTime::default_for_write),
             )
-            .compression_method(file.compression());
+            .compression_method(file.compression())
+            .deflate_level_hint(file.deflate_level_hint());
         if let Some(perms) = file.unix_mode() {
             options = options.unix_permissions(perms);
         }
@@ -1346,6 +2030,9 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// Add a directory entry.
     ///
     /// As directories have no content, you must not call [`ZipWriter::write`] before adding a new file.
+    ///
+    /// Adding a directory that the archive already has does nothing, except finishing any file
+    /// being written, so the first entry and its options are kept.
     pub fn add_directory<S, T: FileOptionExtension>(
         &mut self,
         name: S,
@@ -1367,6 +2054,13 @@ impl<W: Write + Seek> ZipWriter<W> {
             Some('/') | Some('\\') => name_as_string,
             _ => name_as_string + "/",
         };
+        if self
+            .files
+            .get(name_with_slash.as_str())
+            .is_some_and(ZipFileData::is_dir)
+        {
+            return self.finish_file();
+        }
 
         self.start_entry(name_with_slash, options, None)?;
         self.writing_to_file = false;
@@ -1397,9 +2091,58 @@ impl<W: Write + Seek> ZipWriter<W> {
         Ok(inner.unwrap())
     }
 
-    /// Add a symlink entry.
-    ///
-    /// The zip archive will contain an entry for path `name` which is a symlink to `target`.
+    /// Finish the last file and write all other zip-structures, like [`ZipWriter::finish`], and
+    /// also report where they were written, such as for recording the position of the central
+    /// directory in an external index.
+    pub fn finish_with_offsets(mut self) -> ZipResult<(W, FinishOffsets)> {
+        let central_start = self.finalize()?;
+        let total_len = self.inner.get_plain().stream_position()?;
+        let eocd_start = total_len - (size_of::<Zip32CDEBlock>() + self.comment.len()) as u64;
+        let inner = mem::replace(&mut self.inner, Closed);
+        let offsets = FinishOffsets {
+            central_start,
+            eocd_start,
+            total_len,
+        };
+        Ok((inner.unwrap(), offsets))
+    }
+
+    /// Finish the last file, and write the central directory and end-of-central-directory records
+    /// to `central_directory` instead of this writer's own output.
+    ///
+    /// This is for when the entries and the table of contents are transported separately, such
+    /// as when entries are uploaded as they're produced. The records are computed as if they
+    /// followed all the bytes written to this writer's output, including any prefix, so the
+    /// archive is valid once the two outputs are concatenated in that order. Nothing is written
+    /// to this writer's output after the data of the last file.
+    ///
+    /// Returns the inner writer.
+    pub fn finish_with_separate_central_directory<C: Write>(
+        mut self,
+        central_directory: &mut C,
+    ) -> ZipResult<W> {
+        self.finish_file()?;
+        if let Some(sort) = self.sort_on_finish.take() {
+            sort(&mut self)?;
+        }
+        let start = self.inner.get_plain().seek(SeekFrom::End(0))?;
+        write_central_and_footer_to(
+            central_directory,
+            start,
+            &self.files,
+            &self.pre_central_directory_block,
+            &self.comment,
+            self.archive_offset,
+        )?;
+        let inner = mem::replace(&mut self.inner, Closed);
+        Ok(inner.unwrap())
+    }
+
+    /// Add a symlink entry.
+    ///
+    /// The zip archive will contain an entry for path `name` which is a symlink to `target`.
+    /// Its Unix mode is `S_IFLNK` plus the permission bits from `options` (0o777 by default), and
+    /// it's always recorded as coming from a Unix host so that readers can recognize it.
     ///
     /// No validation or normalization of the paths is performed. For best results,
     /// callers should normalize `\` to `/` and ensure symlinks are relative to other
@@ -1420,10 +2163,10 @@ impl<W: Write + Seek> ZipWriter<W> {
         NToOwned: Into<Box<str>>,
         T: Into<Box<str>>,
     {
-        if options.permissions.is_none() {
-            options.permissions = Some(0o777);
-        }
-        *options.permissions.as_mut().unwrap() |= S_IFLNK;
+        // Any file type bits in the given permissions are replaced, and the mode is only
+        // meaningful to readers if the entry is marked as coming from a Unix host.
+        options.permissions = Some(options.permissions.unwrap_or(0o777) & 0o7777 | S_IFLNK);
+        options.system = System::Unix;
         // The symlink target is stored as file content. And compressing the target path
         // likely wastes space. So always store.
         options.compression_method = Stored;
@@ -1454,12 +2197,20 @@ impl<W: Write + Seek> ZipWriter<W> {
 
     fn finalize(&mut self) -> ZipResult<u64> {
         self.finish_file()?;
+        if let Some(sort) = self.sort_on_finish.take() {
+            sort(self)?;
+        }
 
         let mut central_start = self.write_central_and_footer()?;
         let writer = self.inner.get_plain();
         let footer_end = writer.stream_position()?;
         let file_end = writer.seek(SeekFrom::End(0))?;
-        if footer_end < file_end {
+        if let Some(truncate) = self.truncate_on_finish.filter(|_| footer_end < file_end) {
+            // Data from an aborted file or trailing bytes of an appended archive are past the end
+            // of the footer, and the stream can drop them.
+            truncate(writer, footer_end)?;
+            writer.seek(SeekFrom::Start(footer_end))?;
+        } else if footer_end < file_end {
             // Data from an aborted file is past the end of the footer.
 
             // Overwrite the magic so the footer is no longer valid.
@@ -1471,7 +2222,8 @@ impl<W: Write + Seek> ZipWriter<W> {
             writer.write_u32_le(0)?;
 
             // Rewrite the footer at the actual end.
-            let central_and_footer_size = footer_end - central_start;
+            let central_and_footer_size =
+                footer_end - central_start + self.pre_central_directory_block.len() as u64;
             writer.seek(SeekFrom::End(-(central_and_footer_size as i64)))?;
             central_start = self.write_central_and_footer()?;
         }
@@ -1481,53 +2233,15 @@ impl<W: Write + Seek> ZipWriter<W> {
 
     fn write_central_and_footer(&mut self) -> Result<u64, ZipError> {
         let writer = self.inner.get_plain();
-
-        let mut version_needed = MIN_VERSION as u16;
-        let central_start = writer.stream_position()?;
-        for file in self.files.values() {
-            write_central_directory_header(writer, file)?;
-            version_needed = version_needed.max(file.version_needed());
-        }
-        let central_size = writer.stream_position()? - central_start;
-
-        if self.files.len() > spec::ZIP64_ENTRY_THR
-            || central_size.max(central_start) > spec::ZIP64_BYTES_THR
-        {
-            let zip64_footer = spec::Zip64CentralDirectoryEnd {
-                version_made_by: version_needed,
-                version_needed_to_extract: version_needed,
-                disk_number: 0,
-                disk_with_central_directory: 0,
-                number_of_files_on_this_disk: self.files.len() as u64,
-                number_of_files: self.files.len() as u64,
-                central_directory_size: central_size,
-                central_directory_offset: central_start,
-            };
-
-            zip64_footer.write(writer)?;
-
-            let zip64_footer = spec::Zip64CentralDirectoryEndLocator {
-                disk_with_central_directory: 0,
-                end_of_central_directory_offset: central_start + central_size,
-                number_of_disks: 1,
-            };
-
-            zip64_footer.write(writer)?;
-        }
-
-        let number_of_files = self.files.len().min(spec::ZIP64_ENTRY_THR) as u16;
-        let footer = spec::Zip32CentralDirectoryEnd {
-            disk_number: 0,
-            disk_with_central_directory: 0,
-            zip_file_comment: self.comment.clone(),
-            number_of_files_on_this_disk: number_of_files,
-            number_of_files,
-            central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
-            central_directory_offset: central_start.min(spec::ZIP64_BYTES_THR) as u32,
-        };
-
-        footer.write(writer)?;
-        Ok(central_start)
+        let start = writer.stream_position()?;
+        write_central_and_footer_to(
+            writer,
+            start,
+            &self.files,
+            &self.pre_central_directory_block,
+            &self.comment,
+            self.archive_offset,
+        )
     }
 
     fn index_by_name(&self, name: &str) -> ZipResult<usize> {
@@ -1566,6 +2280,175 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 }
 
+impl ZipWriter<Cursor<Vec<u8>>> {
+    /// Initializes a ZipWriter that builds an archive in memory.
+    ///
+    /// Use [`ZipWriter::finish_to_vec`] to get the archive bytes once all files are written:
+    ///
+    /// ```
+    /// # fn main() -> Result<(), zip::result::ZipError> {
+    /// use std::io::Write;
+    /// use zip::{write::SimpleFileOptions, ZipWriter};
+    ///
+    /// let mut zip = ZipWriter::new_buffer();
+    /// zip.start_file("hello.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"Hello, World!")?;
+    /// let bytes: Vec<u8> = zip.finish_to_vec()?;
+    /// assert_eq!(bytes[..4], *b"PK\x03\x04");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_buffer() -> Self {
+        ZipWriter::new(Cursor::new(Vec::new()))
+    }
+
+    /// Finishes the archive and returns its bytes.
+    pub fn finish_to_vec(self) -> ZipResult<Vec<u8>> {
+        Ok(self.finish()?.into_inner())
+    }
+
+    /// When enabled, finishing the archive truncates the buffer to the end of the archive, which
+    /// drops data past it, such as trailing bytes of an archive opened with
+    /// [`ZipWriter::new_append`] or the data of an aborted last file.
+    ///
+    /// This setting is false by default.
+    pub fn set_truncate_on_finish(&mut self, truncate_on_finish: bool) {
+        self.truncate_on_finish = if truncate_on_finish {
+            Some(|cursor, len| {
+                cursor.get_mut().truncate(len as usize);
+                Ok(())
+            })
+        } else {
+            None
+        };
+    }
+}
+
+impl ZipWriter<File> {
+    /// When enabled, each file's header and body are flushed and then synced to disk with
+    /// [`File::sync_data`] once it's finished, i.e. when another file is started or the archive is
+    /// finished. Together with [`ZipWriter::finish`], this makes incremental archive building
+    /// crash-safe: after a power loss, every entry that was finished is on disk and can be
+    /// recovered, for example by [`crate::read::list_from_local_headers`], even though the
+    /// central directory was never written.
+    ///
+    /// Syncing waits for the storage device, so enabling this can make writing archives with many
+    /// small files much slower.
+    ///
+    /// This setting is false by default.
+    pub fn set_fsync_on_finish_file(&mut self, fsync_on_finish_file: bool) {
+        self.sync_on_finish_file = if fsync_on_finish_file {
+            Some(File::sync_data)
+        } else {
+            None
+        };
+    }
+
+    /// When enabled, finishing the archive truncates the file to the end of the archive with
+    /// [`File::set_len`], which drops data past it, such as trailing bytes of an archive opened
+    /// with [`ZipWriter::new_append`] or the data of an aborted last file.
+    ///
+    /// This setting is false by default.
+    pub fn set_truncate_on_finish(&mut self, truncate_on_finish: bool) {
+        self.truncate_on_finish = if truncate_on_finish {
+            Some(|file, len| file.set_len(len))
+        } else {
+            None
+        };
+    }
+}
+
+/// Where the structures written by [`ZipWriter::finish_with_offsets`] ended up. The offsets are
+/// from the start of the writer's output, so they include any data before the archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FinishOffsets {
+    /// Offset of the first central directory header. Any block inserted with
+    /// [`ZipWriter::insert_block_before_central_directory`] comes right before it.
+    pub central_start: u64,
+    /// Offset of the (ZIP32) end-of-central-directory record
+    pub eocd_start: u64,
+    /// Length of the output, up to the end of the archive comment
+    pub total_len: u64,
+}
+
+/// The progress of writing a file, as passed to the callback set with
+/// [`ZipWriter::set_progress_callback`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteProgress<'a> {
+    /// Name of the file being written
+    pub name: &'a str,
+    /// Number of bytes written to the file so far, before compression
+    pub input_bytes: u64,
+    /// Number of bytes of compressed data the file takes up in the output so far
+    pub output_bytes: u64,
+}
+
+/// A writer that can't seek, wrapped for [`ZipWriter::new_streaming`]. It keeps track of how
+/// many bytes have been written, so that it can report its position, but any seek that would
+/// move it fails with [`io::ErrorKind::Unsupported`].
+#[derive(Debug)]
+pub struct StreamWriter<W: Write> {
+    inner: W,
+    bytes_written: u64,
+}
+
+impl<W: Write> StreamWriter<W> {
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for StreamWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.bytes_written += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Seek for StreamWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(offset) if offset == self.bytes_written => Ok(offset),
+            SeekFrom::Current(0) | SeekFrom::End(0) => Ok(self.bytes_written),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "A streaming ZipWriter can't seek",
+            )),
+        }
+    }
+}
+
+impl<W: Write> ZipWriter<StreamWriter<W>> {
+    /// Initializes an archive that's written to `inner` strictly in order, such as to a pipe or
+    /// a socket, without ever seeking.
+    ///
+    /// Every file is written with [`LocalHeaderSizes::DataDescriptorOnly`], whatever its options
+    /// say, so that its CRC-32 and sizes follow its data rather than being written over its local
+    /// header. For the same reason, AES encryption isn't supported, and minimal mode never falls
+    /// back to storing a file that didn't compress well. File contents go straight through to
+    /// `inner` as they're written, so memory use doesn't grow with a file's size, except with
+    /// ZipCrypto encryption, which holds each file until it's finished. Operations that read back what was
+    /// written, such as [`ZipWriter::deep_copy_file`], aren't available, since the output isn't
+    /// readable, and an aborted file's data stays in the output, although the central directory
+    /// won't refer to it.
+    pub fn new_streaming(inner: W) -> Self {
+        let mut writer = ZipWriter::new(StreamWriter {
+            inner,
+            bytes_written: 0,
+        });
+        writer.streaming = true;
+        writer
+    }
+}
+
 impl<W: Write + Seek> Drop for ZipWriter<W> {
     fn drop(&mut self) {
         if !self.inner.is_closed() {
@@ -1576,6 +2459,227 @@ impl<W: Write + Seek> Drop for ZipWriter<W> {
     }
 }
 
+/// The files written so far by a [`ZipWriter`], as returned by [`ZipWriter::export_index`].
+///
+/// A long archive build can periodically save a snapshot to a sidecar file with
+/// [`IndexSnapshot::write_to`]. If the build is interrupted, the partial archive can be reopened
+/// with [`ZipWriter::new_append_with_index`] and the snapshot read back with
+/// [`IndexSnapshot::read_from`], without a central directory and without scanning the files
+/// already written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexSnapshot {
+    end: u64,
+    archive_offset: u64,
+    number_of_files: u64,
+    central_directory: Vec<u8>,
+    comment: Box<[u8]>,
+    pre_central_directory_block: Box<[u8]>,
+}
+
+impl IndexSnapshot {
+    const MAGIC: [u8; 8] = *b"ZIPINDX1";
+
+    /// Offset in the archive's stream just past the last file in the snapshot, where the next
+    /// file will be written when resuming.
+    pub const fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// Number of files in the snapshot.
+    pub const fn len(&self) -> u64 {
+        self.number_of_files
+    }
+
+    /// Whether the snapshot has no files.
+    pub const fn is_empty(&self) -> bool {
+        self.number_of_files == 0
+    }
+
+    /// Serializes the snapshot, for instance to a sidecar file next to the archive.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&Self::MAGIC)?;
+        writer.write_u64_le(self.end)?;
+        writer.write_u64_le(self.archive_offset)?;
+        writer.write_u64_le(self.number_of_files)?;
+        for field in [
+            &self.central_directory[..],
+            &self.comment,
+            &self.pre_central_directory_block,
+        ] {
+            writer.write_u64_le(field.len() as u64)?;
+            writer.write_all(field)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot serialized with [`IndexSnapshot::write_to`].
+    pub fn read_from<R: Read>(reader: &mut R) -> ZipResult<Self> {
+        use crate::unstable::LittleEndianReadExt;
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(InvalidArchive("Not an index snapshot"));
+        }
+        let end = reader.read_u64_le()?;
+        let archive_offset = reader.read_u64_le()?;
+        let number_of_files = reader.read_u64_le()?;
+        let mut read_field = || -> ZipResult<Vec<u8>> {
+            let len = reader.read_u64_le()?;
+            let mut field = Vec::new();
+            reader.take(len).read_to_end(&mut field)?;
+            if field.len() as u64 != len {
+                return Err(InvalidArchive("Index snapshot is truncated"));
+            }
+            Ok(field)
+        };
+        Ok(IndexSnapshot {
+            end,
+            archive_offset,
+            number_of_files,
+            central_directory: read_field()?,
+            comment: read_field()?.into(),
+            pre_central_directory_block: read_field()?.into(),
+        })
+    }
+}
+
+/// Metadata of a source entry, passed to the transform given to [`repack`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct EntryMetadata {
+    /// Index of the entry in the source archive
+    pub index: usize,
+    /// Name of the entry
+    pub name: Box<str>,
+    /// Compression method of the entry
+    pub compression_method: CompressionMethod,
+    /// Compressed size of the entry
+    pub compressed_size: u64,
+    /// Uncompressed size of the entry
+    pub uncompressed_size: u64,
+    /// CRC-32 of the uncompressed data
+    pub crc32: u32,
+    /// Last-modified time, if it is valid
+    pub last_modified_time: Option<DateTime>,
+    /// Unix mode of the entry, if it has one
+    pub unix_mode: Option<u32>,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+    /// Whether the entry is a symbolic link
+    pub is_symlink: bool,
+}
+
+impl EntryMetadata {
+    fn from_file(index: usize, file: &ZipFile) -> Self {
+        EntryMetadata {
+            index,
+            name: file.name().into(),
+            compression_method: file.compression(),
+            compressed_size: file.compressed_size(),
+            uncompressed_size: file.size(),
+            crc32: file.crc32(),
+            last_modified_time: file.last_modified(),
+            unix_mode: file.unix_mode(),
+            is_dir: file.is_dir(),
+            is_symlink: file.is_symlink(),
+        }
+    }
+}
+
+/// What [`repack`] does with an entry of the source archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RepackAction {
+    /// Copy the compressed data unchanged.
+    Keep,
+    /// Decompress the entry and compress it again with the given method and level. Directories
+    /// and symlinks have no data worth recompressing, so they are copied unchanged.
+    Recompress {
+        /// The new compression method
+        method: CompressionMethod,
+        /// The new compression level, or `None` for the method's default
+        level: Option<i64>,
+    },
+    /// Copy the compressed data unchanged, under a new name.
+    Rename(Box<str>),
+    /// Leave the entry out of the new archive.
+    Drop,
+}
+
+/// Copies every entry of `src` into a new archive written to `dest`, in one pass.
+///
+/// `transform` is called once per entry, in central directory order, and decides whether the
+/// entry is copied raw, recompressed, renamed or dropped. Raw copies reuse the compressed bytes
+/// as-is, as [`ZipWriter::raw_copy_file`] does. Recompressing an encrypted entry fails with
+/// [`ZipError::UnsupportedArchive`], since no password is available to decrypt it.
+///
+/// Returns the inner writer after writing the central directory.
+///
+/// ```
+/// # fn main() -> Result<(), zip::result::ZipError> {
+/// use std::io::{Cursor, Write};
+/// use zip::write::{repack, RepackAction, SimpleFileOptions};
+/// use zip::{CompressionMethod, ZipArchive, ZipWriter};
+///
+/// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+/// zip.start_file("keep.txt", SimpleFileOptions::default())?;
+/// zip.write_all(b"kept")?;
+/// zip.start_file("drop.txt", SimpleFileOptions::default())?;
+/// zip.write_all(b"dropped")?;
+/// let src = ZipArchive::new(zip.finish()?)?;
+///
+/// let repacked = repack(src, Cursor::new(Vec::new()), |entry| {
+///     if entry.name.starts_with("drop") {
+///         RepackAction::Drop
+///     } else {
+///         RepackAction::Keep
+///     }
+/// })?;
+/// let archive = ZipArchive::new(repacked)?;
+/// assert_eq!(archive.file_names().collect::<Vec<_>>(), ["keep.txt"]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn repack<R, W, F>(mut src: ZipArchive<R>, dest: W, mut transform: F) -> ZipResult<W>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+    F: FnMut(&EntryMetadata) -> RepackAction,
+{
+    let mut writer = ZipWriter::new(dest);
+    for index in 0..src.len() {
+        let file = src.by_index_raw(index)?;
+        let metadata = EntryMetadata::from_file(index, &file);
+        match transform(&metadata) {
+            RepackAction::Keep => writer.raw_copy_file(file)?,
+            RepackAction::Rename(name) => writer.raw_copy_file_rename(file, name)?,
+            RepackAction::Drop => {}
+            RepackAction::Recompress { .. } if metadata.is_dir || metadata.is_symlink => {
+                writer.raw_copy_file(file)?
+            }
+            RepackAction::Recompress { method, level } => {
+                drop(file);
+                let mut file = src.by_index(index)?;
+                let mut options = SimpleFileOptions::default()
+                    .compression_method(method)
+                    .compression_level(level)
+                    .large_file(metadata.uncompressed_size > spec::ZIP64_BYTES_THR)
+                    .last_modified_time(
+                        metadata
+                            .last_modified_time
+                            .unwrap_or_else(DateTime::default_for_write),
+                    );
+                if let Some(mode) = metadata.unix_mode {
+                    options = options.unix_permissions(mode);
+                }
+                writer.start_file(metadata.name, options)?;
+                io::copy(&mut file, &mut writer)?;
+            }
+        }
+    }
+    writer.finish()
+}
+
 type SwitchWriterFunction<W> = Box<dyn FnOnce(MaybeEncrypted<W>) -> GenericZipWriter<W>>;
 
 impl<W: Write + Seek> GenericZipWriter<W> {
@@ -1584,6 +2688,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
         compression: CompressionMethod,
         compression_level: Option<i64>,
         #[cfg(feature = "deflate-zopfli")] zopfli_buffer_size: Option<usize>,
+        #[cfg(feature = "zstd")] zstd_window_log: Option<u32>,
     ) -> ZipResult<SwitchWriterFunction<W>> {
         if let Closed = self {
             return Err(
@@ -1598,7 +2703,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 Stored => {
                     if compression_level.is_some() {
                         Err(ZipError::UnsupportedArchive(
-                            "Unsupported compression level",
+                            "Stored files can't have a compression level",
                         ))
                     } else {
                         Ok(Box::new(|bare| Storer(bare)))
@@ -1615,13 +2720,8 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                         Compression::default().level() as i64
                     };
 
-                    let level = clamp_opt(
-                        compression_level.unwrap_or(default),
-                        deflate_compression_level_range(),
-                    )
-                    .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
-                    ))? as u32;
+                    let level =
+                        checked_level(compression, compression_level.unwrap_or(default))? as u32;
 
                     #[cfg(feature = "deflate-zopfli")]
                     {
@@ -1668,13 +2768,10 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 )),
                 #[cfg(feature = "bzip2")]
                 CompressionMethod::Bzip2 => {
-                    let level = clamp_opt(
+                    let level = checked_level(
+                        compression,
                         compression_level.unwrap_or(bzip2::Compression::default().level() as i64),
-                        bzip2_compression_level_range(),
-                    )
-                    .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
-                    ))? as u32;
+                    )? as u32;
                     Ok(Box::new(move |bare| {
                         GenericZipWriter::Bzip2(BzEncoder::new(
                             bare,
@@ -1687,15 +2784,16 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 )),
                 #[cfg(feature = "zstd")]
                 CompressionMethod::Zstd => {
-                    let level = clamp_opt(
+                    let level = checked_level(
+                        compression,
                         compression_level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL as i64),
-                        zstd::compression_level_range(),
-                    )
-                    .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
-                    ))?;
+                    )?;
                     Ok(Box::new(move |bare| {
-                        GenericZipWriter::Zstd(ZstdEncoder::new(bare, level as i32).unwrap())
+                        let mut encoder = ZstdEncoder::new(bare, level as i32).unwrap();
+                        if let Some(window_log) = zstd_window_log {
+                            encoder.window_log(window_log).unwrap();
+                        }
+                        GenericZipWriter::Zstd(encoder)
                     }))
                 }
                 #[cfg(feature = "lzma")]
@@ -1758,6 +2856,25 @@ impl<W: Write + Seek> GenericZipWriter<W> {
         matches!(*self, GenericZipWriter::Closed)
     }
 
+    /// The writer underneath any compression and encryption.
+    fn bare_mut(&mut self) -> Option<&mut W> {
+        let maybe_encrypted = match self {
+            Storer(w) => w,
+            #[cfg(feature = "deflate-flate2")]
+            GenericZipWriter::Deflater(w) => w.get_mut(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::ZopfliDeflater(w) => w.get_mut(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::BufferedZopfliDeflater(w) => w.get_mut().get_mut(),
+            #[cfg(feature = "bzip2")]
+            GenericZipWriter::Bzip2(w) => w.get_mut(),
+            #[cfg(feature = "zstd")]
+            GenericZipWriter::Zstd(w) => w.get_mut(),
+            Closed => return None,
+        };
+        Some(maybe_encrypted.get_mut())
+    }
+
     fn get_plain(&mut self) -> &mut W {
         match *self {
             Storer(MaybeEncrypted::Unencrypted(ref mut w)) => w,
@@ -1773,40 +2890,24 @@ impl<W: Write + Seek> GenericZipWriter<W> {
     }
 }
 
-#[cfg(feature = "_deflate-any")]
-fn deflate_compression_level_range() -> std::ops::RangeInclusive<i64> {
-    let min = if cfg!(feature = "deflate-flate2") {
-        Compression::fast().level() as i64
+/// The window logs zstd accepts, from `ZSTD_WINDOWLOG_MIN` to `ZSTD_WINDOWLOG_MAX`
+#[cfg(feature = "zstd")]
+const ZSTD_WINDOW_LOG_RANGE: std::ops::RangeInclusive<u32> =
+    10..=if cfg!(target_pointer_width = "32") {
+        30
     } else {
-        Compression::best().level() as i64 + 1
+        31
     };
 
-    let max = Compression::best().level() as i64
-        + if cfg!(feature = "deflate-zopfli") {
-            u8::MAX as i64
-        } else {
-            0
-        };
-
-    min..=max
-}
-
-#[cfg(feature = "bzip2")]
-fn bzip2_compression_level_range() -> std::ops::RangeInclusive<i64> {
-    let min = bzip2::Compression::fast().level() as i64;
-    let max = bzip2::Compression::best().level() as i64;
-    min..=max
-}
-
+/// Returns `level` if `method` supports it.
 #[cfg(any(feature = "_deflate-any", feature = "bzip2", feature = "zstd"))]
-fn clamp_opt<T: Ord + Copy, U: Ord + Copy + TryFrom<T>>(
-    value: T,
-    range: std::ops::RangeInclusive<U>,
-) -> Option<T> {
-    if range.contains(&value.try_into().ok()?) {
-        Some(value)
+fn checked_level(method: CompressionMethod, level: i64) -> ZipResult<i64> {
+    if method.clamp_level(level) == Some(level) {
+        Ok(level)
     } else {
-        None
+        Err(ZipError::UnsupportedArchive(
+            "Compression level is out of range for the compression method; see CompressionMethod::clamp_level",
+        ))
     }
 }
 
@@ -1854,6 +2955,108 @@ fn update_aes_extra_data<W: Write + io::Seek>(
     Ok(())
 }
 
+/// Above this size, the files rearranged by [`ZipWriter::collect_then_write_sorted`] are buffered
+/// in a temporary file instead of in memory.
+const SORT_IN_MEMORY_LIMIT: u64 = 64 << 20;
+
+/// Copies each `(start, end)` range of a region that was read into `buffer` from `region_start`
+/// onwards to `writer`, in order, and records where each one was written.
+fn copy_extents<B: Read + Seek, T: Write + Seek>(
+    buffer: &mut B,
+    region_start: u64,
+    extents: &[(u64, u64)],
+    writer: &mut T,
+    new_starts: &mut Vec<u64>,
+) -> ZipResult<()> {
+    writer.seek(SeekFrom::Start(region_start))?;
+    for (start, end) in extents {
+        new_starts.push(writer.stream_position()?);
+        buffer.seek(SeekFrom::Start(start - region_start))?;
+        io::copy(&mut buffer.take(end - start), writer)?;
+    }
+    Ok(())
+}
+
+/// A writer that discards its input and only counts it
+#[derive(Default)]
+struct ByteCounter(u64);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for ByteCounter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.0),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "ByteCounter can't seek",
+            )),
+        }
+    }
+}
+
+/// Compress everything read from `reader` with `method` at its default level, and return the
+/// length of the compressed data. The compressed data itself is discarded as it's produced.
+pub(crate) fn compressed_len<R: Read>(reader: &mut R, method: CompressionMethod) -> ZipResult<u64> {
+    let mut writer = Storer(MaybeEncrypted::Unencrypted(ByteCounter::default()));
+    let make_compressor = writer.prepare_next_writer(
+        method,
+        None,
+        #[cfg(feature = "deflate-zopfli")]
+        SimpleFileOptions::default().zopfli_buffer_size,
+        #[cfg(feature = "zstd")]
+        SimpleFileOptions::default().zstd_window_log,
+    )?;
+    writer.switch_to(make_compressor)?;
+    if let Some(compressor) = writer.ref_mut() {
+        io::copy(reader, compressor)?;
+    }
+    writer.switch_to(Box::new(Storer))?;
+    Ok(writer.unwrap().0)
+}
+
+/// A file in [`std::env::temp_dir`] that's deleted when dropped
+struct TempFile {
+    file: File,
+    path: std::path::PathBuf,
+}
+
+impl TempFile {
+    fn new() -> io::Result<Self> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        loop {
+            let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let path =
+                std::env::temp_dir().join(format!("zip-sorted-{}-{count}.tmp", std::process::id()));
+            match File::options()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => return Ok(TempFile { file, path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
 fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     const CRC32_OFFSET: u64 = 14;
     writer.seek(SeekFrom::Start(file.header_start + CRC32_OFFSET))?;
@@ -1875,12 +3078,105 @@ fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData)
     Ok(())
 }
 
-fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+fn write_data_descriptor<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+    writer.write_all(&spec::Magic::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes())?;
+    writer.write_u32_le(file.crc32)?;
+    // Readers expect 8-byte sizes exactly when the local header has a ZIP64 extra field
+    if file.large_file {
+        writer.write_u64_le(file.compressed_size)?;
+        writer.write_u64_le(file.uncompressed_size)?;
+    } else {
+        if file.compressed_size > spec::ZIP64_BYTES_THR {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                "Large file option has not been set",
+            )));
+        }
+        writer.write_u32_le(file.compressed_size as u32)?;
+        writer.write_u32_le(file.uncompressed_size as u32)?;
+    }
+    Ok(())
+}
+
+/// Writes any block inserted before the central directory, the central directory and the
+/// end-of-central-directory records to `writer`, which is at offset `start` of the output.
+/// Returns the offset of the central directory.
+fn write_central_and_footer_to<T: Write>(
+    writer: &mut T,
+    start: u64,
+    files: &IndexMap<Box<str>, ZipFileData>,
+    pre_central_directory_block: &[u8],
+    comment: &[u8],
+    archive_offset: u64,
+) -> ZipResult<u64> {
+    let mut writer = StreamWriter {
+        inner: writer,
+        bytes_written: start,
+    };
+    writer.write_all(pre_central_directory_block)?;
+
+    let mut version_needed = MIN_VERSION as u16;
+    let central_start = writer.bytes_written;
+    for file in files.values() {
+        // Offsets within the archive are relative to the end of any prefix
+        write_central_directory_header(&mut writer, file, file.header_start - archive_offset)?;
+        version_needed = version_needed.max(file.version_needed());
+    }
+    let central_size = writer.bytes_written - central_start;
+    let relative_central_start = central_start - archive_offset;
+
+    // A ZIP32 entry count of 0xFFFF means the real count is in the ZIP64 record
+    if files.len() >= spec::ZIP64_ENTRY_THR
+        || central_size.max(relative_central_start) > spec::ZIP64_BYTES_THR
+    {
+        let zip64_footer = spec::Zip64CentralDirectoryEnd {
+            version_made_by: version_needed,
+            version_needed_to_extract: version_needed,
+            disk_number: 0,
+            disk_with_central_directory: 0,
+            number_of_files_on_this_disk: files.len() as u64,
+            number_of_files: files.len() as u64,
+            central_directory_size: central_size,
+            central_directory_offset: relative_central_start,
+        };
+
+        zip64_footer.write(&mut writer)?;
+
+        let zip64_footer = spec::Zip64CentralDirectoryEndLocator {
+            disk_with_central_directory: 0,
+            end_of_central_directory_offset: relative_central_start + central_size,
+            number_of_disks: 1,
+        };
+
+        zip64_footer.write(&mut writer)?;
+    }
+
+    let number_of_files = files.len().min(spec::ZIP64_ENTRY_THR) as u16;
+    let footer = spec::Zip32CentralDirectoryEnd {
+        disk_number: 0,
+        disk_with_central_directory: 0,
+        zip_file_comment: comment.into(),
+        number_of_files_on_this_disk: number_of_files,
+        number_of_files,
+        central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
+        central_directory_offset: relative_central_start.min(spec::ZIP64_BYTES_THR) as u32,
+    };
+
+    footer.write(&mut writer)?;
+    Ok(central_start)
+}
+
+/// Writes the central directory header of `file`, recording its local header at `header_start`
+fn write_central_directory_header<T: Write>(
+    writer: &mut T,
+    file: &ZipFileData,
+    header_start: u64,
+) -> ZipResult<()> {
     // buffer zip64 extra field to determine its variable length
     let mut zip64_extra_field = [0; 28];
     let zip64_extra_field_length =
-        write_central_zip64_extra_field(&mut zip64_extra_field.as_mut(), file)?;
-    let block = file.block(zip64_extra_field_length)?;
+        write_central_zip64_extra_field(&mut zip64_extra_field.as_mut(), file, header_start)?;
+    let block = file.block(zip64_extra_field_length, header_start)?;
     block.write(writer)?;
     // file name
     writer.write_all(&file.file_name_raw)?;
@@ -1902,7 +3198,7 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
 fn write_local_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     // This entry in the Local header MUST include BOTH original
     // and compressed file size fields.
-    let Some(block) = file.zip64_extra_field_block() else {
+    let Some(block) = file.local_zip64_extra_field_block() else {
         return Err(ZipError::InvalidArchive(
             "Attempted to write a ZIP64 extra field for a file that's within zip32 limits",
         ));
@@ -1928,18 +3224,22 @@ fn update_local_zip64_extra_field<T: Write + Seek>(
 
     writer.seek(SeekFrom::Start(zip64_extra_field))?;
 
-    let block = file.zip64_extra_field_block().unwrap();
+    let block = file.local_zip64_extra_field_block().unwrap();
     let block = block.serialize();
     writer.write_all(&block)?;
     Ok(())
 }
 
-fn write_central_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<u16> {
+fn write_central_zip64_extra_field<T: Write>(
+    writer: &mut T,
+    file: &ZipFileData,
+    header_start: u64,
+) -> ZipResult<u16> {
     // The order of the fields in the zip64 extended
     // information record is fixed, but the fields MUST
     // only appear if the corresponding Local or Central
     // directory record field is set to 0xFFFF or 0xFFFFFFFF.
-    match file.zip64_extra_field_block() {
+    match file.zip64_extra_field_block(header_start) {
         None => Ok(0),
         Some(block) => {
             let block = block.serialize();
@@ -1963,10 +3263,10 @@ const EXTRA_FIELD_MAPPING: [u16; 43] = [
 #[allow(clippy::needless_update)] // So we can use the same FileOptions decls with and without zopfli_buffer_size
 #[allow(clippy::octal_escapes)] // many false positives in converted fuzz cases
 mod test {
-    use super::{ExtendedFileOptions, FileOptions, FullFileOptions, ZipWriter};
+    use super::{ExtendedFileOptions, FileOptions, FullFileOptions, LocalHeaderSizes, ZipWriter};
     use crate::compression::CompressionMethod;
-    use crate::result::ZipResult;
-    use crate::types::DateTime;
+    use crate::result::{ZipError::InvalidArchive, ZipResult};
+    use crate::types::{DateTime, System};
     use crate::write::EncryptWith::ZipCrypto;
     use crate::write::SimpleFileOptions;
     use crate::zipcrypto::ZipCryptoKeys;
@@ -1989,6 +3289,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn empty_zip_round_trip() -> ZipResult<()> {
+        let writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let bytes = writer.finish()?.into_inner();
+        assert_eq!(bytes.len(), 22);
+        let archive = ZipArchive::new(io::Cursor::new(bytes))?;
+        assert_eq!(archive.len(), 0);
+        assert!(archive.is_empty());
+        assert!(archive.comment().is_empty());
+
+        let writer = ZipWriter::new_append(archive.into_inner())?;
+        let archive = writer.finish_into_readable()?;
+        assert!(archive.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn unix_permissions_bitmask() {
         // unix_permissions() throws away upper bits.
@@ -2113,8 +3429,17 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
+            force_utf8: false,
+            local_header_sizes: LocalHeaderSizes::BackPatch,
+            deflate_level_hint: None,
+            version_needed: None,
+            system: System::Unix,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
+            #[cfg(feature = "aes-crypto")]
+            aes_vendor_version: None,
         };
         writer.start_file("mimetype", options).unwrap();
         writer
@@ -2150,8 +3475,17 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
+            force_utf8: false,
+            local_header_sizes: LocalHeaderSizes::BackPatch,
+            deflate_level_hint: None,
+            version_needed: None,
+            system: System::Unix,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
+            #[cfg(feature = "aes-crypto")]
+            aes_vendor_version: None,
         };
 
         // GB18030
@@ -2202,8 +3536,17 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
+            force_utf8: false,
+            local_header_sizes: LocalHeaderSizes::BackPatch,
+            deflate_level_hint: None,
+            version_needed: None,
+            system: System::Unix,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
+            #[cfg(feature = "aes-crypto")]
+            aes_vendor_version: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2252,8 +3595,17 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
+            force_utf8: false,
+            local_header_sizes: LocalHeaderSizes::BackPatch,
+            deflate_level_hint: None,
+            version_needed: None,
+            system: System::Unix,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
+            #[cfg(feature = "aes-crypto")]
+            aes_vendor_version: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2568,6 +3920,7 @@ mod test {
                 encrypt_with: None,
                 extended_options: ExtendedFileOptions {
                     extra_data: vec![].into(),
+                    local_extra_data: vec![].into(),
                     central_extra_data: vec![].into(),
                 },
                 alignment: 2048,
@@ -2594,6 +3947,7 @@ mod test {
         let options = FileOptions {
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![99, 0, 15, 0, 207].into(),
             },
             ..Default::default()
@@ -2616,6 +3970,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![
                     7, 0, 15, 0, 207, 117, 177, 117, 112, 2, 0, 255, 255, 131, 255, 255, 255, 80,
                     185,
@@ -2643,6 +3998,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![
                     1, 41, 4, 0, 1, 255, 245, 117, 117, 112, 5, 0, 80, 255, 149, 255, 247,
                 ]
@@ -2671,6 +4027,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 4,
@@ -2698,6 +4055,7 @@ mod test {
                 encrypt_with: None,
                 extended_options: ExtendedFileOptions {
                     extra_data: vec![].into(),
+                    local_extra_data: vec![].into(),
                     central_extra_data: vec![].into(),
                 },
                 alignment: 185,
@@ -2749,6 +4107,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 2565,
@@ -2765,6 +4124,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 0,
@@ -2789,6 +4149,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 65521,
@@ -2806,6 +4167,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![255, 255, 1, 0, 255, 0, 0, 0, 0].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 65535,
@@ -2832,6 +4194,7 @@ mod test {
                 encrypt_with: None,
                 extended_options: ExtendedFileOptions {
                     extra_data: vec![].into(),
+                    local_extra_data: vec![].into(),
                     central_extra_data: vec![].into(),
                 },
                 alignment: 0,
@@ -2869,6 +4232,7 @@ mod test {
             }),
             extended_options: ExtendedFileOptions {
                 extra_data: vec![2, 0, 1, 0, 0].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![
                     35, 229, 2, 0, 41, 41, 231, 44, 2, 0, 52, 233, 82, 201, 0, 0, 3, 0, 2, 0, 233,
                     255, 3, 0, 2, 0, 26, 154, 38, 251, 0, 0,
@@ -2895,6 +4259,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![76, 0, 1, 0, 0, 2, 0, 0, 0].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![
                     1, 149, 1, 0, 255, 3, 0, 0, 0, 2, 255, 0, 0, 12, 65, 1, 0, 0, 67, 149, 0, 0,
                     76, 149, 2, 0, 149, 149, 67, 149, 0, 0,
@@ -2960,6 +4325,7 @@ mod test {
                                                 )),
                                                 extended_options: ExtendedFileOptions {
                                                     extra_data: vec![].into(),
+                                                    local_extra_data: vec![].into(),
                                                     central_extra_data: vec![].into(),
                                                 },
                                                 alignment: 255,
@@ -2986,6 +4352,7 @@ mod test {
                                             encrypt_with: None,
                                             extended_options: ExtendedFileOptions {
                                                 extra_data: vec![].into(),
+                                                local_extra_data: vec![].into(),
                                                 central_extra_data: vec![].into(),
                                             },
                                             alignment: 43,
@@ -3006,6 +4373,7 @@ mod test {
                                             encrypt_with: None,
                                             extended_options: ExtendedFileOptions {
                                                 extra_data: vec![].into(),
+                                                local_extra_data: vec![].into(),
                                                 central_extra_data: vec![].into(),
                                             },
                                             alignment: 26,
@@ -3032,6 +4400,7 @@ mod test {
                                             extended_options: ExtendedFileOptions {
                                                 extra_data: vec![3, 0, 1, 0, 255, 144, 136, 0, 0]
                                                     .into(),
+                                                local_extra_data: vec![].into(),
                                                 central_extra_data: vec![].into(),
                                             },
                                             alignment: 65535,
@@ -3069,6 +4438,7 @@ mod test {
                                 )),
                                 extended_options: ExtendedFileOptions {
                                     extra_data: vec![].into(),
+                                    local_extra_data: vec![].into(),
                                     central_extra_data: vec![].into(),
                                 },
                                 alignment: 0,
@@ -3122,6 +4492,7 @@ mod test {
                     encrypt_with: None,
                     extended_options: ExtendedFileOptions {
                         extra_data: vec![].into(),
+                        local_extra_data: vec![].into(),
                         central_extra_data: vec![].into(),
                     },
                     alignment: 20555,
@@ -3157,6 +4528,7 @@ mod test {
                 encrypt_with: None,
                 extended_options: ExtendedFileOptions {
                     extra_data: vec![].into(),
+                    local_extra_data: vec![].into(),
                     central_extra_data: vec![].into(),
                 },
                 alignment: 0,
@@ -3217,6 +4589,7 @@ mod test {
                                             encrypt_with: None,
                                             extended_options: ExtendedFileOptions {
                                                 extra_data: vec![].into(),
+                                                local_extra_data: vec![].into(),
                                                 central_extra_data: vec![].into(),
                                             },
                                             alignment: 20555,
@@ -3237,6 +4610,7 @@ mod test {
                                         encrypt_with: None,
                                         extended_options: ExtendedFileOptions {
                                             extra_data: vec![].into(),
+                                            local_extra_data: vec![].into(),
                                             central_extra_data: vec![].into(),
                                         },
                                         alignment: 0,
@@ -3323,6 +4697,7 @@ mod test {
                                 182, 180, 1, 0, 180, 182, 74, 0, 0, 200, 0, 0, 0, 2, 0, 0, 0,
                             ]
                             .into(),
+                            local_extra_data: vec![].into(),
                             central_extra_data: vec![].into(),
                         },
                         alignment: 1542,
@@ -3389,6 +4764,7 @@ mod test {
             }),
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 255,
@@ -3424,6 +4800,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             alignment: 256,
@@ -3457,6 +4834,7 @@ mod test {
             encrypt_with: None,
             extended_options: ExtendedFileOptions {
                 extra_data: vec![].into(),
+                local_extra_data: vec![].into(),
                 central_extra_data: vec![].into(),
             },
             ..Default::default()
@@ -3494,4 +4872,1000 @@ mod test {
         assert!(archive.comment().starts_with(&[33]));
         Ok(())
     }
+
+    #[test]
+    fn large_file_reserves_zip64_local_extra_field() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .large_file(true);
+        writer.start_file("small", options)?;
+        writer.write_all(b"0123456789")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let header_start = archive.by_index_raw(0)?.header_start() as usize;
+        let bytes = archive.clone().into_inner().into_inner();
+        let local = &bytes[header_start..];
+        assert_eq!(&local[18..26], &[0xFF; 8]);
+        let name_len = u16::from_le_bytes([local[26], local[27]]) as usize;
+        let extra_len = u16::from_le_bytes([local[28], local[29]]) as usize;
+        assert_eq!(extra_len, 20);
+        let extra = &local[30 + name_len..30 + name_len + extra_len];
+        assert_eq!(&extra[0..4], &[0x01, 0x00, 0x10, 0x00]);
+        assert_eq!(u64::from_le_bytes(extra[4..12].try_into().unwrap()), 10);
+        assert_eq!(u64::from_le_bytes(extra[12..20].try_into().unwrap()), 10);
+
+        assert!(archive.validate_local_headers()?.is_empty());
+        let mut file = archive.by_name("small")?;
+        assert_eq!(file.size(), 10);
+        assert_eq!(file.compressed_size(), 10);
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "0123456789");
+        drop(file);
+
+        let mut stream = Cursor::new(bytes);
+        let file = crate::read::read_zipfile_from_stream(&mut stream)?.unwrap();
+        assert_eq!(file.size(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn utf8_flag_only_set_for_non_ascii_names() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("ascii.txt", options)?;
+        writer.start_file("\u{4f60}\u{597d}.txt", options)?;
+        writer.start_file("forced.txt", options.force_utf8(true))?;
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+
+        let mut offsets = Vec::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            offsets.push((
+                file.header_start() as usize,
+                file.central_header_start() as usize,
+            ));
+        }
+        let utf8_bits: Vec<_> = offsets
+            .into_iter()
+            .map(|(local, central)| {
+                let local_flags = u16::from_le_bytes([bytes[local + 6], bytes[local + 7]]);
+                let central_flags = u16::from_le_bytes([bytes[central + 8], bytes[central + 9]]);
+                assert_eq!(local_flags, central_flags);
+                local_flags & (1 << 11) != 0
+            })
+            .collect();
+        assert_eq!(utf8_bits, [false, true, true]);
+        assert!(archive.by_name("\u{4f60}\u{597d}.txt").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn from_path_uses_forward_slashes() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        let mut dir = PathBuf::from("dir");
+        dir.push("sub");
+        writer.add_directory_from_path(&dir, options)?;
+        writer.start_file_from_path(dir.join("file.txt"), options)?;
+        #[cfg(windows)]
+        writer.start_file_from_path(r"other\nested\file.txt", options)?;
+        let archive = writer.finish_into_readable()?;
+        let names: Vec<_> = archive.file_names().collect();
+        assert!(names.contains(&"dir/sub/"));
+        assert!(names.contains(&"dir/sub/file.txt"));
+        #[cfg(windows)]
+        assert!(names.contains(&"other/nested/file.txt"));
+        assert!(names.iter().all(|name| !name.contains('\\')));
+        Ok(())
+    }
+
+    #[test]
+    fn host_system_controls_attribute_interpretation() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().unix_permissions(0o640);
+        writer.start_file("unix", options.system(System::Unix))?;
+        writer.start_file("dos", options.system(System::Dos))?;
+        writer.start_file(
+            "dos_read_only",
+            options.unix_permissions(0o444).system(System::Dos),
+        )?;
+        writer.add_directory("dos_dir", options.system(System::Dos))?;
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+
+        assert_eq!(archive.by_name("unix")?.unix_mode(), Some(0o100640));
+        assert_eq!(archive.by_name("dos")?.unix_mode(), Some(0o100664));
+        assert_eq!(archive.by_name("dos_read_only")?.unix_mode(), Some(0o444));
+        assert_eq!(archive.by_name("dos_dir/")?.unix_mode(), Some(0o040775));
+        Ok(())
+    }
+
+    #[test]
+    fn block_before_central_directory() -> ZipResult<()> {
+        const BLOCK: &[u8] = b"APK Sig Block 42, or some other opaque data";
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("first", options)?;
+        writer.write_all(b"first contents")?;
+        writer.start_file("second", options)?;
+        writer.write_all(b"second contents")?;
+        writer.insert_block_before_central_directory(BLOCK);
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        let central_start = archive.by_index_raw(0)?.central_header_start() as usize;
+        assert_eq!(&bytes[central_start - BLOCK.len()..central_start], BLOCK);
+        let mut contents = String::new();
+        archive.by_name("first")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "first contents");
+        contents.clear();
+        archive.by_name("second")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "second contents");
+        Ok(())
+    }
+
+    #[test]
+    fn prefixed_archive() -> ZipResult<()> {
+        const STUB: &[u8] = b"#!/bin/sh\necho 'pretend this is a self-extractor'\nexit 0\n";
+        let mut writer = ZipWriter::new_with_prefix(Cursor::new(Vec::new()), STUB)?;
+        writer.start_file("first", SimpleFileOptions::default())?;
+        writer.write_all(b"first contents")?;
+        writer.add_directory("dir", SimpleFileOptions::default())?;
+        let bytes = writer.finish()?.into_inner();
+        assert!(bytes.starts_with(STUB));
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        assert_eq!(archive.offset(), STUB.len() as u64);
+        let mut contents = String::new();
+        archive.by_name("first")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "first contents");
+        assert!(archive.by_name("dir/")?.is_dir());
+
+        // Without the stub, the offsets are still valid
+        let archive = ZipArchive::new(Cursor::new(&bytes[STUB.len()..]))?;
+        assert_eq!(archive.offset(), 0);
+        assert_eq!(archive.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn directory_names_and_file_names_dont_mix() -> ZipResult<()> {
+        let options = SimpleFileOptions::default();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        assert!(matches!(
+            writer.start_file("dir/", options),
+            Err(InvalidArchive(_))
+        ));
+        writer.add_directory("dir", options)?;
+        assert!(writer.write_all(b"directories have no content").is_err());
+        writer.start_file("dir/file", options)?;
+        writer.write_all(b"file content")?;
+
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["dir/", "dir/file"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn symlink_mode_round_trip() -> ZipResult<()> {
+        use super::ffi::S_IFLNK;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("target", SimpleFileOptions::default())?;
+        writer.write_all(b"target contents")?;
+        writer.add_symlink("default", "target", SimpleFileOptions::default())?;
+        writer.add_symlink(
+            "overridden",
+            "target",
+            SimpleFileOptions::default()
+                .unix_permissions(0o100755)
+                .system(System::Dos),
+        )?;
+        let mut archive = writer.finish_into_readable()?;
+
+        for (name, mode) in [("default", 0o777), ("overridden", 0o755)] {
+            let file = archive.by_name(name)?;
+            assert!(file.is_symlink());
+            assert_eq!(file.unix_mode(), Some(S_IFLNK | mode));
+        }
+
+        #[cfg(unix)]
+        {
+            let dir = tempdir::TempDir::new("symlink_mode_round_trip")?;
+            archive.extract(&dir)?;
+            for name in ["default", "overridden"] {
+                let metadata = std::fs::symlink_metadata(dir.path().join(name))?;
+                assert!(metadata.file_type().is_symlink());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn opaque_local_extra_data_round_trip() -> ZipResult<()> {
+        let payload = b"sidecar metadata".to_vec().into_boxed_slice();
+        let mut options = FullFileOptions::default().compression_method(Stored);
+        options.add_extra_data(0xcafe, payload.clone(), false)?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file", options)?;
+        writer.write_all(b"file contents")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut field = vec![0xfe, 0xca, payload.len() as u8, 0];
+        field.extend_from_slice(&payload);
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+        let mut file = archive.by_name("file")?;
+        assert_eq!(file.extra_data(), Some(&*field));
+        let data_start = file.data_start() as usize;
+        assert_eq!(&bytes[data_start - field.len()..data_start], &*field);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents, b"file contents");
+        Ok(())
+    }
+
+    #[test]
+    fn separate_central_directory() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("comment");
+        writer.start_file("first", SimpleFileOptions::default())?;
+        writer.write_all(b"first contents")?;
+        writer.start_file("second", SimpleFileOptions::default())?;
+        writer.write_all(b"second contents")?;
+        let mut central_directory = Vec::new();
+        let entries = writer
+            .finish_with_separate_central_directory(&mut central_directory)?
+            .into_inner();
+        assert!(ZipArchive::new(Cursor::new(&entries)).is_err());
+
+        let mut bytes = entries;
+        bytes.extend_from_slice(&central_directory);
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.comment(), b"comment");
+        for (name, expected) in [("first", "first contents"), ("second", "second contents")] {
+            let mut contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn alignment_padding_is_minimal() -> ZipResult<()> {
+        const ALIGN: u16 = 64;
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .with_alignment(ALIGN);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        // The first name leaves the data of its entry naturally aligned
+        let names: Vec<String> = (0..12)
+            .map(|i| format!("{i:0>width$}", width = 34 + i * 5))
+            .collect();
+        for name in &names {
+            writer.start_file(name.as_str(), options)?;
+            writer.write_all(b"contents")?;
+        }
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+        let (mut padded, mut unpadded) = (0, 0);
+        for name in &names {
+            let file = archive.by_name(name)?;
+            assert_eq!(file.data_start() % ALIGN as u64, 0);
+            let header_start = file.header_start() as usize;
+            let extra_len =
+                u16::from_le_bytes([bytes[header_start + 28], bytes[header_start + 29]]);
+            let unpadded_data_start = header_start + 30 + name.len();
+            if extra_len == 0 {
+                assert_eq!(unpadded_data_start % ALIGN as usize, 0);
+                unpadded += 1;
+            } else {
+                assert_ne!(unpadded_data_start % ALIGN as usize, 0);
+                assert!((6..6 + ALIGN).contains(&extra_len));
+                padded += 1;
+            }
+        }
+        assert!(padded > 0 && unpadded > 0);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "deflate-flate2")]
+    fn repack_renames_and_recompresses() -> ZipResult<()> {
+        use super::{repack, RepackAction};
+        let contents = b"repack me ".repeat(100);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(Stored);
+        writer.add_directory("dir/", stored)?;
+        for name in [
+            "dir/rename.txt",
+            "dir/recompress.txt",
+            "dir/drop.txt",
+            "keep.txt",
+        ] {
+            writer.start_file(name, stored)?;
+            writer.write_all(&contents)?;
+        }
+        let src = ZipArchive::new(writer.finish()?)?;
+
+        let mut seen = Vec::new();
+        let repacked = repack(src, Cursor::new(Vec::new()), |entry| {
+            seen.push(entry.index);
+            match &*entry.name {
+                "dir/rename.txt" => RepackAction::Rename("dir/renamed.txt".into()),
+                "dir/recompress.txt" => RepackAction::Recompress {
+                    method: CompressionMethod::Deflated,
+                    level: Some(9),
+                },
+                "dir/drop.txt" => RepackAction::Drop,
+                _ => RepackAction::Keep,
+            }
+        })?;
+        assert_eq!(seen, [0, 1, 2, 3, 4]);
+
+        let mut archive = ZipArchive::new(repacked)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["dir/", "dir/renamed.txt", "dir/recompress.txt", "keep.txt"]
+        );
+        for name in ["dir/renamed.txt", "dir/recompress.txt", "keep.txt"] {
+            let mut file = archive.by_name(name)?;
+            let expected_method = if name == "dir/recompress.txt" {
+                CompressionMethod::Deflated
+            } else {
+                Stored
+            };
+            assert_eq!(file.compression(), expected_method);
+            let mut read_back = Vec::new();
+            file.read_to_end(&mut read_back)?;
+            assert_eq!(read_back, contents);
+        }
+        assert!(archive.by_name("dir/recompress.txt")?.compressed_size() < contents.len() as u64);
+        assert!(archive.by_name("dir/")?.is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn fsync_on_finish_file() -> ZipResult<()> {
+        use crate::read::list_from_local_headers;
+        let dir = tempdir::TempDir::new("fsync_on_finish_file")?;
+        let path = dir.path().join("incremental.zip");
+        let mut writer = ZipWriter::new(std::fs::File::create(&path)?);
+        writer.set_fsync_on_finish_file(true);
+        writer.start_file("first.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"first entry")?;
+        writer.start_file("second.txt", SimpleFileOptions::default())?;
+
+        // The first entry is complete on disk, header included, before the archive is finished
+        let entries = list_from_local_headers(&mut std::fs::File::open(&path)?)?;
+        assert_eq!(&*entries[0].name, "first.txt");
+        assert_eq!(entries[0].uncompressed_size, 11);
+        assert_eq!(entries[0].crc32, crc32fast::hash(b"first entry"));
+
+        writer.write_all(b"second entry")?;
+        writer.finish()?;
+        let mut archive = ZipArchive::new(std::fs::File::open(&path)?)?;
+        let mut contents = String::new();
+        archive
+            .by_name("second.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "second entry");
+        Ok(())
+    }
+
+    #[test]
+    fn start_file_with_crc32() -> ZipResult<()> {
+        const CONTENTS: &[u8] = b"contents whose CRC-32 is already known";
+        let crc32 = crc32fast::hash(CONTENTS);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_with_crc32("known.txt", SimpleFileOptions::default(), crc32)?;
+        writer.write_all(CONTENTS)?;
+        // The given CRC-32 only applies to the file it was given for
+        writer.start_file("computed.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"other contents")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        let mut file = archive.by_name("known.txt")?;
+        assert_eq!(file.crc32(), crc32);
+        let mut read_back = Vec::new();
+        file.read_to_end(&mut read_back)?;
+        assert_eq!(read_back, CONTENTS);
+        drop(file);
+        assert_eq!(
+            archive.by_name("computed.txt")?.crc32(),
+            crc32fast::hash(b"other contents")
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn start_file_with_wrong_crc32() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_with_crc32("wrong.txt", SimpleFileOptions::default(), 0x12345678)?;
+        writer.write_all(b"contents")?;
+        assert!(matches!(writer.finish(), Err(InvalidArchive(_))));
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_with_crc32("wrong.txt", SimpleFileOptions::default(), 0x12345678)?;
+        writer.write_all(b"contents")?;
+        assert!(writer
+            .start_file("next.txt", SimpleFileOptions::default())
+            .is_err());
+        // The file with the wrong CRC-32 was aborted
+        writer.start_file("next.txt", SimpleFileOptions::default())?;
+        let archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["next.txt"]);
+        Ok(())
+    }
+
+    #[cfg(feature = "deflate-flate2")]
+    #[test]
+    fn minimal_mode() -> ZipResult<()> {
+        let compressible = b"abcd".repeat(64);
+        let inputs: [(&str, &[u8]); 4] = [
+            ("a", b"x"),
+            ("b", b"hello"),
+            ("c", b"0123456789abcdefghij"),
+            ("d", &compressible),
+        ];
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .with_alignment(64);
+        let write = |minimal: bool| -> ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            if minimal {
+                writer = writer.minimal_mode();
+            }
+            for (name, contents) in inputs {
+                writer.start_file(name, options)?;
+                writer.write_all(contents)?;
+            }
+            Ok(writer.finish()?.into_inner())
+        };
+        let default = write(false)?;
+        let minimal = write(true)?;
+        assert!(
+            minimal.len() < default.len(),
+            "{} >= {}",
+            minimal.len(),
+            default.len()
+        );
+
+        let mut archive = ZipArchive::new(Cursor::new(minimal))?;
+        for (name, contents) in inputs {
+            let mut file = archive.by_name(name)?;
+            let expected_method = if name == "d" {
+                CompressionMethod::Deflated
+            } else {
+                Stored
+            };
+            assert_eq!(file.compression(), expected_method);
+            assert_eq!(file.extra_data(), Some(&[][..]));
+            let mut read_back = Vec::new();
+            file.read_to_end(&mut read_back)?;
+            assert_eq!(read_back, contents);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn append_with_trailing_garbage() -> ZipResult<()> {
+        let append = |archive: Vec<u8>, truncate: bool| -> ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new_append(Cursor::new(archive))?;
+            writer.set_truncate_on_finish(truncate);
+            writer.start_file("appended.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"appended")?;
+            writer.finish_to_vec()
+        };
+        let mut writer = ZipWriter::new_buffer();
+        writer.start_file("original.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"original")?;
+        let original = writer.finish_to_vec()?;
+        let expected = append(original.clone(), false)?;
+
+        for garbage_len in [10, 1000] {
+            let mut with_garbage = original.clone();
+            with_garbage.resize(original.len() + garbage_len, 0x55);
+            let appended = append(with_garbage.clone(), true)?;
+            assert_eq!(appended, expected);
+
+            let appended = append(with_garbage, false)?;
+            let mut archive = ZipArchive::new(Cursor::new(appended))?;
+            for (name, contents) in [("original.txt", "original"), ("appended.txt", "appended")] {
+                let mut read_back = String::new();
+                archive.by_name(name)?.read_to_string(&mut read_back)?;
+                assert_eq!(read_back, contents);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn local_header_sizes() -> ZipResult<()> {
+        const CONTENTS: &[u8] = b"contents of a file with its sizes in different places";
+        #[allow(unused_mut)]
+        let mut methods = vec![Stored];
+        #[cfg(feature = "deflate-flate2")]
+        methods.push(CompressionMethod::Deflated);
+        for method in methods {
+            for large_file in [false, true] {
+                for sizes in [
+                    LocalHeaderSizes::BackPatch,
+                    LocalHeaderSizes::DataDescriptorOnly,
+                ] {
+                    let mut writer = ZipWriter::new_buffer();
+                    let options = SimpleFileOptions::default()
+                        .compression_method(method)
+                        .large_file(large_file)
+                        .local_header_sizes(sizes);
+                    writer.start_file("file.txt", options)?;
+                    writer.write_all(CONTENTS)?;
+                    let bytes = writer.finish_to_vec()?;
+
+                    let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+                    let mut file = archive.by_index(0)?;
+                    let (crc32, compressed_size) = (file.crc32(), file.compressed_size());
+                    let data_end = (file.data_start() + compressed_size) as usize;
+                    let mut read_back = Vec::new();
+                    file.read_to_end(&mut read_back)?;
+                    assert_eq!(read_back, CONTENTS);
+                    drop(file);
+
+                    let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+                    let local_crc32 = u32::from_le_bytes(bytes[14..18].try_into().unwrap());
+                    let local_sizes = &bytes[18..26];
+                    let has_descriptor = bytes[data_end..data_end + 4] == *b"PK\x07\x08";
+                    match sizes {
+                        LocalHeaderSizes::BackPatch => {
+                            assert_eq!(flags & (1 << 3), 0);
+                            assert_eq!(local_crc32, crc32);
+                            if !large_file {
+                                let local_compressed_size =
+                                    u32::from_le_bytes(local_sizes[..4].try_into().unwrap());
+                                assert_eq!(local_compressed_size as u64, compressed_size);
+                            }
+                            assert!(!has_descriptor);
+                        }
+                        LocalHeaderSizes::DataDescriptorOnly => {
+                            assert_ne!(flags & (1 << 3), 0);
+                            assert_eq!(local_crc32, 0);
+                            if !large_file {
+                                assert_eq!(local_sizes, [0; 8]);
+                            }
+                            assert!(has_descriptor);
+                            let descriptor = &bytes[data_end + 4..];
+                            assert_eq!(descriptor[..4], crc32.to_le_bytes());
+                            let descriptor_compressed_size = if large_file {
+                                u64::from_le_bytes(descriptor[4..12].try_into().unwrap())
+                            } else {
+                                u32::from_le_bytes(descriptor[4..8].try_into().unwrap()) as u64
+                            };
+                            assert_eq!(descriptor_compressed_size, compressed_size);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn collect_then_write_sorted() -> ZipResult<()> {
+        let options = SimpleFileOptions::default().last_modified_time(DateTime::default());
+        let write = |names: &[&str], in_memory: bool| -> ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new_buffer().collect_then_write_sorted();
+            if !in_memory {
+                writer.sort_on_finish = Some(|writer| writer.write_sorted(0));
+            }
+            for name in names {
+                writer.start_file(*name, options)?;
+                writer.write_all(name.repeat(10).as_bytes())?;
+            }
+            writer.shallow_copy_file("c.txt", "b_copy.txt")?;
+            writer.finish_to_vec()
+        };
+        let bytes = write(&["c.txt", "a/z.txt", "b.txt", "a/a.txt"], true)?;
+        assert_eq!(
+            bytes,
+            write(&["a/a.txt", "b.txt", "c.txt", "a/z.txt"], true)?
+        );
+        assert_eq!(
+            bytes,
+            write(&["b.txt", "a/z.txt", "a/a.txt", "c.txt"], false)?
+        );
+
+        // Finishing with a separate central directory sorts the files too
+        let mut writer = ZipWriter::new_buffer().collect_then_write_sorted();
+        for name in ["c.txt", "a/z.txt", "b.txt", "a/a.txt"] {
+            writer.start_file(name, options)?;
+            writer.write_all(name.repeat(10).as_bytes())?;
+        }
+        writer.shallow_copy_file("c.txt", "b_copy.txt")?;
+        let mut central_directory = Vec::new();
+        let mut separate = writer
+            .finish_with_separate_central_directory(&mut central_directory)?
+            .into_inner();
+        separate.extend_from_slice(&central_directory);
+        assert_eq!(separate, bytes);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["a/a.txt", "a/z.txt", "b.txt", "b_copy.txt", "c.txt"]
+        );
+        let mut header_starts = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            let original_name = if file.name() == "b_copy.txt" {
+                "c.txt"
+            } else {
+                file.name()
+            };
+            assert_eq!(contents, original_name.repeat(10));
+            header_starts.push(file.header_start());
+        }
+        // The copy shares the local header of c.txt, which is placed as b_copy.txt
+        assert!(header_starts[..4].windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(header_starts[3], header_starts[4]);
+        Ok(())
+    }
+
+    #[cfg(feature = "deflate-flate2")]
+    #[test]
+    fn deflate_level_hint() -> ZipResult<()> {
+        use crate::DeflateLevelHint;
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("default", deflated)?;
+        writer.start_file("maximum", deflated.compression_level(Some(9)))?;
+        writer.start_file("fast", deflated.compression_level(Some(1)))?;
+        writer.start_file(
+            "override",
+            deflated
+                .compression_level(Some(9))
+                .deflate_level_hint(Some(DeflateLevelHint::SuperFast)),
+        )?;
+        writer.start_file(
+            "stored",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        // Copies keep the hint of the original
+        writer.deep_copy_file("override", "deep_copy")?;
+        let mut source = ZipArchive::new(Cursor::new(writer.finish()?.into_inner()))?;
+        let mut writer =
+            ZipWriter::new_append(Cursor::new(source.clone().into_inner().into_inner()))?;
+        writer.raw_copy_file_rename(source.by_name("fast")?, "raw_copy")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut reread = ZipArchive::new(Cursor::new(archive.clone().into_inner().into_inner()))?;
+        for (name, expected) in [
+            ("default", Some(DeflateLevelHint::Normal)),
+            ("maximum", Some(DeflateLevelHint::Maximum)),
+            ("fast", Some(DeflateLevelHint::Fast)),
+            ("override", Some(DeflateLevelHint::SuperFast)),
+            ("stored", None),
+            ("deep_copy", Some(DeflateLevelHint::SuperFast)),
+            ("raw_copy", Some(DeflateLevelHint::Fast)),
+        ] {
+            assert_eq!(
+                archive.by_name(name)?.deflate_level_hint(),
+                expected,
+                "{name}"
+            );
+            assert_eq!(
+                reread.by_name(name)?.deflate_level_hint(),
+                expected,
+                "{name}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn new_append_with_index() -> ZipResult<()> {
+        use super::IndexSnapshot;
+        use std::fs::File;
+        let dir = tempdir::TempDir::new("new_append_with_index")?;
+        let path = dir.path().join("archive.zip");
+        let partial_path = dir.path().join("partial.zip");
+        let options = SimpleFileOptions::default();
+        let mut writer = ZipWriter::new(File::create(&path)?);
+        writer.set_comment("resumed");
+        writer.start_file("first.txt", options)?;
+        writer.write_all(b"first")?;
+        writer.add_directory("dir/", options)?;
+        writer.start_file(
+            "dir/second.txt",
+            options.local_header_sizes(LocalHeaderSizes::DataDescriptorOnly),
+        )?;
+        writer.write_all(b"second")?;
+        writer.start_file("interrupted.txt", options)?;
+        writer.write_all(&[b'x'; 1000])?;
+        let mut sidecar = Vec::new();
+        writer.export_index()?.write_to(&mut sidecar)?;
+        writer.flush()?;
+        std::fs::copy(&path, &partial_path)?;
+        drop(writer);
+
+        let snapshot = IndexSnapshot::read_from(&mut Cursor::new(sidecar))?;
+        assert_eq!(snapshot.len(), 3);
+        let partial = File::options().read(true).write(true).open(&partial_path)?;
+        assert!(partial.metadata()?.len() >= snapshot.end());
+        let mut writer = ZipWriter::new_append_with_index(partial, snapshot.clone())?;
+        writer.start_file("third.txt", options)?;
+        writer.write_all(b"third")?;
+        writer.finish()?;
+
+        let mut archive = ZipArchive::new(File::open(&partial_path)?)?;
+        assert_eq!(archive.comment(), b"resumed");
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["first.txt", "dir/", "dir/second.txt", "third.txt"]
+        );
+        for (name, contents) in [
+            ("first.txt", "first"),
+            ("dir/second.txt", "second"),
+            ("third.txt", "third"),
+        ] {
+            let mut read_back = String::new();
+            archive.by_name(name)?.read_to_string(&mut read_back)?;
+            assert_eq!(read_back, contents);
+        }
+
+        // The snapshot doesn't describe a stream that's shorter than when it was taken
+        let mut short = std::fs::read(&partial_path)?;
+        short.truncate(snapshot.end() as usize - 1);
+        assert!(ZipWriter::new_append_with_index(Cursor::new(short), snapshot).is_err());
+        assert!(IndexSnapshot::read_from(&mut Cursor::new(b"not an index")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn version_needed() -> ZipResult<()> {
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("computed", options.large_file(true))?;
+        writer.start_file("raised", options.version_needed(63))?;
+        writer.start_file("lowered", options.large_file(true).version_needed(20))?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        for (name, expected) in [("computed", 45), ("raised", 63), ("lowered", 20)] {
+            let file = archive.by_name(name)?;
+            let local = file.header_start() as usize + 4;
+            let central = file.central_header_start() as usize + 6;
+            for start in [local, central] {
+                let written = u16::from_le_bytes([bytes[start], bytes[start + 1]]);
+                assert_eq!(written, expected, "{name}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn new_streaming() -> ZipResult<()> {
+        let mut writer = ZipWriter::new_streaming(Vec::new());
+        writer.start_file(
+            "stored.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"stored contents")?;
+        writer.add_directory("dir/", SimpleFileOptions::default())?;
+        writer.start_file("dir/default.txt", SimpleFileOptions::default())?;
+        writer.write_all(&b"default contents, ".repeat(100))?;
+        writer.start_file("aborted.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"never listed")?;
+        writer.abort_file()?;
+        writer.add_symlink("link", "stored.txt", SimpleFileOptions::default())?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.len(), 4);
+        let mut contents = String::new();
+        let mut file = archive.by_name("stored.txt")?;
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "stored contents");
+        drop(file);
+        contents.clear();
+        archive
+            .by_name("dir/default.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "default contents, ".repeat(100));
+        assert!(archive.by_name("dir/")?.is_dir());
+        assert!(archive.by_name("link")?.is_symlink());
+        assert!(archive.by_name("aborted.txt").is_err());
+        for i in 0..archive.len() {
+            assert!(archive.by_index_raw(i)?.data.using_data_descriptor);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_parameters() -> ZipResult<()> {
+        let contents: Vec<u8> = (0..1 << 20).map(|i: u32| (i % 251) as u8).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, window_log) in [("default.bin", None), ("window.bin", Some(20))] {
+            let options = SimpleFileOptions::default().zstd_parameters(19, window_log)?;
+            writer.start_file(name, options)?;
+            writer.write_all(&contents)?;
+        }
+        let mut archive = writer.finish_into_readable()?;
+        for name in ["default.bin", "window.bin"] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.compression(), CompressionMethod::Zstd);
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert!(read == contents, "{name}");
+        }
+
+        for (level, window_log) in [(23, None), (-200_000, None), (3, Some(9)), (3, Some(32))] {
+            assert!(matches!(
+                SimpleFileOptions::default().zstd_parameters(level, window_log),
+                Err(InvalidArchive(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_compression_level() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for method in crate::SUPPORTED_COMPRESSION_METHODS {
+            let level = method.clamp_level(1000);
+            let result = writer.start_file(
+                method.to_string(),
+                options
+                    .compression_method(*method)
+                    .compression_level(Some(1000)),
+            );
+            // Deflate64 can't be written at any level
+            if level.is_some() || *method == Stored {
+                assert!(
+                    matches!(result, Err(crate::result::ZipError::UnsupportedArchive(_))),
+                    "{method}"
+                );
+            }
+            if let Some(level) = level {
+                writer.start_file(
+                    method.to_string(),
+                    options
+                        .compression_method(*method)
+                        .compression_level(Some(level)),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn finish_with_offsets() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("first", options)?;
+        writer.write_all(b"first contents")?;
+        writer.insert_block_before_central_directory(b"opaque block");
+        writer.set_comment("archive comment");
+        let (inner, offsets) = writer.finish_with_offsets()?;
+        let bytes = inner.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        let central_start = archive.by_index_raw(0)?.central_header_start();
+        assert_eq!(offsets.central_start, central_start);
+        let eocd_start = offsets.eocd_start as usize;
+        assert_eq!(bytes[eocd_start..eocd_start + 4], *b"PK\x05\x06");
+        assert_eq!(offsets.total_len, bytes.len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn add_directory_twice() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("a/b/", SimpleFileOptions::default())?;
+        writer.start_file("a/b/c.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        writer.add_directory("a/b", SimpleFileOptions::default())?;
+        assert!(!writer.is_writing_file());
+        writer.add_directory("a/b/", SimpleFileOptions::default())?;
+        let mut writer = ZipWriter::new_append(writer.finish()?)?;
+        writer.add_directory("a/b/", SimpleFileOptions::default())?;
+        let archive = writer.finish_into_readable()?;
+        let names: Vec<_> = archive.file_names().collect();
+        assert_eq!(names, ["a/b/", "a/b/c.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn add_extra_field() -> ZipResult<()> {
+        use super::ExtraFieldLocation;
+
+        let mut options = FullFileOptions::default()
+            .compression_method(Stored)
+            .with_alignment(64);
+        options.add_extra_field(0xcafe, b"local", ExtraFieldLocation::LocalOnly)?;
+        options.add_extra_field(0xbeef, b"central", ExtraFieldLocation::CentralOnly)?;
+        options.add_extra_field(0xf00d, b"both", ExtraFieldLocation::Both)?;
+        for header_id in [0x0001, 0x6375, 0x7075, 0x9901] {
+            assert!(options
+                .add_extra_field(header_id, b"", ExtraFieldLocation::Both)
+                .is_err());
+        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file", options)?;
+        writer.write_all(b"contents")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let has_field = |extra: &[u8], header_id: u16, data: &[u8]| {
+            let mut field = header_id.to_le_bytes().to_vec();
+            field.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            field.extend_from_slice(data);
+            extra.windows(field.len()).any(|window| window == field)
+        };
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        let mut file = archive.by_index(0)?;
+        let central = file.extra_data().unwrap();
+        assert!(has_field(central, 0xbeef, b"central"));
+        assert!(has_field(central, 0xf00d, b"both"));
+        assert!(!has_field(central, 0xcafe, b"local"));
+        let data_start = file.data_start() as usize;
+        assert_eq!(data_start % 64, 0);
+        let local = &bytes[30 + "file".len()..data_start];
+        assert!(has_field(local, 0xcafe, b"local"));
+        assert!(has_field(local, 0xf00d, b"both"));
+        assert!(!has_field(local, 0xbeef, b"central"));
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "contents");
+        Ok(())
+    }
+
+    #[test]
+    fn progress_callback() -> ZipResult<()> {
+        use std::sync::{Arc, Mutex};
+
+        let mut state = 1u32;
+        let contents: Vec<u8> = (0..1 << 20)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 24) as u8
+            })
+            .collect();
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let reports_clone = reports.clone();
+        writer.set_progress_callback(move |progress| {
+            reports_clone.lock().unwrap().push((
+                progress.name.to_owned(),
+                progress.input_bytes,
+                progress.output_bytes,
+            ));
+        });
+        writer.start_file("large.bin", SimpleFileOptions::default())?;
+        io::copy(&mut contents.as_slice(), &mut writer)?;
+        let compressed_size = writer
+            .finish_into_readable()?
+            .by_index(0)?
+            .compressed_size();
+
+        let reports = reports.lock().unwrap();
+        assert!(reports.len() > 1);
+        assert!(reports.iter().all(|(name, ..)| name == "large.bin"));
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].1 < w[1].1 && w[0].2 <= w[1].2));
+        let (_, input_bytes, output_bytes) = reports.last().unwrap();
+        assert_eq!(*input_bytes, contents.len() as u64);
+        assert!(*output_bytes > 0 && *output_bytes <= compressed_size);
+        Ok(())
+    }
 }
@@ -27,7 +27,7 @@ impl Debug for ZipCryptoKeys {
             use std::hash::Hasher;
             let mut t = DefaultHasher::new();
             self.hash(&mut t);
-            return f.write_fmt(format_args!("ZipCryptoKeys(hash {})", t.finish()));
+            f.write_fmt(format_args!("ZipCryptoKeys(hash {})", t.finish()))
         }
         #[cfg(any(test, fuzzing))]
         return f.write_fmt(format_args!(
@@ -1,7 +1,10 @@
 #![cfg(feature = "aes-crypto")]
 
 use std::io::{self, Read, Write};
-use zip::{result::ZipError, write::SimpleFileOptions, AesMode, CompressionMethod, ZipArchive};
+use zip::{
+    result::ZipError, write::SimpleFileOptions, AesMode, AesVendorVersion, CompressionMethod,
+    ZipArchive,
+};
 
 const SECRET_CONTENT: &str = "Lorem ipsum dolor sit amet";
 
@@ -152,3 +155,124 @@ fn test_extract_encrypted_file<R: io::Read + io::Seek>(
         assert_eq!(SECRET_CONTENT, content);
     }
 }
+
+#[test]
+fn aes256_deflated_from_stream() {
+    use zip::read::read_encrypted_zipfile_from_stream;
+
+    let bytes = {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+
+        zip.start_file(
+            "test.txt",
+            SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .with_aes_encryption(AesMode::Aes256, "some password"),
+        )
+        .unwrap();
+        zip.write_all(SECRET_CONTENT.as_bytes()).unwrap();
+
+        zip.finish().unwrap().into_inner()
+    };
+
+    let mut stream = bytes.as_slice();
+    match read_encrypted_zipfile_from_stream(&mut stream, b"other password") {
+        Err(ZipError::InvalidPassword) => {}
+        Err(err) => panic!("Expected invalid password error, got: {err:?}"),
+        Ok(_) => panic!("Expected invalid password error, got a file"),
+    }
+
+    let mut stream = bytes.as_slice();
+    let mut file = read_encrypted_zipfile_from_stream(&mut stream, b"some password")
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.name(), "test.txt");
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(SECRET_CONTENT, content);
+}
+
+#[test]
+fn aes_vendor_version() {
+    let mut v = Vec::new();
+    v.extend_from_slice(include_bytes!("data/aes_archive.zip"));
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+    let file = archive
+        .by_name_decrypt("secret_data_256", PASSWORD)
+        .expect("couldn't find file in archive");
+    assert_eq!(file.aes_vendor_version(), Some(AesVendorVersion::Ae2));
+    assert_eq!(file.aes_crc_is_meaningful(), Some(false));
+    assert_eq!(file.crc32(), 0);
+    drop(file);
+
+    // The writer only uses AE-2 for files too short to hide behind their CRC-32
+    let cursor = {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, "some password");
+        zip.start_file("long.txt", options).unwrap();
+        zip.write_all(SECRET_CONTENT.as_bytes()).unwrap();
+        zip.start_file("short.txt", options).unwrap();
+        zip.write_all(b"short").unwrap();
+        zip.start_file("plain.txt", SimpleFileOptions::default())
+            .unwrap();
+        zip.finish().unwrap()
+    };
+    let mut archive = ZipArchive::new(cursor).expect("couldn't open test zip file");
+    let file = archive
+        .by_name_decrypt("long.txt", b"some password")
+        .unwrap();
+    assert_eq!(file.aes_vendor_version(), Some(AesVendorVersion::Ae1));
+    assert_eq!(file.aes_crc_is_meaningful(), Some(true));
+    assert_eq!(file.crc32(), crc32fast::hash(SECRET_CONTENT.as_bytes()));
+    drop(file);
+    let file = archive
+        .by_name_decrypt("short.txt", b"some password")
+        .unwrap();
+    assert_eq!(file.aes_vendor_version(), Some(AesVendorVersion::Ae2));
+    assert_eq!(file.aes_crc_is_meaningful(), Some(false));
+    drop(file);
+    let file = archive.by_name("plain.txt").unwrap();
+    assert_eq!(file.aes_vendor_version(), None);
+    assert_eq!(file.aes_crc_is_meaningful(), None);
+}
+
+#[test]
+fn aes_vendor_version_option() {
+    let cursor = {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, "some password");
+        zip.start_file(
+            "short.txt",
+            options.aes_vendor_version(AesVendorVersion::Ae1),
+        )
+        .unwrap();
+        zip.write_all(b"short").unwrap();
+        zip.start_file(
+            "long.txt",
+            options.aes_vendor_version(AesVendorVersion::Ae2),
+        )
+        .unwrap();
+        zip.write_all(SECRET_CONTENT.as_bytes()).unwrap();
+        zip.finish().unwrap()
+    };
+    let mut archive = ZipArchive::new(cursor).expect("couldn't open test zip file");
+    let mut file = archive
+        .by_name_decrypt("short.txt", b"some password")
+        .unwrap();
+    assert_eq!(file.aes_vendor_version(), Some(AesVendorVersion::Ae1));
+    assert_eq!(file.crc32(), crc32fast::hash(b"short"));
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "short");
+    drop(file);
+    let mut file = archive
+        .by_name_decrypt("long.txt", b"some password")
+        .unwrap();
+    assert_eq!(file.aes_vendor_version(), Some(AesVendorVersion::Ae2));
+    assert_eq!(file.crc32(), 0);
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, SECRET_CONTENT);
+}
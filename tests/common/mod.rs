@@ -0,0 +1,33 @@
+//! Archives shared by several integration tests.
+
+use std::io::Cursor;
+use std::sync::OnceLock;
+use zip::result::ZipResult;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Stored archives of empty entries named by their index, with 65,534, 65,535 and 65,536
+/// entries: just below, at and just above the 0xFFFF limit of the ZIP32 end of central
+/// directory record. Writing that many entries is slow in debug builds, so the first archive is
+/// built once, and each of the others by appending one entry to the one before.
+pub fn archives_around_entry_limit() -> &'static [Vec<u8>; 3] {
+    static ARCHIVES: OnceLock<[Vec<u8>; 3]> = OnceLock::new();
+    ARCHIVES.get_or_init(|| {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let append = |bytes: Vec<u8>, names: std::ops::Range<usize>| -> ZipResult<Vec<u8>> {
+            let mut writer = if bytes.is_empty() {
+                ZipWriter::new(Cursor::new(bytes))
+            } else {
+                ZipWriter::new_append(Cursor::new(bytes))?
+            };
+            for i in names {
+                writer.start_file(format!("{i}"), options)?;
+            }
+            Ok(writer.finish()?.into_inner())
+        };
+        let below = append(Vec::new(), 0..65534).unwrap();
+        let at = append(below.clone(), 65534..65535).unwrap();
+        let above = append(at.clone(), 65535..65536).unwrap();
+        [below, at, above]
+    })
+}
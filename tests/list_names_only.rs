@@ -0,0 +1,54 @@
+//! Checks that listing names only allocates less than opening the archive.
+
+mod common;
+
+use common::archives_around_entry_limit;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zip::result::ZipResult;
+use zip::ZipArchive;
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the result of `f` and the number of bytes it allocated in total
+fn allocated_by<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    let result = f();
+    (result, ALLOCATED.load(Ordering::SeqCst) - before)
+}
+
+#[test]
+fn list_names_only_64k_files() -> ZipResult<()> {
+    let bytes = &archives_around_entry_limit()[2];
+
+    let (names, names_allocated) =
+        allocated_by(|| ZipArchive::list_names_only(Cursor::new(bytes.as_slice())));
+    let (archive, archive_allocated) =
+        allocated_by(|| ZipArchive::new(Cursor::new(bytes.as_slice())));
+    let (names, archive) = (names?, archive?);
+
+    assert_eq!(names.len(), archive.len());
+    assert!(names.iter().map(|name| &**name).eq(archive.file_names()));
+    assert!(
+        names_allocated * 4 < archive_allocated,
+        "listing names allocated {names_allocated} bytes, opening allocated {archive_allocated}"
+    );
+    Ok(())
+}
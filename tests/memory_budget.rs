@@ -0,0 +1,112 @@
+//! Checks that extracting and streaming large entries use a bounded amount of heap memory.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{Cursor, Read, Write};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use zip::read::Config;
+use zip::result::ZipResult;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+struct CountingAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+/// Held while measuring, so that tests running in parallel don't count toward each other's peak
+static MEASURING: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ENTRY_SIZE: usize = 16 * 1024 * 1024;
+const IO_BUFFER_SIZE: usize = 16 * 1024;
+/// Room for the deflate decoder, the copy buffer and bookkeeping such as paths
+const BUDGET: usize = 512 * 1024;
+
+#[test]
+fn extract_large_entries_within_budget() -> ZipResult<()> {
+    let _measuring = MEASURING.lock().unwrap();
+    let contents: Vec<u8> = (0..ENTRY_SIZE).map(|i| (i % 251) as u8).collect();
+    #[allow(unused_mut)]
+    let mut methods = vec![CompressionMethod::Stored];
+    #[cfg(feature = "deflate-flate2")]
+    methods.push(CompressionMethod::Deflated);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for method in &methods {
+        let options = SimpleFileOptions::default()
+            .compression_method(*method)
+            .large_file(true);
+        writer.start_file(format!("{method}.bin"), options)?;
+        writer.write_all(&contents)?;
+    }
+    let config = Config::default().io_buffer_size(NonZeroUsize::new(IO_BUFFER_SIZE));
+    let mut archive = ZipArchive::with_config(config, writer.finish()?)?;
+    drop(contents);
+    let dir = tempdir::TempDir::new("memory_budget")?;
+
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    archive.extract(dir.path())?;
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+
+    assert!(peak >= IO_BUFFER_SIZE, "copy buffer wasn't used: {peak}");
+    assert!(peak < BUDGET, "peak heap usage {peak} exceeds {BUDGET}");
+    for method in &methods {
+        let extracted = std::fs::metadata(dir.path().join(format!("{method}.bin")))?;
+        assert_eq!(extracted.len(), ENTRY_SIZE as u64);
+    }
+    Ok(())
+}
+
+#[test]
+fn stream_large_stored_entry_within_budget() -> ZipResult<()> {
+    let _measuring = MEASURING.lock().unwrap();
+    let chunk: Vec<u8> = (0..IO_BUFFER_SIZE).map(|i| (i % 251) as u8).collect();
+    // A `Vec` is a `Write`-only sink; reserving its capacity up front keeps the output itself
+    // out of the measurement
+    let sink = Vec::with_capacity(ENTRY_SIZE + 1024);
+
+    let baseline = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    let mut writer = ZipWriter::new_streaming(sink);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    writer.start_file("large.bin", options)?;
+    for _ in 0..ENTRY_SIZE / IO_BUFFER_SIZE {
+        writer.write_all(&chunk)?;
+    }
+    let bytes = writer.finish()?.into_inner();
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+    assert!(peak < BUDGET, "peak heap usage {peak} exceeds {BUDGET}");
+
+    let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+    let mut file = archive.by_index(0)?;
+    assert_eq!(file.compression(), CompressionMethod::Stored);
+    assert_eq!(file.size(), ENTRY_SIZE as u64);
+    assert_eq!(file.compressed_size(), ENTRY_SIZE as u64);
+    let descriptor_start = file.data_start() as usize + ENTRY_SIZE;
+    let descriptor = &bytes[descriptor_start..descriptor_start + 16];
+    assert_eq!(descriptor[..4], 0x08074b50u32.to_le_bytes());
+    assert_eq!(descriptor[4..8], file.crc32().to_le_bytes());
+    assert_eq!(descriptor[8..12], (ENTRY_SIZE as u32).to_le_bytes());
+    assert_eq!(descriptor[12..], (ENTRY_SIZE as u32).to_le_bytes());
+    // Reading to the end checks the CRC-32
+    let mut contents = Vec::with_capacity(ENTRY_SIZE);
+    file.read_to_end(&mut contents)?;
+    assert!(contents.chunks(IO_BUFFER_SIZE).all(|c| c == chunk));
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! Archives with entry counts around the 0xFFFF limit of the ZIP32 end of central directory.
+
+mod common;
+
+use common::archives_around_entry_limit;
+use std::io::{Cursor, Read};
+use zip::result::ZipResult;
+use zip::ZipArchive;
+
+const EOCD_LEN: usize = 22;
+const ZIP64_EOCD_LEN: usize = 56;
+const ZIP64_LOCATOR_LEN: usize = 20;
+
+/// The "total number of entries" field of the ZIP32 end of central directory record.
+fn zip32_entry_count(bytes: &[u8]) -> u16 {
+    let eocd = &bytes[bytes.len() - EOCD_LEN..];
+    assert_eq!(eocd[..4], [b'P', b'K', 5, 6]);
+    u16::from_le_bytes([eocd[10], eocd[11]])
+}
+
+fn has_zip64_eocd_locator(bytes: &[u8]) -> bool {
+    let locator_start = bytes.len() - EOCD_LEN - ZIP64_LOCATOR_LEN;
+    bytes[locator_start..locator_start + 4] == [b'P', b'K', 6, 7]
+}
+
+fn check_entries(bytes: Vec<u8>, count: usize) -> ZipResult<()> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    assert_eq!(archive.len(), count);
+    for i in [0, count / 2, count - 1] {
+        let mut file = archive.by_index(i)?;
+        assert_eq!(file.name(), i.to_string());
+        assert_eq!(file.read(&mut [0; 1])?, 0);
+    }
+    Ok(())
+}
+
+#[test]
+fn entries_below_sentinel() -> ZipResult<()> {
+    let bytes = archives_around_entry_limit()[0].clone();
+    assert_eq!(zip32_entry_count(&bytes), 65534);
+    assert!(!has_zip64_eocd_locator(&bytes));
+    check_entries(bytes, 65534)
+}
+
+#[test]
+fn entries_at_sentinel() -> ZipResult<()> {
+    // 0xFFFF in the ZIP32 record means the real count is in the ZIP64 record, so one has to
+    // be written even though the count itself would fit.
+    let bytes = archives_around_entry_limit()[1].clone();
+    assert_eq!(zip32_entry_count(&bytes), 0xFFFF);
+    assert!(has_zip64_eocd_locator(&bytes));
+    check_entries(bytes, 65535)
+}
+
+#[test]
+fn entries_at_sentinel_without_zip64_record() -> ZipResult<()> {
+    // Some writers store exactly 65535 entries without a ZIP64 record.
+    let mut bytes = archives_around_entry_limit()[1].clone();
+    let eocd_start = bytes.len() - EOCD_LEN;
+    bytes.drain(eocd_start - ZIP64_EOCD_LEN - ZIP64_LOCATOR_LEN..eocd_start);
+    assert_eq!(zip32_entry_count(&bytes), 0xFFFF);
+    assert!(!has_zip64_eocd_locator(&bytes));
+    check_entries(bytes, 65535)
+}
+
+#[test]
+fn entries_above_sentinel() -> ZipResult<()> {
+    let bytes = archives_around_entry_limit()[2].clone();
+    assert_eq!(zip32_entry_count(&bytes), 0xFFFF);
+    assert!(has_zip64_eocd_locator(&bytes));
+    check_entries(bytes, 65536)
+}
@@ -46,3 +46,50 @@ fn correctly_handle_cde_on_window() {
 
     assert_eq!(archive.comment(), "short.".as_bytes());
 }
+
+/// A comment can contain the end-of-central-directory signature, or even a whole record that
+/// parses, as long as the real record's comment covers it.
+#[test]
+fn correctly_handle_eocd_signature_in_comment() {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const SIGNATURE: &[u8] = b"PK\x05\x06";
+    // An empty archive's record, whose comment length covers the rest of the outer comment
+    let mut fake_eocd = SIGNATURE.to_vec();
+    fake_eocd.extend_from_slice(&[0; 16]);
+    fake_eocd.extend_from_slice(&2u16.to_le_bytes());
+    let comments = [
+        [b"before ", SIGNATURE, b" after"].concat(),
+        SIGNATURE.to_vec(),
+        [&fake_eocd[..], b"xx"].concat(),
+        [b"prefix", &fake_eocd[..], b"xx"].concat(),
+    ];
+    for comment in comments {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.set_raw_comment(comment.clone().into());
+        writer
+            .start_file("file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        let archive = ZipArchive::new(writer.finish().unwrap()).expect("couldn't open test zip");
+
+        assert_eq!(archive.comment(), comment);
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["file.txt"]);
+    }
+}
+
+// The end-of-central-directory record declares a 100-byte comment, but the file ends 17 bytes
+// into it.
+#[test]
+fn correctly_handle_truncated_comment() {
+    let mut v = Vec::new();
+    v.extend_from_slice(include_bytes!("../tests/data/truncated_comment.zip"));
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+
+    assert_eq!(archive.comment(), b"truncated comment");
+    let mut contents = String::new();
+    io::Read::read_to_string(&mut archive.by_name("file.txt").unwrap(), &mut contents).unwrap();
+    assert_eq!(contents, "contents");
+}
@@ -119,3 +119,38 @@ fn buffered_read() {
     let mut data = Vec::new();
     file.read_to_end(&mut data).unwrap();
 }
+
+#[test]
+fn encrypted_file_from_stream() {
+    use std::io::Read;
+    use zip::read::{read_encrypted_zipfile_from_stream, read_zipfile_from_stream};
+
+    let mut stream = ZIP_CRYPTO_FILE;
+    match read_zipfile_from_stream(&mut stream) {
+        Err(ZipError::UnsupportedArchive(_)) => (),
+        Err(e) => panic!("Unexpected error: {e:?}"),
+        Ok(_) => panic!("Error: Successfully read encrypted file without password?!"),
+    }
+
+    let mut stream = ZIP_CRYPTO_FILE;
+    let mut file = read_encrypted_zipfile_from_stream(&mut stream, b"test")
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.name(), "test.txt");
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    assert_eq!(data, "abcdefghijklmnopqrstuvwxyz123456789".as_bytes());
+    drop(file);
+    assert!(read_encrypted_zipfile_from_stream(&mut stream, b"test")
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn read_name_decrypt() {
+    let mut archive = zip::ZipArchive::new(Cursor::new(ZIP_CRYPTO_FILE)).unwrap();
+    assert_eq!(
+        archive.read_name_decrypt("test.txt", b"test").unwrap(),
+        b"abcdefghijklmnopqrstuvwxyz123456789"
+    );
+}
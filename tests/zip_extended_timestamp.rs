@@ -16,4 +16,54 @@ fn test_extended_timestamp() {
             }
         }
     }
+    let file = archive.by_name("test.txt").unwrap();
+    assert_eq!(
+        file.extended_timestamp().unwrap().mod_time(),
+        Some(1714635025)
+    );
+    assert!(file.ntfs_times().is_none());
+}
+
+#[test]
+fn test_modified_time_is_utc() -> zip::result::ZipResult<()> {
+    use std::io::Write;
+    use zip::write::{FullFileOptions, SimpleFileOptions};
+
+    let mut v = Vec::new();
+    v.extend_from_slice(include_bytes!("../tests/data/extended_timestamp.zip"));
+    let mut archive = ZipArchive::new(io::Cursor::new(v))?;
+    assert!(archive.by_name("test.txt")?.modified_time_is_utc());
+
+    // NTFS field with only the file times attribute: reserved, tag 1, size 24, 3 FILETIMEs
+    let mut ntfs = vec![0u8; 4];
+    ntfs.extend_from_slice(&1u16.to_le_bytes());
+    ntfs.extend_from_slice(&24u16.to_le_bytes());
+    ntfs.extend_from_slice(&[0x11; 24]);
+    // The NTFS header ID is reserved for writing, so a placeholder is patched afterwards
+    const PLACEHOLDER_ID: u16 = 0xbeef;
+    let mut ntfs_options = FullFileOptions::default();
+    ntfs_options.add_extra_data(PLACEHOLDER_ID, ntfs.into_boxed_slice(), true)?;
+
+    let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer.start_file("dos_time_only.txt", SimpleFileOptions::default())?;
+    writer.write_all(b"no extra fields")?;
+    writer.start_file("ntfs.txt", ntfs_options)?;
+    writer.write_all(b"NTFS timestamps")?;
+    let mut bytes = writer.finish()?.into_inner();
+    let placeholder = [&PLACEHOLDER_ID.to_le_bytes()[..], &32u16.to_le_bytes()].concat();
+    let field_start = bytes
+        .windows(4)
+        .position(|window| window == placeholder)
+        .unwrap();
+    bytes[field_start..field_start + 2].copy_from_slice(&0x000au16.to_le_bytes());
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes))?;
+    assert!(!archive.by_name("dos_time_only.txt")?.modified_time_is_utc());
+    assert!(archive.by_name("ntfs.txt")?.modified_time_is_utc());
+    let ntfs = archive.by_name("ntfs.txt")?;
+    let times = ntfs.ntfs_times().unwrap();
+    assert_eq!(times.mtime(), 0x1111_1111_1111_1111);
+    assert_eq!(times.atime(), 0x1111_1111_1111_1111);
+    assert_eq!(times.ctime(), 0x1111_1111_1111_1111);
+    assert!(ntfs.extended_timestamp().is_none());
+    Ok(())
 }